@@ -2,23 +2,91 @@
 #![no_main]
 
 use core::arch::asm;
+use core::fmt::Write;
 use core::panic::PanicInfo;
 
-use core64_util::{BootInfo, graphics::Color};
+use core64_util::{
+    boot::{
+        entropy::EntropySeed,
+        log::LoaderLog,
+        time::{BootTimestamps, WallClockTime},
+        variable::UefiRuntime,
+    },
+    graphics::{
+        splash::{BootSplash, PROGRESS_MAX},
+        Color,
+    },
+    serial::{SerialPort, COM1},
+    BootInfo,
+};
 
 use crate::video::framebuffer::RawFrameBuffer;
 
 mod video;
 
+core64_util::boot_requests! {
+    stack_size: 1024 * 1024,
+}
+
 #[no_mangle]
 pub extern "sysv64" fn kernel_main(boot_info: &BootInfo) -> ! {
-    let framebuffer = RawFrameBuffer::from(boot_info.frame_buffer_metadata);
-    framebuffer.fill(Color::green());
+    let mut serial = unsafe { SerialPort::new(COM1) };
+    serial.init();
+    let _ = writeln!(serial, "Core64OS kernel started.");
+
+    // refuse to boot with boot info of an incompatible loader build
+    let boot_info = match boot_info.validate() {
+        Ok(boot_info) => boot_info,
+        Err(error) => panic!("Invalid boot info: {error}"),
+    };
+
+    // consume entropy seed, so that it does not linger in memory
+    let _entropy_seed = boot_info
+        .tag::<EntropySeed>()
+        .map(|entropy_seed| unsafe { entropy_seed.take() });
+
+    // replay loader messages, which have partially only been printed to the uefi console
+    if let Some(loader_log) = boot_info.tag::<LoaderLog>() {
+        let (older, newer) = unsafe { loader_log.contents() };
+        older
+            .iter()
+            .chain(newer)
+            .for_each(|byte| serial.write_byte(*byte));
+    }
+
+    if let Some(wall_clock_time) = boot_info.tag::<WallClockTime>() {
+        let _ = writeln!(serial, "Booted at {wall_clock_time}.");
+    }
+    if let Some(timestamps) = boot_info.tag::<BootTimestamps>() {
+        let _ = writeln!(serial, "Boot time breakdown:\n{timestamps}");
+    }
+
+    // complete the loader's boot splash or signal a successful boot
+    match boot_info.tag::<BootSplash>() {
+        Some(splash) => {
+            let mut splash = *splash;
+            unsafe { splash.set_progress(&boot_info.frame_buffer_metadata, PROGRESS_MAX) };
+        }
+        None => {
+            let framebuffer = RawFrameBuffer::from(boot_info.frame_buffer_metadata);
+            framebuffer.fill(Color::green());
+        }
+    }
+
+    // keep booting this kernel slot
+    if let Some(uefi_runtime) = boot_info.tag::<UefiRuntime>() {
+        if let Err(error) = unsafe { uefi_runtime.mark_boot_successful() } {
+            let _ = writeln!(serial, "Could not mark boot successful: {error}");
+        }
+    }
     hlt_loop();
 }
 
 #[panic_handler]
-fn panic(_info: &PanicInfo) -> ! {
+fn panic(info: &PanicInfo) -> ! {
+    // the port has been initialized by the loader or in kernel_main
+    let mut serial = unsafe { SerialPort::new(COM1) };
+    let _ = writeln!(serial, "Kernel panic: {info}");
     hlt_loop();
 }
 
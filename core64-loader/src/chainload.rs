@@ -0,0 +1,105 @@
+use alloc::{format, string::String, vec::Vec};
+
+use log::{info, warn};
+use uefi::{
+    prelude::BootServices,
+    proto::device_path::{
+        build::{media::FilePath, DevicePathBuilder},
+        DevicePath,
+    },
+    table::{boot::LoadImageSource, Boot, SystemTable},
+    CString16, Handle,
+};
+
+use core64_util::boot::variable::BOOT_ATTEMPTS_VARIABLE;
+
+use crate::{config::Config, file, variable};
+
+/// Starts the EFI applications selected by the configuration: the `chainload` application, or the `fallback` application once
+/// the core64 kernel failed to boot `fallback_after` consecutive times. Returns, if the core64 kernel should be booted, e.g.
+/// because the application exited or could not be started.
+pub(super) fn run_configured(
+    image_handle: Handle,
+    system_table: &SystemTable<Boot>,
+    config: &Config,
+) {
+    let boot_services = system_table.boot_services();
+    let Some(device) = file::image_device(image_handle, boot_services) else {
+        if config.chainload.is_some() || config.fallback.is_some() {
+            warn!("Cannot chainload: loader device is unknown.");
+        }
+        return;
+    };
+
+    if let Some(path) = &config.chainload {
+        if let Err(error) = chainload(image_handle, boot_services, device, path) {
+            warn!("{error} Booting core64 kernel instead.");
+        }
+    }
+
+    let Some(fallback) = &config.fallback else {
+        return;
+    };
+    let runtime_services = system_table.runtime_services();
+    let attempts = variable::get_u32(runtime_services, BOOT_ATTEMPTS_VARIABLE).unwrap_or_default();
+    if attempts >= config.fallback_after {
+        warn!("Core64 kernel failed to boot {attempts} time(s). Starting fallback {fallback}.");
+        // the next boot tries the core64 kernel again
+        variable::set_u32(runtime_services, BOOT_ATTEMPTS_VARIABLE, 0);
+        if let Err(error) = chainload(image_handle, boot_services, device, fallback) {
+            warn!("{error} Booting core64 kernel instead.");
+        }
+    }
+
+    // counts as failed, until the kernel resets the counter
+    let attempts = variable::get_u32(runtime_services, BOOT_ATTEMPTS_VARIABLE).unwrap_or_default();
+    variable::set_u32(runtime_services, BOOT_ATTEMPTS_VARIABLE, attempts + 1);
+}
+
+/// Loads and starts an EFI application from the filesystem of `device`. Returns once the application exits.
+pub(super) fn chainload(
+    image_handle: Handle,
+    boot_services: &BootServices,
+    device: Handle,
+    path: &str,
+) -> Result<(), String> {
+    let device_path = boot_services
+        .open_protocol_exclusive::<DevicePath>(device)
+        .map_err(|error| format!("Cannot open device path of {path}: {error}."))?;
+    let path_name = CString16::try_from(path).map_err(|_| format!("Invalid file path: {path}"))?;
+
+    // full device path of the file: device path of the filesystem followed by the file path
+    let mut buffer = Vec::new();
+    let file_path = device_path
+        .node_iter()
+        .try_fold(DevicePathBuilder::with_vec(&mut buffer), |builder, node| {
+            builder.push(&node)
+        })
+        .and_then(|builder| {
+            builder
+                .push(&FilePath {
+                    path_name: &path_name,
+                })?
+                .finalize()
+        })
+        .map_err(|error| format!("Cannot build device path of {path}: {error:?}."))?;
+
+    let child_handle = boot_services
+        .load_image(
+            image_handle,
+            LoadImageSource::FromDevicePath {
+                device_path: file_path,
+                from_boot_manager: false,
+            },
+        )
+        .map_err(|error| format!("Could not load {path}: {error}."))?;
+
+    info!("Starting {path}...");
+    // applications are unloaded by the firmware once they exit
+    boot_services
+        .start_image(child_handle)
+        .map_err(|error| format!("{path} exited with error: {error}."))?;
+
+    info!("{path} exited.");
+    Ok(())
+}
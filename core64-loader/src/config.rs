@@ -0,0 +1,146 @@
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use log::{info, warn};
+use uefi::{prelude::BootServices, Handle};
+
+use crate::file;
+
+/// Configuration file on the filesystem of the loader image. Lines have the form `key = value`, lines starting with `#` are
+/// ignored.
+const CONFIG_PATH: &str = "\\core64.cfg";
+/// Consecutive unsuccessful core64 boots before the fallback application is started
+const DEFAULT_FALLBACK_AFTER: u32 = 3;
+/// Consecutive unsuccessful boots of the active kernel slot before switching to the other slot
+const DEFAULT_MAX_BOOT_ATTEMPTS: u32 = 3;
+/// Manifest read from the source of the kernel image, if none is embedded into the loader
+const DEFAULT_MANIFEST_PATH: &str = "\\core64.sha256";
+
+/// Loader configuration. Missing files or keys select the defaults.
+#[derive(Clone, Debug)]
+pub(super) struct Config {
+    /// EFI application started instead of the core64 kernel (`chainload`)
+    pub(super) chainload: Option<String>,
+    /// EFI application started after `fallback_after` consecutive unsuccessful core64 boots (`fallback`)
+    pub(super) fallback: Option<String>,
+    pub(super) fallback_after: u32,
+    /// Comma-separated paths searched for the kernel image, if no slot is configured (`kernel_path`). Defaults to the built-in
+    /// search paths.
+    pub(super) kernel_paths: Vec<String>,
+    /// Kernel image of slot A (`kernel_a`). A/B slots are used, if both slots are configured.
+    pub(super) kernel_a: Option<String>,
+    /// Kernel image of slot B (`kernel_b`)
+    pub(super) kernel_b: Option<String>,
+    /// Consecutive unsuccessful boots of the active slot before switching to the other slot (`max_boot_attempts`)
+    pub(super) max_boot_attempts: u32,
+    /// Whether the kernel is always fetched via TFTP (`network_boot`). Otherwise TFTP is only used, if it is not found on disk.
+    pub(super) network_boot: bool,
+    /// IPv4 address of the TFTP server (`tftp_server`). Defaults to the boot server announced via DHCP.
+    pub(super) tftp_server: Option<[u8; 4]>,
+    /// Manifest in `sha256sum` format on the source of the kernel image, which the kernel and modules are verified against
+    /// (`manifest`). Its detached Ed25519 signature (64 raw bytes) is read from the same path with `.sig` appended.
+    pub(super) manifest: String,
+    /// BMP or QOI image on the filesystem of the loader image, shown centered while booting (`splash`)
+    pub(super) splash: Option<String>,
+    /// Whether the mappings of the kernel address space are logged before entering the kernel (`log_page_tables`)
+    pub(super) log_page_tables: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            chainload: None,
+            fallback: None,
+            fallback_after: DEFAULT_FALLBACK_AFTER,
+            kernel_paths: Vec::new(),
+            kernel_a: None,
+            kernel_b: None,
+            max_boot_attempts: DEFAULT_MAX_BOOT_ATTEMPTS,
+            network_boot: false,
+            tftp_server: None,
+            manifest: DEFAULT_MANIFEST_PATH.to_string(),
+            splash: None,
+            log_page_tables: false,
+        }
+    }
+}
+
+/// Reads the configuration from the filesystem of the loader image. Invalid entries are ignored.
+pub(super) fn load(image_handle: Handle, boot_services: &BootServices) -> Config {
+    let mut config = Config::default();
+    let Some(device) = file::image_device(image_handle, boot_services) else {
+        return config;
+    };
+    let Ok(contents) = file::read_file(boot_services, device, CONFIG_PATH) else {
+        return config;
+    };
+    let Ok(contents) = String::from_utf8(contents) else {
+        warn!("Ignoring {CONFIG_PATH}: not valid UTF-8.");
+        return config;
+    };
+
+    for line in contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+    {
+        let Some((key, value)) = line.split_once('=') else {
+            warn!("Ignoring invalid configuration line: {line}");
+            continue;
+        };
+        let value = value.trim();
+
+        match key.trim() {
+            "chainload" => config.chainload = Some(value.to_string()),
+            "fallback" => config.fallback = Some(value.to_string()),
+            "fallback_after" => match value.parse() {
+                Ok(fallback_after) => config.fallback_after = fallback_after,
+                Err(_) => warn!("Ignoring invalid fallback_after: {value}"),
+            },
+            "kernel_path" => {
+                config.kernel_paths = value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|path| !path.is_empty())
+                    .map(ToString::to_string)
+                    .collect()
+            }
+            "kernel_a" => config.kernel_a = Some(value.to_string()),
+            "kernel_b" => config.kernel_b = Some(value.to_string()),
+            "max_boot_attempts" => match value.parse() {
+                Ok(max_boot_attempts) => config.max_boot_attempts = max_boot_attempts,
+                Err(_) => warn!("Ignoring invalid max_boot_attempts: {value}"),
+            },
+            "network_boot" => match value.parse() {
+                Ok(network_boot) => config.network_boot = network_boot,
+                Err(_) => warn!("Ignoring invalid network_boot: {value}"),
+            },
+            "tftp_server" => match parse_ipv4(value) {
+                Some(tftp_server) => config.tftp_server = Some(tftp_server),
+                None => warn!("Ignoring invalid tftp_server: {value}"),
+            },
+            "manifest" => config.manifest = value.to_string(),
+            "splash" => config.splash = Some(value.to_string()),
+            "log_page_tables" => match value.parse() {
+                Ok(log_page_tables) => config.log_page_tables = log_page_tables,
+                Err(_) => warn!("Ignoring invalid log_page_tables: {value}"),
+            },
+            key => warn!("Ignoring unknown configuration key: {key}"),
+        }
+    }
+
+    info!("Loaded configuration from {CONFIG_PATH}.");
+    config
+}
+
+/// Parses an IPv4 address in dotted decimal notation
+fn parse_ipv4(value: &str) -> Option<[u8; 4]> {
+    let mut address = [0; 4];
+    let mut octets = value.split('.');
+    for octet in &mut address {
+        *octet = octets.next()?.parse().ok()?;
+    }
+    octets.next().is_none().then_some(address)
+}
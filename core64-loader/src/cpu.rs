@@ -0,0 +1,123 @@
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::arch::x86_64::{__cpuid, __cpuid_count};
+
+use log::{info, warn};
+use uefi::{
+    prelude::BootServices,
+    proto::pi::mp::MpServices,
+    table::{
+        cfg::{ACPI2_GUID, ACPI_GUID},
+        Boot, SystemTable,
+    },
+};
+
+use core64_util::{
+    acpi::{self, MADT_SIGNATURE},
+    boot::cpu::{CpuFlags, CpuInfo},
+    memory::PhysicalAddress,
+};
+
+/// Discovers all logical processors using the MP services protocol. Falls back to parsing the ACPI MADT, if the protocol is not available.
+pub(super) fn discover_cpus(system_table: &SystemTable<Boot>) -> Result<Vec<CpuInfo>, String> {
+    let cpus = match mp_services_cpus(system_table.boot_services()) {
+        Ok(cpus) => cpus,
+        Err(error) => {
+            warn!("{error} Falling back to ACPI MADT.");
+            madt_cpus(system_table)?
+        }
+    };
+
+    info!("Discovered {} processor(s).", cpus.len());
+    Ok(cpus)
+}
+
+/// Queries processors via `EFI_MP_SERVICES_PROTOCOL`
+fn mp_services_cpus(boot_services: &BootServices) -> Result<Vec<CpuInfo>, String> {
+    let mp_services_handle = boot_services
+        .get_handle_for_protocol::<MpServices>()
+        .map_err(|error| format!("Could not get handle for MP services: {error}."))?;
+
+    let mp_services = boot_services
+        .open_protocol_exclusive::<MpServices>(mp_services_handle)
+        .map_err(|error| format!("Could not open MP services: {error}."))?;
+
+    let processor_count = mp_services
+        .get_number_of_processors()
+        .map_err(|error| format!("Could not get number of processors: {error}."))?;
+
+    (0..processor_count.total)
+        .map(|processor_number| {
+            let info = mp_services
+                .get_processor_info(processor_number)
+                .map_err(|error| {
+                    format!("Could not get information of processor {processor_number}: {error}.")
+                })?;
+
+            let mut flags = CpuFlags::empty();
+            flags.set(CpuFlags::BOOTSTRAP, info.is_bsp());
+            flags.set(CpuFlags::ENABLED, info.is_enabled());
+            flags.set(CpuFlags::HEALTHY, info.is_healthy());
+
+            Ok(CpuInfo {
+                apic_id: info.processor_id as u32,
+                package: info.location.package,
+                core: info.location.core,
+                thread: info.location.thread,
+                flags,
+            })
+        })
+        .collect()
+}
+
+/// Reads processor local APIC structures from the ACPI MADT. The processor topology is unknown.
+fn madt_cpus(system_table: &SystemTable<Boot>) -> Result<Vec<CpuInfo>, String> {
+    let rsdp = rsdp_address(system_table).ok_or("Could not find ACPI RSDP.".to_string())?;
+
+    // firmware tables are identity mapped while boot services are active
+    let madt = unsafe { acpi::find_table(rsdp, MADT_SIGNATURE) }
+        .ok_or("Could not find ACPI MADT.".to_string())?;
+
+    let bootstrap_apic_id = bootstrap_apic_id();
+    Ok(acpi::madt_local_apics(madt)
+        .filter(|local_apic| local_apic.enabled || local_apic.online_capable)
+        .map(|local_apic| {
+            let mut flags = CpuFlags::HEALTHY;
+            flags.set(CpuFlags::BOOTSTRAP, local_apic.apic_id == bootstrap_apic_id);
+            flags.set(CpuFlags::ENABLED, local_apic.enabled);
+
+            CpuInfo {
+                apic_id: local_apic.apic_id,
+                package: CpuInfo::UNKNOWN,
+                core: CpuInfo::UNKNOWN,
+                thread: CpuInfo::UNKNOWN,
+                flags,
+            }
+        })
+        .collect())
+}
+
+/// Physical address of the ACPI RSDP from the UEFI configuration table, preferring the ACPI 2.0 entry
+pub(super) fn rsdp_address(system_table: &SystemTable<Boot>) -> Option<PhysicalAddress> {
+    let config_table = system_table.config_table();
+    config_table
+        .iter()
+        .find(|entry| entry.guid == ACPI2_GUID)
+        .or_else(|| config_table.iter().find(|entry| entry.guid == ACPI_GUID))
+        .map(|entry| entry.address as PhysicalAddress)
+}
+
+/// Returns the (x2)APIC ID of the current processor
+fn bootstrap_apic_id() -> u32 {
+    // extended topology leaf reports the full x2APIC ID
+    if __cpuid(0).eax >= 0xB {
+        let topology = __cpuid_count(0xB, 0);
+        if topology.ebx != 0 {
+            return topology.edx;
+        }
+    }
+    __cpuid(1).ebx >> 24
+}
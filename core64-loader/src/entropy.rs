@@ -0,0 +1,115 @@
+use alloc::{format, string::String};
+use core::{
+    arch::{
+        asm,
+        x86_64::{__cpuid, __cpuid_count, _rdtsc},
+    },
+    slice,
+};
+
+use log::warn;
+use uefi::{
+    prelude::BootServices,
+    proto::rng::Rng,
+    table::boot::{AllocateType::AnyPages, MemoryType},
+};
+
+use core64_util::{boot::entropy::ENTROPY_SEED_SIZE, memory::PhysicalAddress};
+
+/// Amount of attempts for RDRAND/RDSEED before giving up, as recommended by Intel
+const HARDWARE_RANDOM_RETRIES: usize = 10;
+
+/// Allocates a page and fills it with an entropy seed. The seed is taken from `EFI_RNG_PROTOCOL` and mixed with RDSEED/RDRAND, if available, or the TSC otherwise.
+pub(super) fn allocate_entropy_seed(bt: &BootServices) -> Result<PhysicalAddress, String> {
+    let seed_address = bt
+        .allocate_pages(AnyPages, MemoryType::LOADER_DATA, 1)
+        .map_err(|error| format!("Could not allocate page for entropy seed: {error}."))?;
+
+    let seed = unsafe { slice::from_raw_parts_mut(seed_address as *mut u8, ENTROPY_SEED_SIZE) };
+    seed.fill(0);
+
+    if let Err(error) = firmware_random(bt, seed) {
+        warn!("{error} Entropy seed relies on hardware sources only.");
+    }
+
+    let hardware_random = hardware_random_source();
+    for chunk in seed.chunks_mut(size_of::<u64>()) {
+        let value = hardware_random().to_le_bytes();
+        chunk
+            .iter_mut()
+            .zip(value)
+            .for_each(|(byte, random)| *byte ^= random);
+    }
+
+    Ok(seed_address)
+}
+
+/// Fills buffer with random bytes of `EFI_RNG_PROTOCOL`
+fn firmware_random(bt: &BootServices, buffer: &mut [u8]) -> Result<(), String> {
+    let rng_handle = bt
+        .get_handle_for_protocol::<Rng>()
+        .map_err(|error| format!("Could not get handle for RNG protocol: {error}."))?;
+
+    let mut rng = bt
+        .open_protocol_exclusive::<Rng>(rng_handle)
+        .map_err(|error| format!("Could not open RNG protocol: {error}."))?;
+
+    rng.get_rng(None, buffer)
+        .map_err(|error| format!("Could not get random bytes from RNG protocol: {error}."))
+}
+
+/// Returns the best available hardware source of randomness
+fn hardware_random_source() -> fn() -> u64 {
+    let has_rdseed = __cpuid(0).eax >= 7 && __cpuid_count(7, 0).ebx & (1 << 18) != 0;
+    let has_rdrand = __cpuid(1).ecx & (1 << 30) != 0;
+
+    if has_rdseed {
+        || rdseed().or_else(rdrand).unwrap_or_else(tsc)
+    } else if has_rdrand {
+        || rdrand().unwrap_or_else(tsc)
+    } else {
+        tsc
+    }
+}
+
+fn rdseed() -> Option<u64> {
+    (0..HARDWARE_RANDOM_RETRIES).find_map(|_| {
+        let value: u64;
+        let success: u8;
+        unsafe {
+            asm!(
+                "rdseed {0}",
+                "setc {1}",
+                out(reg) value,
+                out(reg_byte) success,
+                options(nomem, nostack)
+            );
+        }
+        (success != 0).then_some(value)
+    })
+}
+
+fn rdrand() -> Option<u64> {
+    (0..HARDWARE_RANDOM_RETRIES).find_map(|_| {
+        let value: u64;
+        let success: u8;
+        unsafe {
+            asm!(
+                "rdrand {0}",
+                "setc {1}",
+                out(reg) value,
+                out(reg_byte) success,
+                options(nomem, nostack)
+            );
+        }
+        (success != 0).then_some(value)
+    })
+}
+
+/// Time stamp counter spread over all bits (splitmix64 finalizer). Only a weak source of entropy.
+fn tsc() -> u64 {
+    let mut value = unsafe { _rdtsc() };
+    value = (value ^ (value >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    value = (value ^ (value >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    value ^ (value >> 31)
+}
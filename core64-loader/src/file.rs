@@ -3,79 +3,389 @@ use alloc::{
     string::{String, ToString},
     vec::Vec,
 };
-use core::slice;
+use core::{ptr, slice};
 
-use goblin::{elf64::program_header::PT_LOAD, elf::Elf};
-use uefi::{
-    CString16,
-    fs::FileSystem,
-    Handle,
-    prelude::BootServices, table::boot::{AllocateType, PAGE_SIZE},
+use core64_util::{
+    boot::{
+        device::BootDevice,
+        limine::{self, Marker},
+        request::{BootRequests, BOOT_REQUESTS_SECTION},
+    },
+    compression::Compression,
+    memory::{address::PhysAddr, paging::KERNEL_MAPPING_OFFSET, VirtualAddress},
+};
+use goblin::{
+    elf::{Elf, ProgramHeader},
+    elf64::program_header::PT_LOAD,
 };
+use log::info;
 use uefi::data_types::PhysicalAddress;
 use uefi::table::boot::MemoryType;
-use core64_util::memory::VirtualAddress;
+use uefi::{
+    fs::FileSystem,
+    prelude::BootServices,
+    proto::{
+        device_path::{
+            media::PartitionSignature,
+            text::{AllowShortcuts, DisplayOnly},
+            DevicePath, DevicePathNodeEnum,
+        },
+        loaded_image::LoadedImage,
+        media::fs::SimpleFileSystem,
+    },
+    table::boot::{AllocateType, PAGE_SIZE},
+    CString16, Handle,
+};
+
+use crate::{
+    multiboot::{self, MultibootKernel},
+    network,
+    verify::{self, Manifest},
+};
+
+/// Load option prefix of boot modules, e.g. `module=\boot\initrd`. All other load options form the kernel command line.
+const MODULE_OPTION_PREFIX: &str = "module=";
+/// Modules must be accessible with 32-bit addresses by Multiboot2 kernels
+const MODULE_MAX_ADDRESS: PhysicalAddress = u32::MAX as PhysicalAddress;
 
-/// Gets data of a file from filesystem
-pub(super) fn get_file_data(
+/// File found by [`find_file`] or [`network::find_file`]
+pub(super) struct FoundFile {
+    pub(super) data: Vec<u8>,
+    /// Search path the file has been found at
+    pub(super) path: String,
+    /// Source the file has been read from. Related files, e.g. manifest and modules, are read from the same source.
+    pub(super) source: FileSource,
+    pub(super) boot_device: BootDevice,
+}
+
+/// Location files are read from
+#[derive(Copy, Clone, Debug)]
+pub(super) enum FileSource {
+    /// Filesystem handle
+    Disk(Handle),
+    /// Handle of the PXE base code protocol and IPv4 address of the TFTP server
+    Tftp(Handle, [u8; 4]),
+}
+
+impl FileSource {
+    pub(super) fn read(self, boot_services: &BootServices, path: &str) -> Result<Vec<u8>, String> {
+        match self {
+            Self::Disk(device) => read_file(boot_services, device, path),
+            Self::Tftp(device, server) => network::read_file(boot_services, device, server, path),
+        }
+    }
+}
+
+/// Searches all filesystems for the first existing path of `search_paths`, starting with the filesystem of the loader image.
+pub(super) fn find_file(
     image_handle: Handle,
     boot_services: &BootServices,
-    filename: &str,
+    search_paths: &[&str],
+) -> Result<FoundFile, String> {
+    let image_device = image_device(image_handle, boot_services);
+    let mut devices = boot_services
+        .find_handles::<SimpleFileSystem>()
+        .map_err(|error| format!("Cannot find filesystems: {error}."))?;
+    // prefer the device the loader has been started from
+    devices.sort_by_key(|device| Some(*device) != image_device);
+
+    for device in devices {
+        for path in search_paths {
+            if let Ok(data) = read_file(boot_services, device, path) {
+                let boot_device = boot_device(device, boot_services);
+                info!("Found {path} on {}.", boot_device.device_path());
+                return Ok(FoundFile {
+                    data,
+                    path: path.to_string(),
+                    source: FileSource::Disk(device),
+                    boot_device,
+                });
+            }
+        }
+    }
+
+    Err(format!(
+        "Unable to find any of {search_paths:?} on any filesystem."
+    ))
+}
+
+/// Reads a file from the filesystem of `device`
+pub(super) fn read_file(
+    boot_services: &BootServices,
+    device: Handle,
+    path: &str,
 ) -> Result<Vec<u8>, String> {
-    let mut file_system = FileSystem::new(
-        boot_services
-            .get_image_file_system(image_handle)
-            .map_err(|_| "Cannot get filesystem.".to_string())?,
+    let file_system = boot_services
+        .open_protocol_exclusive::<SimpleFileSystem>(device)
+        .map_err(|error| format!("Cannot open filesystem: {error}."))?;
+    let path_name = CString16::try_from(path).map_err(|_| format!("Invalid file path: {path}"))?;
+
+    FileSystem::new(file_system)
+        .read(path_name.as_ref())
+        .map_err(|_| format!("Unable to read file with name: {path}."))
+}
+
+/// Handle of the filesystem the loader image has been loaded from
+pub(super) fn image_device(image_handle: Handle, boot_services: &BootServices) -> Option<Handle> {
+    let loaded_image = boot_services
+        .open_protocol_exclusive::<LoadedImage>(image_handle)
+        .ok()?;
+    let device_path = boot_services
+        .open_protocol_exclusive::<DevicePath>(loaded_image.device()?)
+        .ok()?;
+    boot_services
+        .locate_device_path::<SimpleFileSystem>(&mut &*device_path)
+        .ok()
+}
+
+/// Describes the device of a filesystem or network handle by its device path and GPT partition
+pub(super) fn boot_device(device: Handle, boot_services: &BootServices) -> BootDevice {
+    let Ok(device_path) = boot_services.open_protocol_exclusive::<DevicePath>(device) else {
+        return BootDevice::new("", None, 0);
+    };
+
+    let device_path_string = device_path
+        .to_string(boot_services, DisplayOnly(false), AllowShortcuts(false))
+        .map(|device_path| device_path.to_string())
+        .unwrap_or_default();
+
+    let hard_drive = device_path
+        .node_iter()
+        .find_map(|node| match node.as_enum() {
+            Ok(DevicePathNodeEnum::MediaHardDrive(hard_drive)) => Some(hard_drive),
+            _ => None,
+        });
+    let (partition_guid, partition_number) = hard_drive.map_or((None, 0), |hard_drive| {
+        let partition_guid = match hard_drive.partition_signature() {
+            PartitionSignature::Guid(guid) => Some(guid.to_bytes()),
+            _ => None,
+        };
+        (partition_guid, hard_drive.partition_number())
+    });
+
+    BootDevice::new(&device_path_string, partition_guid, partition_number)
+}
+
+/// Decompresses gzip, zlib or LZ4 compressed images (detected by their magic number). Uncompressed images are returned as is.
+pub(super) fn decompress(data: Vec<u8>) -> Result<Vec<u8>, String> {
+    let Some(compression) = Compression::detect(&data) else {
+        return Ok(data);
+    };
+
+    let decompressed = compression
+        .decompress(&data)
+        .map_err(|error| format!("Could not decompress {compression:?} image: {error}."))?;
+    info!(
+        "Decompressed {compression:?} image from {} to {} bytes.",
+        data.len(),
+        decompressed.len()
     );
-    file_system
-        .read(
-            CString16::try_from(filename)
-                .map_err(|_| format!("Invalid filename: {filename}"))?
-                .as_ref(),
+    Ok(decompressed)
+}
+
+/// Load options of the loader image, e.g. set by the boot entry or the UEFI shell
+pub(super) struct LoadOptions {
+    /// Boot module paths given as `module=<path>`
+    pub(super) module_paths: Vec<String>,
+    /// All other load options, separated by spaces
+    pub(super) command_line: String,
+}
+
+impl LoadOptions {
+    pub(super) fn read(boot_services: &BootServices, image_handle: Handle) -> Self {
+        let load_options = boot_services
+            .open_protocol_exclusive::<LoadedImage>(image_handle)
+            .ok()
+            .and_then(|loaded_image| {
+                loaded_image
+                    .load_options_as_cstr16()
+                    .ok()
+                    .map(|load_options| load_options.to_string())
+            })
+            .unwrap_or_default();
+
+        let (module_options, command_line): (Vec<&str>, Vec<&str>) = load_options
+            .split_whitespace()
+            .partition(|option| option.starts_with(MODULE_OPTION_PREFIX));
+
+        Self {
+            module_paths: module_options
+                .into_iter()
+                .map(|option| option[MODULE_OPTION_PREFIX.len()..].to_string())
+                .collect(),
+            command_line: command_line.join(" "),
+        }
+    }
+}
+
+/// Boot module loaded into pages below 4 GiB
+pub(super) struct Module {
+    pub(super) address: PhysicalAddress,
+    pub(super) size: usize,
+    pub(super) path: String,
+}
+
+impl Module {
+    /// Physical address and page count of the module
+    pub(super) fn pages(&self) -> (PhysAddr, usize) {
+        (
+            PhysAddr::new(self.address),
+            self.size.div_ceil(PAGE_SIZE).max(1),
         )
-        .map_err(|_| format!("Unable to read file with name: {filename}."))
+    }
 }
 
-/// Allocates the file data in memory. Returns elf entry address, file start address and file page count
+/// Loads the modules at `paths`, see [`load_module`]
+pub(super) fn load_modules(
+    boot_services: &BootServices,
+    manifest: &Manifest,
+    source: FileSource,
+    boot_device: BootDevice,
+    paths: &[String],
+) -> Result<Vec<Module>, String> {
+    paths
+        .iter()
+        .map(|path| load_module(boot_services, manifest, source, boot_device, path))
+        .collect()
+}
+
+/// Reads a module from the source of the kernel, verifies it against the manifest like the kernel and decompresses it into pages
+/// below 4 GiB
+fn load_module(
+    boot_services: &BootServices,
+    manifest: &Manifest,
+    source: FileSource,
+    boot_device: BootDevice,
+    path: &str,
+) -> Result<Module, String> {
+    let file = FoundFile {
+        data: source.read(boot_services, path)?,
+        path: path.to_string(),
+        source,
+        boot_device,
+    };
+    verify::verify_file(manifest, &file)?;
+
+    let data = decompress(file.data)?;
+    let address = boot_services
+        .allocate_pages(
+            AllocateType::MaxAddress(MODULE_MAX_ADDRESS),
+            MemoryType::LOADER_DATA,
+            data.len().div_ceil(PAGE_SIZE).max(1),
+        )
+        .map_err(|error| format!("Could not allocate pages for module {path}: {error}."))?;
+    unsafe { ptr::copy_nonoverlapping(data.as_ptr(), address as *mut u8, data.len()) };
+
+    Ok(Module {
+        address,
+        size: data.len(),
+        path: file.path,
+    })
+}
+
+/// Kernel image loaded into memory by [`parse_elf`]
+pub(super) struct LoadedKernel {
+    pub(super) entry: VirtualAddress,
+    pub(super) physical_address: PhysicalAddress,
+    /// Virtual address the first page of the image is mapped at
+    pub(super) virtual_address: VirtualAddress,
+    pub(super) page_count: usize,
+    pub(super) boot_requests: BootRequests,
+    /// Limine requests and base revision markers with offsets relative to the image start. Empty, if the kernel is not booted in
+    /// Limine compatibility mode.
+    pub(super) limine_markers: Vec<Marker>,
+    /// Multiboot2 header of kernels booted with the Multiboot2 protocol
+    pub(super) multiboot: Option<MultibootKernel>,
+}
+
+/// Allocates the file data in memory and reads the kernel's boot requests. The file may be compressed, see [`decompress`].
+///
+/// Kernels without core64 boot requests are booted with the Multiboot2 protocol, if their image contains a Multiboot2 header.
+/// Otherwise, kernels with Limine requests are booted in Limine compatibility mode. Their segments are placed at any physical
+/// address, as Limine kernels only specify virtual addresses.
 pub(super) fn parse_elf(
     data: Vec<u8>,
     boot_services: &BootServices,
-) -> Result<(VirtualAddress, PhysicalAddress, usize), String> {
+) -> Result<LoadedKernel, String> {
+    let data = decompress(data)?;
     let data = data.as_slice();
     let elf = Elf::parse(data).map_err(|_| "Unable to parse file to elf!".to_string())?;
 
-    if !elf.is_64 {
+    // kernel requirements, loader defaults are used if the kernel does not declare any
+    let boot_requests_section = elf
+        .section_headers
+        .iter()
+        .find(|section| elf.shdr_strtab.get_at(section.sh_name) == Some(BOOT_REQUESTS_SECTION));
+    let boot_requests = boot_requests_section
+        .map(|section| {
+            let start = section.sh_offset as usize;
+            let bytes = data
+                .get(start..start + section.sh_size as usize)
+                .ok_or_else(|| "Boot requests section is out of bounds.".to_string())?;
+            BootRequests::from_bytes(bytes)
+                .map_err(|error| format!("Invalid boot requests: {error}."))
+        })
+        .transpose()?
+        .unwrap_or_default();
+
+    // Multiboot2 kernels may be 32-bit images entered through their 64-bit entry point
+    let multiboot = match boot_requests_section {
+        None => multiboot::parse_header(data)?,
+        Some(_) => None,
+    };
+    if !elf.is_64 && multiboot.is_none() {
         return Err("Invalid elf format.".to_string());
     }
 
+    let load_headers = || {
+        elf.program_headers
+            .iter()
+            .filter(|pheader| pheader.p_type == PT_LOAD)
+    };
+    let is_limine = boot_requests_section.is_none()
+        && multiboot.is_none()
+        && load_headers().any(|pheader| {
+            let start = pheader.p_offset as usize;
+            data.get(start..start + pheader.p_filesz as usize)
+                .is_some_and(|segment| limine::find_markers(segment).next().is_some())
+        });
+
+    // segments are placed by physical address, in Limine compatibility mode relative to the lowest virtual address
+    let segment_address = |pheader: &ProgramHeader| {
+        if is_limine {
+            pheader.p_vaddr
+        } else {
+            pheader.p_paddr
+        }
+    };
+
     let mut dest_start = u64::MAX;
     let mut dest_end = 0;
 
     // set up range of memory needed to be allocated
-    for pheader in elf.program_headers.iter() {
-        // skip non-load segments (e.g.: dynamic linking info)
-        if pheader.p_type != PT_LOAD {
-            continue;
-        }
-
-        dest_start = dest_start.min(pheader.p_paddr);
-        dest_end = dest_end.max(pheader.p_paddr + pheader.p_memsz);
+    for pheader in load_headers() {
+        dest_start = dest_start.min(segment_address(pheader));
+        dest_end = dest_end.max(segment_address(pheader) + pheader.p_memsz);
+    }
+    if is_limine {
+        dest_start -= dest_start % PAGE_SIZE as u64;
     }
 
     let num_pages = (dest_end as usize - dest_start as usize + PAGE_SIZE - 1) / PAGE_SIZE;
 
     // allocate file data
-    boot_services
-        .allocate_pages(AllocateType::Address(dest_start), MemoryType::LOADER_DATA, num_pages)
+    let allocate_type = if is_limine {
+        AllocateType::AnyPages
+    } else {
+        AllocateType::Address(dest_start)
+    };
+    let physical_address = boot_services
+        .allocate_pages(allocate_type, MemoryType::LOADER_DATA, num_pages)
         .map_err(|error| format!("Could not allocate pages for kernel: {}", error))?;
 
     // Copy program segments of kernel into memory
-    for pheader in elf.program_headers.iter() {
-        // skip non-load segments (e.g.: dynamic linking info) and segments of size 0 in the file
-        if pheader.p_type != PT_LOAD {
-            continue;
-        }
-        let base_address = pheader.p_paddr;
+    for pheader in load_headers() {
+        let base_address = physical_address + (segment_address(pheader) - dest_start);
         let offset = pheader.p_offset as usize;
         let size_in_file = pheader.p_filesz as usize;
         let size_in_memory = pheader.p_memsz as usize;
@@ -85,5 +395,21 @@ pub(super) fn parse_elf(
         dest[size_in_file..].fill(0);
     }
 
-    Ok((elf.entry, dest_start, num_pages))
+    let (virtual_address, limine_markers) = if is_limine {
+        let image =
+            unsafe { slice::from_raw_parts(physical_address as *const u8, num_pages * PAGE_SIZE) };
+        (dest_start, limine::find_markers(image).collect())
+    } else {
+        (KERNEL_MAPPING_OFFSET + physical_address, Vec::new())
+    };
+
+    Ok(LoadedKernel {
+        entry: elf.entry,
+        physical_address,
+        virtual_address,
+        page_count: num_pages,
+        boot_requests,
+        limine_markers,
+        multiboot,
+    })
 }
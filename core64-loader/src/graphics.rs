@@ -1,16 +1,26 @@
-use alloc::{format, string::String};
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec,
+};
 
+use log::warn;
 use uefi::{
     prelude::BootServices,
     proto::console::gop::{GraphicsOutput, PixelFormat},
+    Handle,
 };
 
-use core64_util::graphics::framebuffer::FrameBufferMetadata;
+use core64_util::graphics::{
+    framebuffer::FrameBufferMetadata, image::ImageFormat, splash::BootSplash, Color,
+};
 
-/// Initialize framebuffer (GOP)
+use crate::file;
 
+/// Initialize framebuffer (GOP), switching to the requested resolution (width, height) if the firmware supports it
 pub(super) fn initialize_framebuffer(
     boot_services: &BootServices,
+    resolution: Option<(usize, usize)>,
 ) -> Result<FrameBufferMetadata, String> {
     let gop_handle = boot_services
         .get_handle_for_protocol::<GraphicsOutput>()
@@ -19,6 +29,26 @@ pub(super) fn initialize_framebuffer(
     let mut gop = boot_services
         .open_protocol_exclusive::<GraphicsOutput>(gop_handle)
         .map_err(|error| format!("Could not open GOP: {error}."))?;
+
+    if let Some(resolution) = resolution {
+        let mode = gop.modes(boot_services).find(|mode| {
+            mode.info().resolution() == resolution
+                && matches!(
+                    mode.info().pixel_format(),
+                    PixelFormat::Rgb | PixelFormat::Bgr
+                )
+        });
+        match mode {
+            Some(mode) => gop
+                .set_mode(&mode)
+                .map_err(|error| format!("Could not set GOP mode: {error}."))?,
+            None => warn!(
+                "No GOP mode with resolution {}x{}, keeping current mode.",
+                resolution.0, resolution.1
+            ),
+        }
+    }
+
     let mut raw_frame_buffer = gop.frame_buffer();
     let base = raw_frame_buffer.as_mut_ptr() as u64;
     let size = raw_frame_buffer.size();
@@ -43,3 +73,48 @@ pub(super) fn initialize_framebuffer(
         is_rgb,
     })
 }
+
+/// Draws the splash image at `path` on the filesystem of the loader image centered on the framebuffer
+pub(super) fn draw_splash(
+    image_handle: Handle,
+    boot_services: &BootServices,
+    path: &str,
+    framebuffer: &FrameBufferMetadata,
+) -> Result<BootSplash, String> {
+    let device = file::image_device(image_handle, boot_services)
+        .ok_or_else(|| "Cannot load splash: loader device is unknown.".to_string())?;
+    let data = file::read_file(boot_services, device, path)?;
+
+    let format =
+        ImageFormat::detect(&data).ok_or_else(|| format!("Unknown image format of {path}."))?;
+    let info = format
+        .info(&data)
+        .map_err(|error| format!("Invalid splash image {path}: {error}."))?;
+    // check the fit before allocating pixels for the untrusted image size
+    let mut splash = BootSplash::new(framebuffer, info.width, info.height).ok_or_else(|| {
+        format!(
+            "Splash image {path} ({}x{}) does not fit the framebuffer.",
+            info.width, info.height
+        )
+    })?;
+
+    let mut pixels = vec![Color::black(); info.pixel_count()];
+    format
+        .decode(&data, &mut pixels)
+        .map_err(|error| format!("Invalid splash image {path}: {error}."))?;
+    // the framebuffer is identity mapped while boot services are active
+    unsafe { splash.draw(framebuffer, &pixels) };
+    Ok(splash)
+}
+
+/// Advances the progress bar of the boot splash, if one is shown
+pub(super) fn advance_splash(
+    splash: &mut Option<BootSplash>,
+    framebuffer: &FrameBufferMetadata,
+    progress: u32,
+) {
+    if let Some(splash) = splash {
+        // the loader's page tables identity map the framebuffer as well
+        unsafe { splash.set_progress(framebuffer, progress) };
+    }
+}
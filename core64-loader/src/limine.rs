@@ -0,0 +1,444 @@
+use alloc::{format, string::String, vec::Vec};
+use core::{arch::asm, ptr};
+
+use log::{info, warn};
+use uefi::{
+    prelude::BootServices,
+    table::boot::{AllocateType::AnyPages, MemoryType},
+};
+
+use core64_util::{
+    boot::limine::{
+        BootloaderInfoResponse, File, Framebuffer, FramebufferResponse, HhdmResponse,
+        KernelAddressResponse, Marker, MemoryMapEntry, MemoryMapResponse, ModuleResponse,
+        RequestKind, RsdpResponse, FRAMEBUFFER_RGB, MAX_BASE_REVISION,
+        MEMORY_MAP_BOOTLOADER_RECLAIMABLE, MEMORY_MAP_KERNEL_AND_MODULES, MEMORY_MAP_RESERVED,
+        MEMORY_MAP_USABLE, RESPONSE_POINTER_OFFSET, RSDP_PHYSICAL_BASE_REVISION,
+    },
+    graphics::framebuffer::{FrameBufferMetadata, BPP},
+    memory::{address::PhysAddr, PhysicalAddress, VirtualAddress, PAGE_SIZE},
+};
+
+use crate::{
+    file::Module,
+    memory::{self, AddressSpace},
+    CoreMemoryMap, CoreMemoryType,
+};
+
+/// Higher half direct map offset in Limine compatibility mode, the offset used by Limine without KASLR
+pub(super) const HHDM_OFFSET: u64 = 0xFFFF_8000_0000_0000;
+
+const LOADER_NAME: &str = "core64-loader\0";
+const LOADER_VERSION: &str = concat!(env!("CARGO_PKG_VERSION"), "\0");
+
+/// Pages for fixed size responses, memory map entries are allocated on top
+const RESPONSE_PAGE_COUNT: usize = 1;
+/// Additional memory map entries allocated on top of the descriptor count, for available memory split by allocated frames
+const MEMORY_MAP_PADDING: usize = 64;
+
+const CODE_SELECTOR: u64 = 0x28;
+const DATA_SELECTOR: u64 = 0x30;
+/// GDT mandated by the protocol: null descriptor, 16-bit, 32-bit and 64-bit code and data descriptors
+static GDT: [u64; 7] = [
+    0,
+    0x0000_9A00_0000_FFFF,
+    0x0000_9200_0000_FFFF,
+    0x00CF_9A00_0000_FFFF,
+    0x00CF_9200_0000_FFFF,
+    0x00AF_9A00_0000_FFFF,
+    0x00CF_9200_0000_FFFF,
+];
+
+#[repr(C, packed)]
+struct DescriptorTablePointer {
+    limit: u16,
+    base: u64,
+}
+
+/// Whether the kernel requests modules, which are then loaded from the load options like for Multiboot2 kernels
+pub(super) fn requests_modules(markers: &[Marker]) -> bool {
+    markers.iter().any(|marker| {
+        matches!(
+            marker,
+            Marker::Request {
+                kind: Some(RequestKind::Module),
+                ..
+            }
+        )
+    })
+}
+
+/// Limine requests of a kernel booted in compatibility mode. Response memory is allocated, before boot services are exited.
+pub(super) struct LimineBoot {
+    markers: Vec<Marker>,
+    modules: Vec<Module>,
+    kernel_address: PhysicalAddress,
+    kernel_virtual_address: VirtualAddress,
+    rsdp: Option<PhysicalAddress>,
+    response_address: PhysicalAddress,
+    response_page_count: usize,
+}
+
+impl LimineBoot {
+    /// Allocates memory for the responses. `descriptor_capacity` is the maximum amount of memory map descriptors.
+    pub(super) fn new(
+        bt: &BootServices,
+        markers: Vec<Marker>,
+        modules: Vec<Module>,
+        kernel_address: PhysicalAddress,
+        kernel_virtual_address: VirtualAddress,
+        rsdp: Option<PhysicalAddress>,
+        descriptor_capacity: usize,
+    ) -> Result<Self, String> {
+        let memory_map_size = (descriptor_capacity + MEMORY_MAP_PADDING)
+            * (size_of::<MemoryMapEntry>() + size_of::<VirtualAddress>());
+        // pointers to the files, null terminated paths, empty command lines and aligned files
+        let modules_size = modules
+            .iter()
+            .map(|module| {
+                size_of::<VirtualAddress>()
+                    + module.path.len()
+                    + 2
+                    + align_of::<File>()
+                    + size_of::<File>()
+            })
+            .sum::<usize>();
+        let response_page_count =
+            RESPONSE_PAGE_COUNT + (memory_map_size + modules_size).div_ceil(PAGE_SIZE);
+        let response_address = bt
+            .allocate_pages(AnyPages, MemoryType::LOADER_DATA, response_page_count)
+            .map_err(|error| format!("Could not allocate pages for Limine responses: {error}."))?;
+
+        for marker in &markers {
+            match marker {
+                Marker::Request {
+                    kind: None, offset, ..
+                } => warn!("Unsupported Limine request at offset {offset:#x} is not answered."),
+                Marker::Request {
+                    kind: Some(RequestKind::Smp),
+                    ..
+                } => warn!(
+                    "Limine SMP request is not answered, application processors are not started."
+                ),
+                Marker::BaseRevision { revision, .. } if *revision > MAX_BASE_REVISION => {
+                    warn!("Unsupported Limine base revision {revision}.")
+                }
+                _ => {}
+            }
+        }
+        info!(
+            "Booting kernel in Limine compatibility mode with {} module(s).",
+            modules.len()
+        );
+
+        Ok(Self {
+            markers,
+            modules,
+            kernel_address,
+            kernel_virtual_address,
+            rsdp,
+            response_address,
+            response_page_count,
+        })
+    }
+
+    /// Regions (physical address, page count) of the modules
+    pub(super) fn module_pages(&self) -> impl Iterator<Item = (PhysAddr, usize)> + '_ {
+        self.modules.iter().map(Module::pages)
+    }
+
+    /// Base revision the kernel is booted with. Kernels without (supported) base revision marker get revision 0.
+    fn base_revision(&self) -> u64 {
+        self.markers
+            .iter()
+            .find_map(|marker| match marker {
+                Marker::BaseRevision { revision, .. } if *revision <= MAX_BASE_REVISION => {
+                    Some(*revision)
+                }
+                _ => None,
+            })
+            .unwrap_or_default()
+    }
+
+    /// Writes responses and links them to the requests in the loaded kernel image. Must be called after the address space has
+    /// been set up, so that the memory map reflects all allocated page tables.
+    pub(super) fn answer_requests(
+        &self,
+        memory_map: &CoreMemoryMap,
+        address_space: &AddressSpace,
+        framebuffer: &FrameBufferMetadata,
+    ) {
+        let mut writer = ResponseWriter {
+            next: self.response_address,
+            end: self.response_address + (self.response_page_count * PAGE_SIZE) as u64,
+        };
+        let base_revision = self.base_revision();
+
+        for marker in &self.markers {
+            match *marker {
+                Marker::Request {
+                    offset,
+                    kind: Some(kind),
+                    ..
+                } => {
+                    let response = match kind {
+                        RequestKind::BootloaderInfo => {
+                            let name = writer.write_bytes(LOADER_NAME.as_bytes());
+                            let version = writer.write_bytes(LOADER_VERSION.as_bytes());
+                            writer.write(BootloaderInfoResponse {
+                                revision: 0,
+                                name,
+                                version,
+                            })
+                        }
+                        RequestKind::Hhdm => writer.write(HhdmResponse {
+                            revision: 0,
+                            offset: HHDM_OFFSET,
+                        }),
+                        RequestKind::Framebuffer => {
+                            let framebuffer = writer.write(limine_framebuffer(framebuffer));
+                            let framebuffers = writer.write(framebuffer);
+                            writer.write(FramebufferResponse {
+                                revision: 0,
+                                framebuffer_count: 1,
+                                framebuffers,
+                            })
+                        }
+                        RequestKind::MemoryMap => {
+                            writer.write_memory_map(memory_map, address_space)
+                        }
+                        RequestKind::Rsdp => match self.rsdp {
+                            Some(rsdp) => writer.write(RsdpResponse {
+                                revision: 0,
+                                address: if base_revision >= RSDP_PHYSICAL_BASE_REVISION {
+                                    rsdp
+                                } else {
+                                    HHDM_OFFSET + rsdp
+                                },
+                            }),
+                            None => continue,
+                        },
+                        RequestKind::KernelAddress => writer.write(KernelAddressResponse {
+                            revision: 0,
+                            physical_base: self.kernel_address,
+                            virtual_base: self.kernel_virtual_address,
+                        }),
+                        RequestKind::Module => writer.write_modules(&self.modules),
+                        // application processors are not started, so the kernel could not tell a single processor from
+                        // processors it cannot use
+                        RequestKind::Smp => continue,
+                    };
+
+                    // kernel image is still identity mapped
+                    let response_pointer =
+                        self.kernel_address + (offset + RESPONSE_POINTER_OFFSET) as u64;
+                    unsafe { ptr::write(response_pointer as *mut VirtualAddress, response) };
+                }
+                Marker::BaseRevision { offset, revision } if revision <= MAX_BASE_REVISION => {
+                    // supported revisions are acknowledged by zeroing the revision
+                    let revision_pointer = self.kernel_address + offset as u64 + 16;
+                    unsafe { ptr::write(revision_pointer as *mut u64, 0) };
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Bump allocator for responses. Returns higher half direct map addresses of the written values.
+struct ResponseWriter {
+    next: PhysicalAddress,
+    end: PhysicalAddress,
+}
+
+impl ResponseWriter {
+    fn reserve(&mut self, size: usize, align: usize) -> PhysicalAddress {
+        let address = self.next.next_multiple_of(align as u64);
+        assert!(
+            address + size as u64 <= self.end,
+            "Limine responses exceed their pages"
+        );
+        self.next = address + size as u64;
+        address
+    }
+
+    fn write<T>(&mut self, value: T) -> VirtualAddress {
+        let address = self.reserve(size_of::<T>(), align_of::<T>());
+        unsafe { ptr::write(address as *mut T, value) };
+        HHDM_OFFSET + address
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) -> VirtualAddress {
+        let address = self.reserve(bytes.len(), 1);
+        unsafe { ptr::copy_nonoverlapping(bytes.as_ptr(), address as *mut u8, bytes.len()) };
+        HHDM_OFFSET + address
+    }
+
+    /// Writes the array of pointers to the module files, followed by the files and their paths
+    fn write_modules(&mut self, modules: &[Module]) -> VirtualAddress {
+        let files = self.reserve(
+            modules.len() * size_of::<VirtualAddress>(),
+            align_of::<VirtualAddress>(),
+        );
+        for (index, module) in modules.iter().enumerate() {
+            let path = self.write_bytes(module.path.as_bytes());
+            self.write_bytes(b"\0");
+            let cmdline = self.write_bytes(b"\0");
+            let file = self.write(File {
+                revision: 0,
+                address: HHDM_OFFSET + module.address,
+                size: module.size as u64,
+                path,
+                cmdline,
+                // generic media, the device of the module is not reported
+                media_type: 0,
+                unused: 0,
+                tftp_ip: 0,
+                tftp_port: 0,
+                partition_index: 0,
+                mbr_disk_id: 0,
+                gpt_disk_uuid: [0; 16],
+                gpt_part_uuid: [0; 16],
+                part_uuid: [0; 16],
+            });
+            unsafe { ptr::write((files as *mut VirtualAddress).add(index), file) };
+        }
+
+        self.write(ModuleResponse {
+            revision: 0,
+            module_count: modules.len() as u64,
+            modules: HHDM_OFFSET + files,
+        })
+    }
+
+    /// Writes the memory map entries followed by the array of pointers to them
+    fn write_memory_map(
+        &mut self,
+        memory_map: &CoreMemoryMap,
+        address_space: &AddressSpace,
+    ) -> VirtualAddress {
+        let mut entries_address = None;
+        let mut entry_count = 0;
+        memory::for_each_memory_region(memory_map, address_space, |base, length, r#type| {
+            let r#type = match r#type {
+                CoreMemoryType::Available => MEMORY_MAP_USABLE,
+                CoreMemoryType::Reserved => MEMORY_MAP_RESERVED,
+                CoreMemoryType::KernelCode => MEMORY_MAP_KERNEL_AND_MODULES,
+                CoreMemoryType::KernelStack
+                | CoreMemoryType::KernelData
+                | CoreMemoryType::LoaderReclaimable => MEMORY_MAP_BOOTLOADER_RECLAIMABLE,
+            };
+            let address = self.write(MemoryMapEntry {
+                base,
+                length,
+                r#type,
+            });
+            entries_address.get_or_insert(address);
+            entry_count += 1;
+        });
+
+        // entries are written contiguously
+        let entries_address = entries_address.unwrap_or_default();
+        let entries = self.reserve(
+            entry_count * size_of::<VirtualAddress>(),
+            align_of::<VirtualAddress>(),
+        );
+        for index in 0..entry_count {
+            let entry = entries_address + (index * size_of::<MemoryMapEntry>()) as u64;
+            unsafe { ptr::write((entries as *mut VirtualAddress).add(index), entry) };
+        }
+
+        self.write(MemoryMapResponse {
+            revision: 0,
+            entry_count: entry_count as u64,
+            entries: HHDM_OFFSET + entries,
+        })
+    }
+}
+
+fn limine_framebuffer(framebuffer: &FrameBufferMetadata) -> Framebuffer {
+    let (red_mask_shift, blue_mask_shift) = if framebuffer.is_rgb { (0, 16) } else { (16, 0) };
+    Framebuffer {
+        address: HHDM_OFFSET + framebuffer.base,
+        width: framebuffer.width as u64,
+        height: framebuffer.height as u64,
+        pitch: (framebuffer.stride * BPP) as u64,
+        bpp: (BPP * 8) as u16,
+        memory_model: FRAMEBUFFER_RGB,
+        red_mask_size: 8,
+        red_mask_shift,
+        green_mask_size: 8,
+        green_mask_shift: 8,
+        blue_mask_size: 8,
+        blue_mask_shift,
+        unused: [0; 7],
+        edid_size: 0,
+        edid: 0,
+    }
+}
+
+/// Switches to the kernel address space and jumps to the kernel entry in the machine state defined by the protocol: protocol GDT
+/// loaded, interrupts disabled, all general purpose registers zeroed and a zero return address on the kernel stack.
+///
+/// # Safety
+/// `pml4` must map the loader, the kernel entry and the kernel stack.
+pub(super) unsafe fn jump_to_kernel(
+    pml4: PhysicalAddress,
+    stack_top: VirtualAddress,
+    entry: VirtualAddress,
+) -> ! {
+    // the loader image (and thereby the GDT) is identity mapped in the kernel address space
+    let gdt_pointer = DescriptorTablePointer {
+        limit: (size_of_val(&GDT) - 1) as u16,
+        base: GDT.as_ptr() as u64,
+    };
+
+    unsafe {
+        asm!(
+            "cli",
+            "lgdt [rdi]",
+            // switch to custom paging
+            "mov cr3, rsi",
+            // set stack pointer to kernel stack top
+            "mov rsp, rdx",
+            // reload code segment
+            "push {code_selector}",
+            "lea rax, [rip + 2f]",
+            "push rax",
+            "retfq",
+            "2:",
+            "mov eax, {data_selector}",
+            "mov ds, ax",
+            "mov es, ax",
+            "mov fs, ax",
+            "mov gs, ax",
+            "mov ss, ax",
+            // zero return address, the kernel entry is jumped to by returning
+            "push 0",
+            "push rcx",
+            "xor eax, eax",
+            "xor ebx, ebx",
+            "xor ecx, ecx",
+            "xor edx, edx",
+            "xor esi, esi",
+            "xor edi, edi",
+            "xor ebp, ebp",
+            "xor r8d, r8d",
+            "xor r9d, r9d",
+            "xor r10d, r10d",
+            "xor r11d, r11d",
+            "xor r12d, r12d",
+            "xor r13d, r13d",
+            "xor r14d, r14d",
+            "xor r15d, r15d",
+            "ret",
+            code_selector = const CODE_SELECTOR,
+            data_selector = const DATA_SELECTOR,
+            in("rdi") &gdt_pointer,
+            in("rsi") pml4,
+            in("rdx") stack_top,
+            in("rcx") entry,
+            options(noreturn)
+        );
+    }
+}
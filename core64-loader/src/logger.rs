@@ -0,0 +1,114 @@
+use alloc::{format, string::String};
+use core::{
+    cell::UnsafeCell,
+    fmt::{self, Write},
+    ptr, slice,
+    sync::atomic::{AtomicPtr, Ordering},
+};
+
+use log::{Log, Metadata, Record};
+use uefi::{
+    prelude::BootServices,
+    proto::console::text::Output,
+    table::{
+        boot::{AllocateType::AnyPages, MemoryType},
+        Boot, SystemTable,
+    },
+};
+
+use core64_util::{
+    boot::log::{LoaderLog, LogRingBuffer},
+    memory::{PhysicalAddress, VirtualAddress, PAGE_SIZE},
+    serial::{SerialPort, COM1},
+};
+
+/// Size of the log ring buffer handed to the kernel in pages
+pub(super) const LOG_BUFFER_PAGE_COUNT: usize = 4;
+
+static LOGGER: Logger = Logger {
+    console: AtomicPtr::new(ptr::null_mut()),
+    serial: unsafe { SerialPort::new(COM1) },
+    ring_buffer: UnsafeCell::new(None),
+};
+
+/// Logs to the UEFI console while boot services are active and to the serial port afterward.
+/// Every record is mirrored into the log ring buffer, once it has been allocated.
+struct Logger {
+    /// UEFI console output, null once boot services have been exited
+    console: AtomicPtr<Output>,
+    serial: SerialPort,
+    ring_buffer: UnsafeCell<Option<LogRingBuffer<'static>>>,
+}
+
+impl Log for Logger {
+    fn enabled(&self, _metadata: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        // logging must never fail, so errors are ignored
+        let _ = match unsafe { self.console.load(Ordering::Acquire).as_mut() } {
+            Some(console) => write_record(console, record),
+            None => write_record(&mut { self.serial }, record),
+        };
+        if let Some(ring_buffer) = unsafe { &mut *self.ring_buffer.get() } {
+            let _ = write_record(ring_buffer, record);
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+// the loader only runs on the bootstrap processor
+unsafe impl Sync for Logger {}
+
+fn write_record(writer: &mut impl Write, record: &Record) -> fmt::Result {
+    writeln!(
+        writer,
+        "[{:>5}]: {}@{:03}: {}",
+        record.level(),
+        record.file().unwrap_or("<unknown file>"),
+        record.line().unwrap_or(0),
+        record.args()
+    )
+}
+
+/// Installs the loader logger, initially writing to the UEFI console
+pub(super) fn init(system_table: &mut SystemTable<Boot>) {
+    LOGGER
+        .console
+        .store(system_table.stdout() as *mut Output, Ordering::Release);
+    log::set_logger(&LOGGER).unwrap();
+    log::set_max_level(log::STATIC_MAX_LEVEL);
+}
+
+/// Switches the log sink to the serial port. Must be called before boot services are exited.
+pub(super) fn switch_to_serial() {
+    LOGGER.console.store(ptr::null_mut(), Ordering::Release);
+    let mut serial = LOGGER.serial;
+    serial.init();
+}
+
+/// Allocates the log ring buffer, which the kernel can use to replay loader messages. Messages logged before are not retained.
+pub(super) fn allocate_log_buffer(bt: &BootServices) -> Result<PhysicalAddress, String> {
+    let address = bt
+        .allocate_pages(AnyPages, MemoryType::LOADER_DATA, LOG_BUFFER_PAGE_COUNT)
+        .map_err(|error| format!("Could not allocate pages for log buffer: {error}."))?;
+
+    let buffer =
+        unsafe { slice::from_raw_parts_mut(address as *mut u8, LOG_BUFFER_PAGE_COUNT * PAGE_SIZE) };
+    unsafe { *LOGGER.ring_buffer.get() = Some(LogRingBuffer::new(buffer)) };
+
+    Ok(address)
+}
+
+/// Describes the log ring buffer mapped at `address`. Messages logged afterward are not reflected in the returned value.
+pub(super) fn loader_log(address: VirtualAddress) -> Option<LoaderLog> {
+    unsafe { &*LOGGER.ring_buffer.get() }
+        .as_ref()
+        .map(|ring_buffer| LoaderLog {
+            address,
+            capacity: ring_buffer.capacity(),
+            written: ring_buffer.written(),
+        })
+}
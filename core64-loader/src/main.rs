@@ -1,29 +1,67 @@
 #![no_std]
 #![no_main]
-#![feature(vec_into_raw_parts)]
 
 extern crate alloc;
 
-use alloc::vec::Vec;
-use core::{arch::asm, panic::PanicInfo};
+use alloc::{string::String, vec, vec::Vec};
+use core::{arch::asm, panic::PanicInfo, slice};
 
-use log::{error, info};
+use log::{error, info, warn};
 use uefi::{
     entry,
-    Handle,
-    Status, table::{Boot, boot::MemoryType, Runtime, SystemTable},
+    table::{boot::MemoryType, Boot, Runtime, SystemTable},
+    Handle, Status,
 };
 
-use core64_util::{BootInfo, memory::PAGE_SIZE};
+use core64_util::{
+    boot::{
+        entropy::{EntropySeed, ENTROPY_SEED_SIZE},
+        request::BootFeatures,
+        time::BootTimestamps,
+        variable::UefiRuntime,
+        BootInfo, BootInfoWriter,
+    },
+    memory::{
+        address::{PhysAddr, VirtAddr},
+        builder::MemoryMapBuilder,
+        paging::BOOT_DATA_MAPPING_OFFSET,
+        PAGE_SIZE,
+    },
+};
 
-use crate::memory::KernelInfo;
+use crate::{
+    limine::LimineBoot,
+    memory::{KernelInfo, BOOT_INFO_PAGE_COUNT},
+    multiboot::MultibootBoot,
+};
 
+mod chainload;
+mod config;
+mod cpu;
+mod entropy;
 mod file;
 mod graphics;
+mod limine;
+mod logger;
 mod memory;
+mod multiboot;
+mod network;
+mod slot;
+mod time;
+mod variable;
+mod verify;
 
-const KERNEL_FILE_NAME: &str = "kernel.elf";
+/// Paths searched for the kernel image on every filesystem, in order, unless configured otherwise
+const KERNEL_SEARCH_PATHS: &[&str] = &[
+    "\\kernel.elf",
+    "\\boot\\core64\\kernel.elf",
+    "\\EFI\\core64\\kernel.elf",
+];
 const KERNEL_STACK_SIZE: usize = 1024 * 1024; // 1MiB
+/// Unmapped pages below the kernel stack, catching stack overflows
+const KERNEL_STACK_GUARD_PAGES_BELOW: usize = 4;
+/// Unmapped pages above the kernel stack, catching stack underflows
+const KERNEL_STACK_GUARD_PAGES_ABOVE: usize = 1;
 
 type CoreMemoryMap = core64_util::memory::MemoryMap;
 type CoreMemoryDescriptor = core64_util::memory::MemoryDescriptor;
@@ -31,45 +69,280 @@ type CoreMemoryType = core64_util::memory::MemoryType;
 
 #[entry]
 fn main(image_handle: Handle, mut system_table: SystemTable<Boot>) -> Status {
+    // calibrate before the first timestamp, so that the stall does not count towards any boot stage
+    let tsc_frequency = time::tsc_frequency(system_table.boot_services());
+    let mut timestamps = BootTimestamps {
+        tsc_frequency,
+        loader_entry: time::timestamp(),
+        ..Default::default()
+    };
     uefi::helpers::init(&mut system_table).unwrap();
+    logger::init(&mut system_table);
     let boot_services = system_table.boot_services();
+    let log_buffer_address = logger::allocate_log_buffer(boot_services).unwrap();
 
     info!("Core64OS Bootloader started. Loading kernel entry...");
 
-    // load file data
-    let kernel_file_data =
-        file::get_file_data(image_handle, boot_services, KERNEL_FILE_NAME).unwrap();
+    // start configured EFI applications instead of or as fallback for the kernel
+    let config = config::load(image_handle, boot_services);
+    chainload::run_configured(image_handle, &system_table, &config);
+
+    // load file data from disk or the network, from the active kernel slot or the default search paths
+    let find_file = |search_paths: &[&str]| {
+        if config.network_boot {
+            return network::find_file(boot_services, config.tftp_server, search_paths);
+        }
+        file::find_file(image_handle, boot_services, search_paths).or_else(|error| {
+            warn!("{error} Trying network boot.");
+            network::find_file(boot_services, config.tftp_server, search_paths)
+        })
+    };
+    let kernel_search_paths: Vec<&str> = if config.kernel_paths.is_empty() {
+        KERNEL_SEARCH_PATHS.to_vec()
+    } else {
+        config.kernel_paths.iter().map(String::as_str).collect()
+    };
+    let kernel_file = slot::find_kernel(system_table.runtime_services(), &config, find_file)
+        .unwrap_or_else(|| find_file(&kernel_search_paths))
+        .unwrap();
+
+    // verify file data against manifest, which is also used for the modules
+    let manifest =
+        verify::Manifest::load(boot_services, kernel_file.source, &config.manifest).unwrap();
+    verify::verify_file(&manifest, &kernel_file).unwrap();
 
     // parse elf
-    let (kernel_entry_address, kernel_code_address, kernel_code_page_count) =
-        file::parse_elf(kernel_file_data, boot_services).unwrap();
+    let kernel = file::parse_elf(kernel_file.data, boot_services).unwrap();
+    let boot_requests = kernel.boot_requests;
+    timestamps.kernel_loaded = time::timestamp();
 
     // initialize framebuffer
-    let framebuffer_metadata = graphics::initialize_framebuffer(boot_services).unwrap();
+    let framebuffer_resolution = match kernel.multiboot {
+        Some(multiboot_kernel) => multiboot_kernel.framebuffer_resolution,
+        None => (boot_requests.framebuffer_width != 0 && boot_requests.framebuffer_height != 0)
+            .then_some((
+                boot_requests.framebuffer_width as usize,
+                boot_requests.framebuffer_height as usize,
+            )),
+    };
+    let framebuffer_metadata =
+        graphics::initialize_framebuffer(boot_services, framebuffer_resolution).unwrap();
+    timestamps.framebuffer_ready = time::timestamp();
+
+    // show the boot splash, keeping further log messages off the console
+    let mut splash = config.splash.as_deref().and_then(|path| {
+        graphics::draw_splash(image_handle, boot_services, path, &framebuffer_metadata)
+            .map_err(|error| warn!("{error}"))
+            .ok()
+    });
+    if splash.is_some() {
+        logger::switch_to_serial();
+    }
+    graphics::advance_splash(&mut splash, &framebuffer_metadata, 10);
+
+    // Multiboot2 kernels are entered with boot services running, after loading their modules
+    if let Some(multiboot_kernel) = kernel.multiboot {
+        let load_options = file::LoadOptions::read(boot_services, image_handle);
+        let modules = file::load_modules(
+            boot_services,
+            &manifest,
+            kernel_file.source,
+            kernel_file.boot_device,
+            &load_options.module_paths,
+        )
+        .unwrap();
+        let multiboot_boot = MultibootBoot::new(
+            boot_services,
+            image_handle,
+            load_options.command_line,
+            modules,
+            multiboot_kernel,
+            system_table.as_ptr() as u64,
+            cpu::rsdp_address(&system_table),
+        )
+        .unwrap();
+        let info = multiboot_boot
+            .write_info(boot_services, &framebuffer_metadata)
+            .unwrap();
+        info!("Jumping to Multiboot2 kernel entry with boot services running...");
+        unsafe { multiboot::jump_to_kernel(multiboot_boot.entry(), info) };
+    }
+
+    // discover processors
+    let cpus = cpu::discover_cpus(&system_table).unwrap_or_else(|error| {
+        warn!("Could not discover processors: {error}");
+        Vec::new()
+    });
 
     // allocate kernel stack
+    let kernel_stack_size = match boot_requests.stack_size {
+        0 => KERNEL_STACK_SIZE,
+        stack_size => stack_size as usize,
+    };
     let (kernel_stack_address, kernel_stack_page_count) =
-        memory::allocate_stack(boot_services).unwrap();
+        memory::allocate_stack(boot_services, kernel_stack_size).unwrap();
 
     // allocate boot info
     let (boot_info_address, mmap_descriptors) = memory::allocate_boot_info(boot_services).unwrap();
 
+    // gather entropy for the kernel
+    let entropy_seed_address = entropy::allocate_entropy_seed(boot_services).unwrap();
+
+    // prepare responses for kernels booted in Limine compatibility mode, loading modules only if requested
+    let limine_boot = (!kernel.limine_markers.is_empty())
+        .then(|| {
+            let modules = if limine::requests_modules(&kernel.limine_markers) {
+                let load_options = file::LoadOptions::read(boot_services, image_handle);
+                file::load_modules(
+                    boot_services,
+                    &manifest,
+                    kernel_file.source,
+                    kernel_file.boot_device,
+                    &load_options.module_paths,
+                )?
+            } else {
+                Vec::new()
+            };
+            LimineBoot::new(
+                boot_services,
+                kernel.limine_markers,
+                modules,
+                kernel.physical_address,
+                kernel.virtual_address,
+                cpu::rsdp_address(&system_table),
+                mmap_descriptors.len(),
+            )
+        })
+        .transpose()
+        .unwrap();
+    let mmio = memory::mmio_ranges(boot_services).unwrap();
+    let physical_end = memory::physical_end(boot_services, &framebuffer_metadata, &mmio).unwrap();
+    let hhdm_offset = match limine_boot {
+        Some(_) => Some(limine::HHDM_OFFSET),
+        None => memory::hhdm_offset(&boot_requests).unwrap(),
+    };
+
+    let boot_data = vec![
+        (PhysAddr::new(entropy_seed_address), 1),
+        (
+            PhysAddr::new(log_buffer_address),
+            logger::LOG_BUFFER_PAGE_COUNT,
+        ),
+    ];
+
     let kernel_info = KernelInfo {
-        kernel_code_address,
-        kernel_code_page_count,
+        kernel_code_address: PhysAddr::new(kernel.physical_address),
+        kernel_virtual_address: VirtAddr::new(kernel.virtual_address),
+        kernel_code_page_count: kernel.page_count,
         kernel_stack_address,
         kernel_stack_page_count,
         boot_info_address,
+        boot_data,
+        modules: limine_boot
+            .as_ref()
+            .map(|limine_boot| limine_boot.module_pages().collect())
+            .unwrap_or_default(),
+        hhdm: hhdm_offset
+            .map(|offset| memory::higher_half_direct_map(offset, physical_end))
+            .transpose()
+            .unwrap(),
+        framebuffer: framebuffer_metadata,
+        mmio,
+    };
+    let wall_clock_time = time::wall_clock_time(system_table.runtime_services());
+    // runtime services stay identity mapped, e.g. to mark the boot successful
+    let uefi_runtime = UefiRuntime {
+        system_table: system_table.as_ptr() as u64,
+        runtime_services: system_table.runtime_services() as *const _ as u64,
     };
+
+    // refuse to boot kernels requiring boot info, that cannot be provided
+    let mut provided_features = BootFeatures::MEMORY_MAP
+        | BootFeatures::FRAME_ALLOCATOR
+        | BootFeatures::KERNEL_STACK
+        | BootFeatures::ENTROPY_SEED
+        | BootFeatures::BOOT_TIMESTAMPS
+        | BootFeatures::LOADER_LOG
+        | BootFeatures::BOOT_DEVICE
+        | BootFeatures::UEFI_RUNTIME;
+    provided_features.set(BootFeatures::CPUS, !cpus.is_empty());
+    provided_features.set(BootFeatures::WALL_CLOCK_TIME, wall_clock_time.is_some());
+    provided_features.set(BootFeatures::HHDM, kernel_info.hhdm.is_some());
+    provided_features.set(BootFeatures::BOOT_SPLASH, splash.is_some());
+    let missing_features = boot_requests.required_features - provided_features;
+    assert!(
+        missing_features.is_empty(),
+        "Kernel requires unavailable boot info features: {missing_features:?}"
+    );
+
+    graphics::advance_splash(&mut splash, &framebuffer_metadata, 25);
+
     // exit boot services
     let (_runtime, memory_map) = drop_boot_services(system_table, mmap_descriptors, &kernel_info);
+    timestamps.boot_services_exited = time::timestamp();
+    graphics::advance_splash(&mut splash, &framebuffer_metadata, 40);
 
     // set up address space
-    let (pml4, rsp, virtual_boot_info_address) =
-        memory::set_up_address_space(&memory_map, kernel_info).unwrap();
+    let address_space = memory::set_up_address_space(&memory_map, kernel_info).unwrap();
+    timestamps.paging_set_up = time::timestamp();
+    if config.log_page_tables {
+        memory::log_address_space(&address_space);
+    }
+    memory::load_pat();
+    // the remaining progress is left to the kernel
+    graphics::advance_splash(&mut splash, &framebuffer_metadata, 50);
+    info!("Address space set up. Jumping to kernel entry...");
 
-    let boot_info = unsafe { &mut *(boot_info_address as *mut BootInfo) };
-    boot_info.frame_buffer_metadata = framebuffer_metadata;
+    let boot_info_buffer = unsafe {
+        slice::from_raw_parts_mut(
+            boot_info_address.as_u64() as *mut u8,
+            BOOT_INFO_PAGE_COUNT * PAGE_SIZE,
+        )
+    };
+    let boot_info = BootInfo::new(
+        address_space.framebuffer,
+        address_space.memory_map,
+        address_space.frame_allocator,
+        address_space.kernel_stack,
+    );
+    let mut boot_info_writer = BootInfoWriter::new(boot_info_buffer, boot_info).unwrap();
+    if !cpus.is_empty() {
+        boot_info_writer.add_tag_slice(&cpus).unwrap();
+    }
+    boot_info_writer
+        .add_tag(&EntropySeed {
+            address: BOOT_DATA_MAPPING_OFFSET + entropy_seed_address,
+            size: ENTROPY_SEED_SIZE as u64,
+        })
+        .unwrap();
+    boot_info_writer.add_tag(&kernel_file.boot_device).unwrap();
+    if let Some(hhdm) = address_space.hhdm {
+        boot_info_writer.add_tag(&hhdm).unwrap();
+    }
+    if let Some(wall_clock_time) = wall_clock_time {
+        boot_info_writer.add_tag(&wall_clock_time).unwrap();
+    }
+    boot_info_writer.add_tag(&uefi_runtime).unwrap();
+    if let Some(splash) = &splash {
+        boot_info_writer.add_tag(splash).unwrap();
+    }
+    timestamps.kernel_jump = time::timestamp();
+    boot_info_writer.add_tag(&timestamps).unwrap();
+    if let Some(loader_log) = logger::loader_log(BOOT_DATA_MAPPING_OFFSET + log_buffer_address) {
+        boot_info_writer.add_tag(&loader_log).unwrap();
+    }
+    boot_info_writer.finish();
+
+    if let Some(limine_boot) = limine_boot {
+        limine_boot.answer_requests(&memory_map, &address_space, &framebuffer_metadata);
+        unsafe {
+            limine::jump_to_kernel(
+                address_space.pml4.as_u64(),
+                address_space.kernel_stack.top,
+                kernel.entry,
+            )
+        };
+    }
 
     unsafe {
         asm!(
@@ -81,10 +354,10 @@ fn main(image_handle: Handle, mut system_table: SystemTable<Boot>) -> Status {
             "mov rsp, {1}",
             // jump to kernel entry
             "jmp {3}",
-            in(reg) virtual_boot_info_address,
-            in(reg) rsp,
-            in(reg) pml4,
-            in(reg) kernel_entry_address
+            in(reg) address_space.boot_info_address.as_u64(),
+            in(reg) address_space.kernel_stack.top,
+            in(reg) address_space.pml4.as_u64(),
+            in(reg) kernel.entry
         );
     }
     // should never happen
@@ -94,102 +367,101 @@ fn main(image_handle: Handle, mut system_table: SystemTable<Boot>) -> Status {
 /// Drops boot services and returns converted memory map and runtime system table
 fn drop_boot_services(
     system_table: SystemTable<Boot>,
-    mut descriptors: Vec<CoreMemoryDescriptor>,
+    descriptors: Vec<CoreMemoryDescriptor>,
     kernel_info: &KernelInfo,
 ) -> (SystemTable<Runtime>, CoreMemoryMap) {
-    // drop boot services
+    // drop boot services, the uefi console is unusable afterward
+    logger::switch_to_serial();
     let (runtime, uefi_mmap) = unsafe { system_table.exit_boot_services(MemoryType::LOADER_DATA) };
 
-    let mut first_addr = u64::MAX;
-    let mut first_available_addr = u64::MAX;
-    let mut last_addr = u64::MIN;
-    let mut last_available_addr = u64::MIN;
+    // descriptor buffer is handed over to the kernel as part of the memory map
+    let descriptors = descriptors.leak();
     let desc_start_addr = descriptors.as_ptr() as u64;
-    let desc_end_addr =
-        desc_start_addr + (descriptors.capacity() * size_of::<CoreMemoryDescriptor>()) as u64;
-    // collect available memory descriptors (convert uefi mmap to core64 mmap)
-    uefi_mmap.entries().for_each(|descriptor| {
-        let phys_end = descriptor.phys_start + descriptor.page_count * PAGE_SIZE as u64;
-
-        if descriptor.phys_start < first_addr {
-            first_addr = descriptor.phys_start;
-        }
+    let desc_end_addr = desc_start_addr + size_of_val(descriptors) as u64;
 
-        if descriptor.phys_start != 0x0
-            && matches!(
-                descriptor.ty,
-                MemoryType::CONVENTIONAL
-                    | MemoryType::BOOT_SERVICES_CODE
-                    | MemoryType::BOOT_SERVICES_DATA
-            )
-        {
-            if descriptor.phys_start < first_available_addr {
-                first_available_addr = descriptor.phys_start;
-            }
-            if phys_end > last_available_addr {
-                last_available_addr = phys_end;
-            }
-        }
-
-        if phys_end > last_addr {
-            last_addr = phys_end;
-        }
+    let mut builder = MemoryMapBuilder::new(descriptors);
 
-        let r#type = if descriptor.phys_start < 0x1000 {
-            CoreMemoryType::Reserved
-        }
-        // mark mmap data as kernel data and boot info struct
-        else if (descriptor.phys_start <= desc_start_addr && phys_end >= desc_end_addr)
-            || descriptor.phys_start <= kernel_info.boot_info_address
-                && phys_end >= kernel_info.boot_info_address + PAGE_SIZE as u64
-        {
-            CoreMemoryType::KernelData
-        }
-        // mark kernel file as kernel code
-        else if descriptor.phys_start <= kernel_info.kernel_code_address
-            && phys_end
-                >= kernel_info.kernel_code_address
-                    + (kernel_info.kernel_code_page_count * PAGE_SIZE) as u64
-        {
-            CoreMemoryType::KernelCode
-        }
-        // mark stack as kernel stack
-        else if descriptor.phys_start <= kernel_info.kernel_stack_address
-            && phys_end
-                >= kernel_info.kernel_stack_address
-                    + (kernel_info.kernel_stack_page_count * PAGE_SIZE) as u64
-        {
-            CoreMemoryType::KernelStack
-        } else {
+    // collect memory descriptors (convert uefi mmap to core64 mmap)
+    uefi_mmap
+        .entries()
+        .try_for_each(|descriptor| {
             // Determine the core memory type based on the UEFI memory type
-            match descriptor.ty {
+            let r#type = match descriptor.ty {
                 MemoryType::CONVENTIONAL
                 | MemoryType::BOOT_SERVICES_DATA
                 | MemoryType::BOOT_SERVICES_CODE => CoreMemoryType::Available,
+                // loader image and every loader allocation (uefi heap, kernel file buffer). Memory handed to the kernel is marked below.
+                MemoryType::LOADER_CODE | MemoryType::LOADER_DATA => {
+                    CoreMemoryType::LoaderReclaimable
+                }
                 _ => CoreMemoryType::Reserved,
-            }
-        };
+            };
+            builder.push(descriptor.phys_start, descriptor.page_count, r#type)
+        })
+        .unwrap();
 
-        descriptors.push(CoreMemoryDescriptor {
-            phys_start: descriptor.phys_start,
-            phys_end,
-            num_pages: descriptor.page_count,
-            r#type,
-        });
-    });
+    let kernel_code_end =
+        kernel_info.kernel_code_address + (kernel_info.kernel_code_page_count * PAGE_SIZE) as u64;
+    let kernel_stack_end =
+        kernel_info.kernel_stack_address + (kernel_info.kernel_stack_page_count * PAGE_SIZE) as u64;
+
+    // mark kernel file as kernel code
+    builder
+        .mark(
+            kernel_info.kernel_code_address.as_u64(),
+            kernel_code_end.as_u64(),
+            CoreMemoryType::KernelCode,
+        )
+        .unwrap();
+    // mark stack as kernel stack
+    builder
+        .mark(
+            kernel_info.kernel_stack_address.as_u64(),
+            kernel_stack_end.as_u64(),
+            CoreMemoryType::KernelStack,
+        )
+        .unwrap();
+    // mark mmap data and boot info struct as kernel data
+    builder
+        .mark(desc_start_addr, desc_end_addr, CoreMemoryType::KernelData)
+        .unwrap();
+    builder
+        .mark(
+            kernel_info.boot_info_address.as_u64(),
+            (kernel_info.boot_info_address + (BOOT_INFO_PAGE_COUNT * PAGE_SIZE) as u64).as_u64(),
+            CoreMemoryType::KernelData,
+        )
+        .unwrap();
+    // mark modules like the kernel file
+    kernel_info
+        .modules
+        .iter()
+        .try_for_each(|(address, page_count)| {
+            builder.mark(
+                address.as_u64(),
+                (*address + (page_count * PAGE_SIZE) as u64).as_u64(),
+                CoreMemoryType::KernelCode,
+            )
+        })
+        .unwrap();
+    // mark additional boot data as kernel data
+    kernel_info
+        .boot_data
+        .iter()
+        .try_for_each(|(address, page_count)| {
+            builder.mark(
+                address.as_u64(),
+                (*address + (page_count * PAGE_SIZE) as u64).as_u64(),
+                CoreMemoryType::KernelData,
+            )
+        })
+        .unwrap();
+    // never hand out the null page
+    builder
+        .mark(0x0, PAGE_SIZE as u64, CoreMemoryType::Reserved)
+        .unwrap();
 
-    let (ptr, len, _cap) = descriptors.into_raw_parts();
-    (
-        runtime,
-        CoreMemoryMap {
-            descriptors: ptr as *mut CoreMemoryDescriptor,
-            descriptors_len: len as u64,
-            first_addr,
-            first_available_addr,
-            last_addr,
-            last_available_addr,
-        },
-    )
+    (runtime, builder.build())
 }
 
 #[panic_handler]
@@ -1,39 +1,136 @@
 use alloc::{
     format,
     string::{String, ToString},
+    vec,
     vec::Vec,
 };
 use core::ptr;
 
+use log::info;
 use uefi::{
-    data_types::VirtualAddress,
     prelude::BootServices,
     table::boot::{AllocateType::AnyPages, MemoryType},
 };
 
-use core64_util::memory::{
-    PAGE_SIZE,
-    paging::{
-        KERNEL_MAPPING_OFFSET,
-        KERNEL_STACK_MAPPING_OFFSET, manager::{PageFrameAllocator, PageTableManager}, PageEntryFlags, PageTable,
+use core64_util::{
+    boot::{
+        request::{BootRequests, HigherHalfDirectMap},
+        KernelStack, BOOT_INFO_MAX_SIZE,
     },
-    PhysicalAddress, pmm::{BitMapAllocator, PageFrameAllocatorError},
+    graphics::framebuffer::FrameBufferMetadata,
+    memory::{
+        address::{PhysAddr, VirtAddr},
+        page::{Page, PhysFrame},
+        paging::{
+            dump::Mappings,
+            manager::{PageFrameAllocator, PageTableManager},
+            pat::{self, CacheType},
+            PageEntryFlags, PageTable, PagingMode, BOOT_DATA_MAPPING_OFFSET,
+            FRAMEBUFFER_MAPPING_ADDRESS, HIGHER_HALF_START, KERNEL_STACK_MAPPING_OFFSET,
+        },
+        pmm::{BitMapAllocator, BitMapAllocatorState, PageFrameAllocatorError},
+        PhysicalAddress, PAGE_SIZE,
+    },
+};
+
+use crate::{
+    CoreMemoryDescriptor, CoreMemoryMap, CoreMemoryType, KERNEL_STACK_GUARD_PAGES_ABOVE,
+    KERNEL_STACK_GUARD_PAGES_BELOW,
 };
 
-use crate::{CoreMemoryDescriptor, CoreMemoryMap, KERNEL_STACK_SIZE};
+/// Additional memory map descriptors allocated on top of the uefi memory map entry count
+const MEMORY_MAP_PADDING: usize = 32;
+/// Pages allocated for boot info and its tag list
+pub(super) const BOOT_INFO_PAGE_COUNT: usize = BOOT_INFO_MAX_SIZE / PAGE_SIZE;
 
 #[derive(Clone, Debug)]
 pub(super) struct KernelInfo {
-    pub(super) kernel_code_address: PhysicalAddress,
+    pub(super) kernel_code_address: PhysAddr,
+    /// Virtual address the kernel code is mapped at
+    pub(super) kernel_virtual_address: VirtAddr,
     pub(super) kernel_code_page_count: usize,
-    pub(super) kernel_stack_address: PhysicalAddress,
+    pub(super) kernel_stack_address: PhysAddr,
     pub(super) kernel_stack_page_count: usize,
-    pub(super) boot_info_address: PhysicalAddress,
+    pub(super) boot_info_address: PhysAddr,
+    /// Additional page aligned kernel data regions (physical address, page count), mapped at [`BOOT_DATA_MAPPING_OFFSET`]
+    pub(super) boot_data: Vec<(PhysAddr, usize)>,
+    /// Modules of kernels booted in Limine compatibility mode (physical address, page count), reported like the kernel code
+    pub(super) modules: Vec<(PhysAddr, usize)>,
+    /// Higher half direct map of all physical memory validated by [`higher_half_direct_map`], if requested by the kernel
+    pub(super) hhdm: Option<HigherHalfDirectMap>,
+    /// Framebuffer with its physical base address, mapped at [`FRAMEBUFFER_MAPPING_ADDRESS`]
+    pub(super) framebuffer: FrameBufferMetadata,
+    /// Memory mapped IO ranges (physical address, page count), mapped uncached into the identity and direct map
+    pub(super) mmio: Vec<(PhysAddr, usize)>,
+}
+
+/// Validates the higher half direct map offset requested by the kernel. Returns `None`, if no direct map was requested.
+pub(super) fn hhdm_offset(boot_requests: &BootRequests) -> Result<Option<u64>, String> {
+    let offset = boot_requests.hhdm_offset;
+    if offset == 0 {
+        return Ok(None);
+    }
+
+    if !offset.is_multiple_of(PAGE_SIZE as u64)
+        || !(HIGHER_HALF_START..BOOT_DATA_MAPPING_OFFSET).contains(&offset)
+    {
+        return Err(format!(
+            "Invalid higher half direct map offset: {offset:#x}."
+        ));
+    }
+    Ok(Some(offset))
+}
+
+/// End of physical memory, including memory mapped IO and the framebuffer, which might be located above the last memory map
+/// descriptor
+pub(super) fn physical_end(
+    bt: &BootServices,
+    framebuffer: &FrameBufferMetadata,
+    mmio: &[(PhysAddr, usize)],
+) -> Result<PhysAddr, String> {
+    let memory_map = bt
+        .memory_map(MemoryType::LOADER_DATA)
+        .map_err(|error| format!("Could not get uefi memory map: {error}"))?;
+    let framebuffer_end =
+        PhysAddr::new(framebuffer.base + framebuffer.size as u64).align_up(PAGE_SIZE as u64);
+
+    Ok(memory_map
+        .entries()
+        .map(|descriptor| {
+            PhysAddr::new(descriptor.phys_start + descriptor.page_count * PAGE_SIZE as u64)
+        })
+        .chain(mmio.iter().map(|&(physical_address, page_count)| {
+            physical_address + (PAGE_SIZE * page_count) as u64
+        }))
+        .fold(framebuffer_end, PhysAddr::max))
 }
 
-/// Allocate pages for kernel stack. Returns physical address of allocated stack and amount of pages allocated.
-pub(super) fn allocate_stack(bt: &BootServices) -> Result<(PhysicalAddress, usize), String> {
-    let num_pages = (KERNEL_STACK_SIZE + PAGE_SIZE - 1) / PAGE_SIZE;
+/// Direct map of physical memory up to `physical_end` at `offset`. Fails, if it overlaps the boot data mapping.
+pub(super) fn higher_half_direct_map(
+    offset: u64,
+    physical_end: PhysAddr,
+) -> Result<HigherHalfDirectMap, String> {
+    let physical_end = physical_end.as_u64();
+    if offset
+        .checked_add(physical_end)
+        .is_none_or(|end| end > BOOT_DATA_MAPPING_OFFSET)
+    {
+        return Err(format!(
+            "Higher half direct map at {offset:#x} of {physical_end:#x} bytes overlaps boot data."
+        ));
+    }
+    Ok(HigherHalfDirectMap {
+        offset,
+        size: physical_end,
+    })
+}
+
+/// Allocate pages for kernel stack of `stack_size` bytes. Returns physical address of allocated stack and amount of pages allocated.
+pub(super) fn allocate_stack(
+    bt: &BootServices,
+    stack_size: usize,
+) -> Result<(PhysAddr, usize), String> {
+    let num_pages = (stack_size + PAGE_SIZE - 1) / PAGE_SIZE;
     let start_addr = bt
         .allocate_pages(AnyPages, MemoryType::LOADER_DATA, num_pages)
         .map_err(|_| {
@@ -42,15 +139,37 @@ pub(super) fn allocate_stack(bt: &BootServices) -> Result<(PhysicalAddress, usiz
                 num_pages
             )
         })?;
-    Ok((start_addr, num_pages))
+    Ok((PhysAddr::new(start_addr), num_pages))
 }
-/// Allocate a single page to store the boot information in. As well as uefi memory map descriptors
+/// Memory mapped IO ranges (physical address, page count) reported by the firmware
+pub(super) fn mmio_ranges(bt: &BootServices) -> Result<Vec<(PhysAddr, usize)>, String> {
+    let memory_map = bt
+        .memory_map(MemoryType::LOADER_DATA)
+        .map_err(|error| format!("Could not get uefi memory map: {error}"))?;
+    Ok(memory_map
+        .entries()
+        .filter(|descriptor| {
+            matches!(
+                descriptor.ty,
+                MemoryType::MMIO | MemoryType::MMIO_PORT_SPACE
+            )
+        })
+        .map(|descriptor| {
+            (
+                PhysAddr::new(descriptor.phys_start),
+                descriptor.page_count as usize,
+            )
+        })
+        .collect())
+}
+
+/// Allocate pages to store the boot information in. As well as uefi memory map descriptors
 pub(super) fn allocate_boot_info(
     bt: &BootServices,
-) -> Result<(PhysicalAddress, Vec<CoreMemoryDescriptor>), String> {
+) -> Result<(PhysAddr, Vec<CoreMemoryDescriptor>), String> {
     let boot_info_addr = bt
-        .allocate_pages(AnyPages, MemoryType::LOADER_DATA, 1)
-        .map_err(|_| "Could not allocate page for kernel boot information.".to_string())?;
+        .allocate_pages(AnyPages, MemoryType::LOADER_DATA, BOOT_INFO_PAGE_COUNT)
+        .map_err(|_| "Could not allocate pages for kernel boot information.".to_string())?;
 
     // get uefi mmap meta data to allocate enough later for custom memory map in `drop_boot_services`
     let uefi_memory_map_meta = bt
@@ -59,83 +178,359 @@ pub(super) fn allocate_boot_info(
         .as_raw()
         .1;
 
-    // allocate enough memory for the map. Add additional padding in case map size changes
-    let sufficient_memory_map_size = uefi_memory_map_meta.entry_count() + 8;
+    // allocate enough memory for the map. Add additional padding in case map size changes and for descriptors split while marking kernel memory
+    let sufficient_memory_map_size = uefi_memory_map_meta.entry_count() + MEMORY_MAP_PADDING;
 
     // allocate descriptors in memory
-    let descriptors = Vec::with_capacity(sufficient_memory_map_size);
+    let descriptors = vec![CoreMemoryDescriptor::default(); sufficient_memory_map_size];
 
-    Ok((boot_info_addr, descriptors))
+    Ok((PhysAddr::new(boot_info_addr), descriptors))
+}
+/// Kernel address space set up by [`set_up_address_space`]
+#[derive(Debug)]
+pub(super) struct AddressSpace {
+    /// Physical address of the top level table (pml4, or pml5 with 5-level paging)
+    pub(super) pml4: PhysAddr,
+    /// Paging mode of the firmware, which the tables are set up for
+    pub(super) paging_mode: PagingMode,
+    /// Higher half kernel stack layout including guard pages
+    pub(super) kernel_stack: KernelStack,
+    /// Higher half address of the boot info page
+    pub(super) boot_info_address: VirtAddr,
+    /// Memory map with descriptors pointing to their higher half mapping
+    pub(super) memory_map: CoreMemoryMap,
+    /// Final state of the page frame allocator with the bitmap pointing to its higher half mapping
+    pub(super) frame_allocator: BitMapAllocatorState,
+    /// Physical address of the page frame allocator bitmap
+    pub(super) bit_map_address: PhysAddr,
+    /// Higher half direct map of all physical memory, if requested by the kernel
+    pub(super) hhdm: Option<HigherHalfDirectMap>,
+    /// Framebuffer with its virtual base address
+    pub(super) framebuffer: FrameBufferMetadata,
 }
-/// Sets up paging that includes mappings for higher half kernel and higher half stack. Returns address pointing to page table manager, stack pointer and pointer to boot info.
+
+/// Sets up paging that includes mappings for higher half kernel and higher half stack, as well as boot info, memory map and page frame allocator bitmap directly after the kernel.
 pub(super) fn set_up_address_space(
     memory_map: &CoreMemoryMap,
     kernel_info: KernelInfo,
-) -> Result<(PhysicalAddress, VirtualAddress, VirtualAddress), PageFrameAllocatorError> {
+) -> Result<AddressSpace, PageFrameAllocatorError> {
     let KernelInfo {
         kernel_code_address,
+        kernel_virtual_address,
         kernel_code_page_count,
         kernel_stack_address,
         kernel_stack_page_count,
         boot_info_address,
+        boot_data,
+        // identity and direct mapped like all physical memory
+        modules: _,
+        hhdm,
+        framebuffer,
+        mmio,
     } = kernel_info;
 
     // set up physical memory manager
     let mut pmm = BitMapAllocator::try_new(*memory_map)?;
 
-    let pml4_addr = pmm.request_page()?;
-    assert_eq!(
-        (pml4_addr as usize) % align_of::<PageTable>(),
-        0,
+    let pml4_addr = pmm.request_page()?.start_address();
+    assert!(
+        pml4_addr.is_aligned(align_of::<PageTable>() as u64),
         "pml4 pointer is not aligned"
     );
 
-    let pml4_table = pml4_addr as *mut PageTable;
+    let pml4_table = pml4_addr.as_u64() as *mut PageTable;
 
     // zero out new table
     unsafe { ptr::write_bytes(pml4_table, 0, 1) };
 
+    // paging mode cannot be switched while paging is enabled, so the new tables use the mode of the firmware
+    let paging_mode = unsafe { PagingMode::current() };
+    info!("Setting up {}-level paging.", paging_mode.levels());
     let mut manager: PageTableManager<BitMapAllocator, PageFrameAllocatorError> =
-        PageTableManager::new(pml4_table, pmm);
-    let first_addr = memory_map.first_addr;
-    let last_addr = memory_map.last_addr;
-    let page_count = ((last_addr - first_addr) as usize + PAGE_SIZE - 1) / PAGE_SIZE;
+        PageTableManager::with_paging_mode(pml4_table, paging_mode, pmm);
+    let first_addr = PhysAddr::new(memory_map.first_addr);
+    let last_addr = PhysAddr::new(memory_map.last_addr);
+    let page_count = (last_addr - first_addr).div_ceil(PAGE_SIZE as u64) as usize;
     // identity map entire available physical address space
-    for page in 0..page_count {
-        let physical_address = (PAGE_SIZE * page) as u64 + first_addr;
-        manager.map_memory(
-            physical_address,
-            physical_address,
-            PageEntryFlags::default(),
+    map_pages(
+        &mut manager,
+        direct_map_page(0, first_addr),
+        PhysFrame::containing_address(first_addr),
+        page_count,
+    )?;
+
+    // memory mapped IO and the framebuffer might be located above the last memory map descriptor
+    let framebuffer_base = PhysAddr::new(framebuffer.base);
+    let framebuffer_frame = PhysFrame::containing_address(framebuffer_base);
+    let framebuffer_offset = framebuffer_base - framebuffer_frame.start_address();
+    let framebuffer_page_count =
+        (framebuffer_offset as usize + framebuffer.size).div_ceil(PAGE_SIZE);
+
+    // map physical memory to the direct map offset requested by the kernel, its size was validated before boot services were
+    // exited
+    if let Some(hhdm) = hhdm {
+        let hhdm_page_count =
+            page_count.min((hhdm.size.saturating_sub(first_addr.as_u64()) as usize) / PAGE_SIZE);
+        map_pages(
+            &mut manager,
+            direct_map_page(hhdm.offset, first_addr),
+            PhysFrame::containing_address(first_addr),
+            hhdm_page_count,
         )?;
     }
+    let direct_map_offsets = [Some(0), hhdm.map(|hhdm| hhdm.offset)];
 
-    // map higher half kernel virtual addresses to physical kernel addresses
-    for page in 0..kernel_code_page_count {
-        let physical_address = ((PAGE_SIZE * page) as u64) + kernel_code_address;
-        let virtual_address = KERNEL_MAPPING_OFFSET + physical_address;
-        manager.map_memory(virtual_address, physical_address, PageEntryFlags::default())?;
+    // map memory mapped IO uncached into the identity and direct map
+    for &(physical_address, page_count) in &mmio {
+        for offset in direct_map_offsets.into_iter().flatten() {
+            manager.map_mmio(
+                direct_map_page(offset, physical_address),
+                physical_address,
+                PAGE_SIZE * page_count,
+            )?;
+        }
+    }
+
+    // map framebuffer write combining, or uncached without PAT support. Every mapping uses the same memory type.
+    let framebuffer_cache_type = if pat::is_supported() {
+        CacheType::WriteCombining
+    } else {
+        CacheType::Uncacheable
+    };
+    let framebuffer_flags = PageEntryFlags::default().with_cache_type(framebuffer_cache_type);
+    let framebuffer_pages = direct_map_offsets
+        .into_iter()
+        .flatten()
+        .map(|offset| direct_map_page(offset, framebuffer_frame.start_address()))
+        .chain([Page::containing_address(VirtAddr::new(
+            FRAMEBUFFER_MAPPING_ADDRESS,
+        ))]);
+    for page in framebuffer_pages {
+        map_pages_with_flags(
+            &mut manager,
+            page,
+            framebuffer_frame,
+            framebuffer_page_count,
+            framebuffer_flags,
+        )?;
     }
 
-    // map boot info page to higher half directly after kernel
+    // map higher half kernel virtual addresses to physical kernel addresses
+    map_pages(
+        &mut manager,
+        Page::containing_address(kernel_virtual_address),
+        PhysFrame::containing_address(kernel_code_address),
+        kernel_code_page_count,
+    )?;
+
+    // map boot info pages to higher half directly after kernel
     let virtual_boot_info_address =
-        KERNEL_MAPPING_OFFSET + (PAGE_SIZE * kernel_code_page_count) as u64;
-    manager.map_memory(
-        virtual_boot_info_address,
-        boot_info_address,
-        PageEntryFlags::default(),
+        kernel_virtual_address + (PAGE_SIZE * kernel_code_page_count) as u64;
+    map_pages(
+        &mut manager,
+        Page::containing_address(virtual_boot_info_address),
+        PhysFrame::containing_address(boot_info_address),
+        BOOT_INFO_PAGE_COUNT,
+    )?;
+
+    // map memory map descriptors directly after boot info
+    let descriptors_address = PhysAddr::new(memory_map.descriptors as u64);
+    let descriptors_offset = descriptors_address.as_u64() % PAGE_SIZE as u64;
+    let descriptors_page_count = (descriptors_offset as usize
+        + memory_map.descriptors_len as usize * size_of::<CoreMemoryDescriptor>())
+    .div_ceil(PAGE_SIZE);
+    let virtual_descriptors_address =
+        virtual_boot_info_address + (PAGE_SIZE * BOOT_INFO_PAGE_COUNT) as u64;
+    map_pages(
+        &mut manager,
+        Page::containing_address(virtual_descriptors_address),
+        PhysFrame::containing_address(descriptors_address),
+        descriptors_page_count,
+    )?;
+
+    // map page frame allocator bitmap directly after memory map, the loader accesses it through the identity map
+    let bit_map = manager.frame_allocator().state();
+    let bit_map_address = PhysAddr::new(bit_map.bit_map.as_u64());
+    let bit_map_page_count = (bit_map.bit_map_size as usize).div_ceil(PAGE_SIZE);
+    let virtual_bit_map_address =
+        virtual_descriptors_address + (PAGE_SIZE * descriptors_page_count) as u64;
+    map_pages(
+        &mut manager,
+        Page::containing_address(virtual_bit_map_address),
+        PhysFrame::containing_address(bit_map_address),
+        bit_map_page_count,
     )?;
 
-    // map stack to higher half offset
-    for page in 0..kernel_stack_page_count {
-        let physical_address = (PAGE_SIZE * page) as u64 + kernel_stack_address;
-        let virtual_address = (PAGE_SIZE * page) as u64 + KERNEL_STACK_MAPPING_OFFSET;
-        manager.map_memory(virtual_address, physical_address, PageEntryFlags::default())?;
+    // map additional boot data to its higher half offset
+    for (physical_address, page_count) in boot_data {
+        map_pages(
+            &mut manager,
+            direct_map_page(BOOT_DATA_MAPPING_OFFSET, physical_address),
+            PhysFrame::containing_address(physical_address),
+            page_count,
+        )?;
     }
 
-    Ok((
-        pml4_addr,
-        KERNEL_STACK_MAPPING_OFFSET + KERNEL_STACK_SIZE as u64,
-        virtual_boot_info_address,
-    ))
+    // map stack to higher half offset, leaving guard pages below and above unmapped
+    let guard_below = (PAGE_SIZE * KERNEL_STACK_GUARD_PAGES_BELOW) as u64;
+    let guard_above = (PAGE_SIZE * KERNEL_STACK_GUARD_PAGES_ABOVE) as u64;
+    let kernel_stack_bottom = KERNEL_STACK_MAPPING_OFFSET + guard_below;
+    let kernel_stack = KernelStack {
+        bottom: kernel_stack_bottom,
+        top: kernel_stack_bottom + (PAGE_SIZE * kernel_stack_page_count) as u64,
+        guard_below,
+        guard_above,
+    };
+    map_pages(
+        &mut manager,
+        Page::containing_address(VirtAddr::new(kernel_stack.bottom)),
+        PhysFrame::containing_address(kernel_stack_address),
+        kernel_stack_page_count,
+    )?;
+
+    // nothing else may be mapped into the guard regions
+    assert!(
+        (kernel_stack.bottom - guard_below..kernel_stack.bottom)
+            .chain(kernel_stack.top..kernel_stack.top + guard_above)
+            .step_by(PAGE_SIZE)
+            .all(|address| manager.translate(VirtAddr::new(address)).is_none()),
+        "kernel stack guard pages are mapped"
+    );
+
+    // state after all page tables have been allocated
+    let frame_allocator = BitMapAllocatorState {
+        bit_map: virtual_bit_map_address,
+        ..manager.frame_allocator().state()
+    };
+
+    Ok(AddressSpace {
+        pml4: pml4_addr,
+        paging_mode,
+        kernel_stack,
+        boot_info_address: virtual_boot_info_address,
+        memory_map: CoreMemoryMap {
+            descriptors: (virtual_descriptors_address + descriptors_offset).as_mut_ptr(),
+            ..*memory_map
+        },
+        frame_allocator,
+        bit_map_address,
+        hhdm,
+        framebuffer: FrameBufferMetadata {
+            base: FRAMEBUFFER_MAPPING_ADDRESS + framebuffer_offset,
+            ..framebuffer
+        },
+    })
+}
+
+/// Calls `f` with the regions (start address, size, type) of the final memory map in ascending order, merging adjacent regions of
+/// the same type. Available frames allocated while setting up the address space (page tables, allocator bitmap) are reported as
+/// kernel data, so that kernels of other boot protocols do not overwrite them.
+pub(super) fn for_each_memory_region(
+    memory_map: &CoreMemoryMap,
+    address_space: &AddressSpace,
+    mut f: impl FnMut(PhysicalAddress, u64, CoreMemoryType),
+) {
+    let allocator = unsafe {
+        BitMapAllocator::from_state(
+            *memory_map,
+            BitMapAllocatorState {
+                // physical memory is still identity mapped
+                bit_map: VirtAddr::new(address_space.bit_map_address.as_u64()),
+                ..address_space.frame_allocator
+            },
+        )
+    };
+
+    let mut pending: Option<(PhysicalAddress, u64, CoreMemoryType)> = None;
+    let mut push = |start: PhysicalAddress, size: u64, r#type: CoreMemoryType| match &mut pending {
+        Some((pending_start, pending_size, pending_type))
+            if *pending_type == r#type && *pending_start + *pending_size == start =>
+        {
+            *pending_size += size
+        }
+        _ => {
+            if let Some((start, size, r#type)) = pending.replace((start, size, r#type)) {
+                f(start, size, r#type);
+            }
+        }
+    };
+
+    for descriptor in memory_map.descriptors() {
+        if descriptor.r#type != CoreMemoryType::Available {
+            push(
+                descriptor.phys_start.as_u64(),
+                descriptor.size(),
+                descriptor.r#type,
+            );
+            continue;
+        }
+
+        for frame in descriptor.frames() {
+            let r#type = match allocator.is_frame_used(frame) {
+                Ok(false) => CoreMemoryType::Available,
+                _ => CoreMemoryType::KernelData,
+            };
+            push(frame.start_address().as_u64(), frame.size(), r#type);
+        }
+    }
+
+    if let Some((start, size, r#type)) = pending {
+        f(start, size, r#type);
+    }
+}
+
+/// Page of the physical `address` in the direct map at `offset`, which is zero for the identity map
+fn direct_map_page(offset: u64, address: PhysAddr) -> Page {
+    Page::containing_address(VirtAddr::new(offset + address.as_u64()))
+}
+
+/// Maps `page_count` contiguous pages starting at the given page and frame
+fn map_pages(
+    manager: &mut PageTableManager<BitMapAllocator, PageFrameAllocatorError>,
+    page: Page,
+    frame: PhysFrame,
+    page_count: usize,
+) -> Result<(), PageFrameAllocatorError> {
+    map_pages_with_flags(manager, page, frame, page_count, PageEntryFlags::default())
+}
+
+/// Maps `page_count` contiguous pages starting at the given page and frame with the given flags
+fn map_pages_with_flags(
+    manager: &mut PageTableManager<BitMapAllocator, PageFrameAllocatorError>,
+    page: Page,
+    frame: PhysFrame,
+    page_count: usize,
+    flags: PageEntryFlags,
+) -> Result<(), PageFrameAllocatorError> {
+    let page_count = page_count as u64;
+    let pages = Page::range(page, page + page_count);
+    for (page, frame) in pages.zip(PhysFrame::range(frame, frame + page_count)) {
+        manager.map_memory(page, frame, flags)?;
+    }
+
+    Ok(())
+}
+
+/// Logs the mappings of the address space, coalesced into contiguous ranges. Does not allocate, so it can be used after boot
+/// services have been exited.
+pub(super) fn log_address_space(address_space: &AddressSpace) {
+    // physical memory is still identity mapped by the firmware
+    let mappings = unsafe {
+        Mappings::new(
+            address_space.pml4.as_u64() as *const PageTable,
+            address_space.paging_mode,
+        )
+    };
+    info!("Kernel address space:");
+    for mapping in mappings {
+        info!("{mapping}");
+    }
+}
+
+/// Loads the page attribute table selecting write combining for the framebuffer mapping. Must be called after boot services
+/// have been exited, right before entering the kernel.
+pub(super) fn load_pat() {
+    if pat::is_supported() {
+        unsafe { pat::load() };
+    }
 }
@@ -0,0 +1,365 @@
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{arch::asm, ptr, slice};
+
+use log::info;
+use uefi::{
+    prelude::BootServices,
+    table::boot::{AllocateType, MemoryType},
+    Handle,
+};
+
+use core64_util::{
+    boot::multiboot2::{
+        Header, InfoWriter, ARCHITECTURE_I386, BOOTLOADER_MAGIC, FRAMEBUFFER_TYPE_RGB,
+        HEADER_TAG_ADDRESS, HEADER_TAG_EFI_BOOT_SERVICES, HEADER_TAG_ENTRY_ADDRESS_EFI64,
+        HEADER_TAG_FRAMEBUFFER, HEADER_TAG_INFORMATION_REQUEST, HEADER_TAG_RELOCATABLE,
+        MEMORY_ACPI_RECLAIMABLE, MEMORY_AVAILABLE, MEMORY_BAD_RAM, MEMORY_NVS, MEMORY_RESERVED,
+        MMAP_ENTRY_SIZE, TAG_ACPI_NEW, TAG_ACPI_OLD, TAG_BASIC_MEMINFO, TAG_BOOT_LOADER_NAME,
+        TAG_CMDLINE, TAG_EFI64, TAG_EFI64_IMAGE_HANDLE, TAG_EFI_BS_NOT_TERMINATED, TAG_EFI_MMAP,
+        TAG_FRAMEBUFFER, TAG_MMAP, TAG_MODULE,
+    },
+    graphics::framebuffer::{FrameBufferMetadata, BPP},
+    memory::{PhysicalAddress, PAGE_SIZE},
+};
+
+use crate::file::Module;
+
+const LOADER_NAME: &str = "core64-loader";
+/// Boot information must be accessible with 32-bit addresses
+const MAX_ADDRESS: PhysicalAddress = u32::MAX as PhysicalAddress;
+/// Pages for fixed size tags, memory map entries are allocated on top
+const INFO_PAGE_COUNT: usize = 1;
+/// Additional memory map entries allocated on top of the descriptor count, as the memory map changes until it is written
+const MEMORY_MAP_PADDING: usize = 16;
+/// Lower memory reported in the basic memory information ends at 640 KiB
+const LOWER_MEMORY_END: u64 = 640 * 1024;
+/// Upper memory reported in the basic memory information starts at 1 MiB
+const UPPER_MEMORY_START: u64 = 1024 * 1024;
+
+/// Information tags provided by the loader. Kernels may require any of them in an information request.
+const PROVIDED_TAGS: &[u32] = &[
+    TAG_CMDLINE,
+    TAG_BOOT_LOADER_NAME,
+    TAG_MODULE,
+    TAG_BASIC_MEMINFO,
+    TAG_MMAP,
+    TAG_FRAMEBUFFER,
+    TAG_EFI64,
+    TAG_ACPI_OLD,
+    TAG_ACPI_NEW,
+    TAG_EFI_MMAP,
+    TAG_EFI_BS_NOT_TERMINATED,
+    TAG_EFI64_IMAGE_HANDLE,
+];
+
+/// Multiboot2 kernel entered through its EFI amd64 entry address, with boot services running
+#[derive(Copy, Clone, Debug)]
+pub(super) struct MultibootKernel {
+    pub(super) entry: PhysicalAddress,
+    /// Preferred framebuffer resolution (width, height)
+    pub(super) framebuffer_resolution: Option<(usize, usize)>,
+}
+
+/// Searches the image for a Multiboot2 header. Fails, if the kernel depends on header tags that are not supported.
+///
+/// The EFI amd64 entry address is only honoured together with the EFI boot services tag, otherwise kernels are entered in 32-bit
+/// protected mode, which is not supported.
+pub(super) fn parse_header(image: &[u8]) -> Result<Option<MultibootKernel>, String> {
+    let Some(header) = Header::find(image) else {
+        return Ok(None);
+    };
+    if header.architecture != ARCHITECTURE_I386 {
+        return Err(format!(
+            "Unsupported Multiboot2 architecture: {}.",
+            header.architecture
+        ));
+    }
+
+    let mut entry = None;
+    let mut boot_services = false;
+    let mut framebuffer_resolution = None;
+    for tag in header.tags() {
+        match tag.r#type {
+            HEADER_TAG_ENTRY_ADDRESS_EFI64 => entry = tag.u32(0),
+            HEADER_TAG_EFI_BOOT_SERVICES => boot_services = true,
+            HEADER_TAG_FRAMEBUFFER => {
+                framebuffer_resolution = match (tag.u32(0), tag.u32(1)) {
+                    (Some(width), Some(height)) if width != 0 && height != 0 => {
+                        Some((width as usize, height as usize))
+                    }
+                    _ => None,
+                }
+            }
+            HEADER_TAG_INFORMATION_REQUEST if !tag.is_optional() => {
+                if let Some(r#type) = tag.u32s().find(|r#type| !PROVIDED_TAGS.contains(r#type)) {
+                    return Err(format!(
+                        "Multiboot2 kernel requires unsupported information tag {type}."
+                    ));
+                }
+            }
+            HEADER_TAG_ADDRESS if !tag.is_optional() => {
+                return Err("Multiboot2 address tags are not supported.".to_string())
+            }
+            r#type if r#type > HEADER_TAG_RELOCATABLE && !tag.is_optional() => {
+                return Err(format!("Unsupported Multiboot2 header tag {type}."))
+            }
+            // other entry points, console flags, module alignment (modules are page aligned) and relocation (images are loaded
+            // at their preferred address)
+            _ => {}
+        }
+    }
+
+    let entry =
+        entry.ok_or_else(|| "Multiboot2 kernel has no EFI amd64 entry address.".to_string())?;
+    if !boot_services {
+        return Err(
+            "Multiboot2 kernel has no EFI boot services tag, its EFI amd64 entry address is ignored.".to_string(),
+        );
+    }
+    Ok(Some(MultibootKernel {
+        entry: entry as PhysicalAddress,
+        framebuffer_resolution,
+    }))
+}
+
+/// Multiboot2 boot information gathered while boot services are running
+pub(super) struct MultibootBoot {
+    kernel: MultibootKernel,
+    image_handle: Handle,
+    command_line: String,
+    modules: Vec<Module>,
+    system_table: u64,
+    rsdp: Option<PhysicalAddress>,
+    info_address: PhysicalAddress,
+    info_page_count: usize,
+}
+
+impl MultibootBoot {
+    /// Allocates memory for the boot information. Modules have been loaded by [`crate::file::load_modules`].
+    pub(super) fn new(
+        bt: &BootServices,
+        image_handle: Handle,
+        command_line: String,
+        modules: Vec<Module>,
+        kernel: MultibootKernel,
+        system_table: u64,
+        rsdp: Option<PhysicalAddress>,
+    ) -> Result<Self, String> {
+        let memory_map_meta = bt
+            .memory_map(MemoryType::LOADER_DATA)
+            .map_err(|error| format!("Could not get uefi memory map: {error}"))?
+            .as_raw()
+            .1;
+        let descriptor_capacity = memory_map_meta.entry_count() + MEMORY_MAP_PADDING;
+
+        let variable_size = command_line.len()
+            + modules
+                .iter()
+                .map(|module| 16 + module.path.len() + 1)
+                .sum::<usize>()
+            + descriptor_capacity * (MMAP_ENTRY_SIZE + memory_map_meta.desc_size);
+        let info_page_count = INFO_PAGE_COUNT + variable_size.div_ceil(PAGE_SIZE);
+        let info_address = bt
+            .allocate_pages(
+                AllocateType::MaxAddress(MAX_ADDRESS),
+                MemoryType::LOADER_DATA,
+                info_page_count,
+            )
+            .map_err(|error| {
+                format!("Could not allocate pages for Multiboot2 information: {error}.")
+            })?;
+
+        info!(
+            "Booting Multiboot2 kernel with {} module(s) and command line \"{command_line}\".",
+            modules.len()
+        );
+
+        Ok(Self {
+            kernel,
+            image_handle,
+            command_line,
+            modules,
+            system_table,
+            rsdp,
+            info_address,
+            info_page_count,
+        })
+    }
+
+    /// Writes the boot information, including a snapshot of the UEFI memory map. Must be called right before entering the kernel,
+    /// so that the memory map reflects all allocations of the loader. Returns the physical address of the boot information.
+    pub(super) fn write_info(
+        &self,
+        bt: &BootServices,
+        framebuffer: &FrameBufferMetadata,
+    ) -> Result<PhysicalAddress, String> {
+        let buffer = unsafe {
+            slice::from_raw_parts_mut(
+                self.info_address as *mut u8,
+                self.info_page_count * PAGE_SIZE,
+            )
+        };
+        let mut writer = InfoWriter::new(buffer).unwrap();
+
+        writer
+            .add_string_tag(TAG_CMDLINE, &self.command_line)
+            .unwrap();
+        writer
+            .add_string_tag(TAG_BOOT_LOADER_NAME, LOADER_NAME)
+            .unwrap();
+        for module in &self.modules {
+            writer
+                .add_tag(TAG_MODULE, 8 + module.path.len() + 1, |data| {
+                    let end = module.address + module.size as u64;
+                    data[..4].copy_from_slice(&(module.address as u32).to_le_bytes());
+                    data[4..8].copy_from_slice(&(end as u32).to_le_bytes());
+                    data[8..8 + module.path.len()].copy_from_slice(module.path.as_bytes());
+                })
+                .unwrap();
+        }
+
+        // boot services keep running, so only conventional memory is available to the kernel
+        let memory_map = bt
+            .memory_map(MemoryType::LOADER_DATA)
+            .map_err(|error| format!("Could not get uefi memory map: {error}"))?;
+        let mut regions = memory_map
+            .entries()
+            .map(|descriptor| {
+                let r#type = match descriptor.ty {
+                    MemoryType::CONVENTIONAL => MEMORY_AVAILABLE,
+                    MemoryType::ACPI_RECLAIM => MEMORY_ACPI_RECLAIMABLE,
+                    MemoryType::ACPI_NON_VOLATILE => MEMORY_NVS,
+                    MemoryType::UNUSABLE => MEMORY_BAD_RAM,
+                    _ => MEMORY_RESERVED,
+                };
+                (
+                    descriptor.phys_start,
+                    descriptor.page_count * PAGE_SIZE as u64,
+                    r#type,
+                )
+            })
+            .collect::<Vec<_>>();
+        regions.sort_unstable_by_key(|(start, ..)| *start);
+
+        let mut lower_end = 0;
+        let mut upper_end = UPPER_MEMORY_START;
+        for (start, size, r#type) in &regions {
+            if *r#type == MEMORY_AVAILABLE {
+                for end in [&mut lower_end, &mut upper_end] {
+                    if (*start..start + size).contains(end) {
+                        *end = start + size;
+                    }
+                }
+            }
+        }
+        let mem_lower = lower_end.min(LOWER_MEMORY_END) / 1024;
+        let mem_upper = (upper_end - UPPER_MEMORY_START) / 1024;
+        writer
+            .add_tag(TAG_BASIC_MEMINFO, 8, |data| {
+                data[..4].copy_from_slice(&(mem_lower as u32).to_le_bytes());
+                data[4..].copy_from_slice(&(mem_upper.min(u32::MAX as u64) as u32).to_le_bytes());
+            })
+            .unwrap();
+        writer
+            .add_tag(TAG_MMAP, 8 + regions.len() * MMAP_ENTRY_SIZE, |data| {
+                data[..4].copy_from_slice(&(MMAP_ENTRY_SIZE as u32).to_le_bytes());
+                for ((start, size, r#type), entry) in regions
+                    .iter()
+                    .zip(data[8..].chunks_exact_mut(MMAP_ENTRY_SIZE))
+                {
+                    entry[..8].copy_from_slice(&start.to_le_bytes());
+                    entry[8..16].copy_from_slice(&size.to_le_bytes());
+                    entry[16..20].copy_from_slice(&r#type.to_le_bytes());
+                }
+            })
+            .map_err(|error| format!("Multiboot2 memory map does not fit: {error}."))?;
+
+        let (descriptors, meta) = memory_map.as_raw();
+        let descriptors = &descriptors[..meta.map_size];
+        writer
+            .add_tag(TAG_EFI_MMAP, 8 + descriptors.len(), |data| {
+                data[..4].copy_from_slice(&(meta.desc_size as u32).to_le_bytes());
+                data[4..8].copy_from_slice(&meta.desc_version.to_le_bytes());
+                data[8..].copy_from_slice(descriptors);
+            })
+            .map_err(|error| format!("UEFI memory map does not fit: {error}."))?;
+
+        writer
+            .add_tag(TAG_FRAMEBUFFER, 30, |data| {
+                let (red_position, blue_position) =
+                    if framebuffer.is_rgb { (0, 16) } else { (16, 0) };
+                data[..8].copy_from_slice(&framebuffer.base.to_le_bytes());
+                data[8..12].copy_from_slice(&((framebuffer.stride * BPP) as u32).to_le_bytes());
+                data[12..16].copy_from_slice(&(framebuffer.width as u32).to_le_bytes());
+                data[16..20].copy_from_slice(&(framebuffer.height as u32).to_le_bytes());
+                data[20] = (BPP * 8) as u8;
+                data[21] = FRAMEBUFFER_TYPE_RGB;
+                data[24..30].copy_from_slice(&[red_position, 8, 8, 8, blue_position, 8]);
+            })
+            .unwrap();
+
+        writer
+            .add_tag(TAG_EFI64, 8, |data| {
+                data.copy_from_slice(&self.system_table.to_le_bytes())
+            })
+            .unwrap();
+        writer
+            .add_tag(TAG_EFI64_IMAGE_HANDLE, 8, |data| {
+                data.copy_from_slice(&(self.image_handle.as_ptr() as u64).to_le_bytes())
+            })
+            .unwrap();
+        writer
+            .add_tag(TAG_EFI_BS_NOT_TERMINATED, 0, |_| {})
+            .unwrap();
+
+        if let Some(rsdp) = self.rsdp {
+            // ACPI 2.0+ RSDP holds its length, ACPI 1.0 RSDP is 20 bytes long
+            let revision = unsafe { ptr::read((rsdp + 15) as *const u8) };
+            let (r#type, size) = if revision >= 2 {
+                let length = unsafe { ptr::read_unaligned((rsdp + 20) as *const u32) };
+                (TAG_ACPI_NEW, length as usize)
+            } else {
+                (TAG_ACPI_OLD, 20)
+            };
+            writer
+                .add_tag(r#type, size, |data| unsafe {
+                    ptr::copy_nonoverlapping(rsdp as *const u8, data.as_mut_ptr(), size)
+                })
+                .unwrap();
+        }
+
+        writer.finish();
+        Ok(self.info_address)
+    }
+
+    pub(super) fn entry(&self) -> PhysicalAddress {
+        self.kernel.entry
+    }
+}
+
+/// Jumps to the EFI amd64 entry address in the machine state defined by the specification for kernels with the EFI boot services
+/// tag: magic in `RAX`, the boot information address in `RBX`, boot services running on the firmware's page tables and stack.
+/// The kernel is responsible for exiting boot services.
+///
+/// # Safety
+/// Kernel, modules and boot information must be loaded at their physical addresses, which the firmware identity maps.
+pub(super) unsafe fn jump_to_kernel(entry: PhysicalAddress, info: PhysicalAddress) -> ! {
+    unsafe {
+        asm!(
+            // align the stack as at the entry of a called function, with a zero return address
+            "and rsp, -16",
+            "push 0",
+            "mov eax, {magic}",
+            "mov rbx, rsi",
+            "jmp rcx",
+            magic = const BOOTLOADER_MAGIC,
+            in("rsi") info,
+            in("rcx") entry,
+            options(noreturn)
+        );
+    }
+}
@@ -0,0 +1,121 @@
+use alloc::{format, string::String, vec::Vec};
+
+use log::{info, warn};
+use uefi::{
+    prelude::BootServices,
+    proto::network::{
+        pxe::{BaseCode, DhcpV4Packet},
+        IpAddress,
+    },
+    CStr8, Handle,
+};
+
+use crate::file::{self, FileSource, FoundFile};
+
+/// Searches the TFTP server of every network interface for the first existing path of `search_paths`. The server defaults to
+/// the boot server announced via DHCP. Paths are requested relative to the TFTP root with `/` separators.
+pub(super) fn find_file(
+    boot_services: &BootServices,
+    server: Option<[u8; 4]>,
+    search_paths: &[&str],
+) -> Result<FoundFile, String> {
+    let devices = boot_services
+        .find_handles::<BaseCode>()
+        .map_err(|error| format!("Cannot find network interfaces: {error}."))?;
+
+    for device in devices {
+        let server = match configure(boot_services, device, server) {
+            Ok(server) => server,
+            Err(error) => {
+                warn!("{error}");
+                continue;
+            }
+        };
+        for path in search_paths {
+            if let Ok(data) = read_file(boot_services, device, server, path) {
+                let [a, b, c, d] = server;
+                info!("Found {path} on TFTP server {a}.{b}.{c}.{d}.");
+                return Ok(FoundFile {
+                    data,
+                    path: String::from(*path),
+                    source: FileSource::Tftp(device, server),
+                    boot_device: file::boot_device(device, boot_services),
+                });
+            }
+        }
+    }
+
+    Err(format!(
+        "Unable to find any of {search_paths:?} on any TFTP server."
+    ))
+}
+
+/// Reads a file from a TFTP server through the PXE base code protocol of `device`
+pub(super) fn read_file(
+    boot_services: &BootServices,
+    device: Handle,
+    server: [u8; 4],
+    path: &str,
+) -> Result<Vec<u8>, String> {
+    let mut base_code = boot_services
+        .open_protocol_exclusive::<BaseCode>(device)
+        .map_err(|error| format!("Cannot open PXE base code: {error}."))?;
+    let file_name = tftp_file_name(path);
+    let file_name =
+        CStr8::from_bytes_with_nul(&file_name).map_err(|_| format!("Invalid file path: {path}"))?;
+    let server = IpAddress::new_v4(server);
+
+    let size = base_code
+        .tftp_get_file_size(&server, file_name)
+        .map_err(|_| format!("Unable to read file with name: {path}."))?;
+    // the size reported by the server is untrusted, so it must not abort the loader on allocation failure
+    let mut data = Vec::new();
+    data.try_reserve_exact(size as usize)
+        .map_err(|_| format!("Not enough memory to download {path} ({size} bytes)."))?;
+    data.resize(size as usize, 0);
+    if !data.is_empty() {
+        base_code
+            .tftp_read_file(&server, file_name, Some(&mut data))
+            .map_err(|error| format!("Unable to download {path}: {error}."))?;
+    }
+    Ok(data)
+}
+
+/// Starts the PXE base code of `device` and acquires an address via DHCP, unless already done by the firmware.
+/// Returns the TFTP server address.
+fn configure(
+    boot_services: &BootServices,
+    device: Handle,
+    server: Option<[u8; 4]>,
+) -> Result<[u8; 4], String> {
+    let mut base_code = boot_services
+        .open_protocol_exclusive::<BaseCode>(device)
+        .map_err(|error| format!("Cannot open PXE base code: {error}."))?;
+    if !base_code.mode().started {
+        base_code
+            .start(false)
+            .map_err(|error| format!("Cannot start PXE base code: {error}."))?;
+    }
+    if !base_code.mode().dhcp_ack_received {
+        base_code
+            .dhcp(false)
+            .map_err(|error| format!("DHCP failed: {error}."))?;
+    }
+
+    let dhcp_ack: &DhcpV4Packet = base_code.mode().dhcp_ack.as_ref();
+    let boot_server = dhcp_ack.bootp_si_addr;
+    match server {
+        Some(server) => Ok(server),
+        None if boot_server != [0; 4] => Ok(boot_server),
+        None => Err("DHCP did not announce a boot server.".into()),
+    }
+}
+
+/// Converts a filesystem path, e.g. `\boot\kernel.elf`, to a null terminated TFTP file name, e.g. `boot/kernel.elf`
+fn tftp_file_name(path: &str) -> Vec<u8> {
+    path.trim_start_matches('\\')
+        .bytes()
+        .map(|byte| if byte == b'\\' { b'/' } else { byte })
+        .chain([0])
+        .collect()
+}
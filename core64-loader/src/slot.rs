@@ -0,0 +1,55 @@
+use alloc::string::String;
+
+use log::{info, warn};
+use uefi::table::runtime::RuntimeServices;
+
+use core64_util::boot::variable::{BootSlot, BOOT_SLOT_VARIABLE, SLOT_ATTEMPTS_VARIABLE};
+
+use crate::{config::Config, file::FoundFile, variable};
+
+/// Loads the kernel of the active A/B slot. Switches to the other slot, once the active slot has not been marked successful
+/// for `max_boot_attempts` consecutive boots or its kernel cannot be found by `find_file`. Returns `None`, if A/B slots are not
+/// configured.
+pub(super) fn find_kernel(
+    runtime_services: &RuntimeServices,
+    config: &Config,
+    find_file: impl Fn(&[&str]) -> Result<FoundFile, String>,
+) -> Option<Result<FoundFile, String>> {
+    let (Some(kernel_a), Some(kernel_b)) = (&config.kernel_a, &config.kernel_b) else {
+        if config.kernel_a.is_some() || config.kernel_b.is_some() {
+            warn!("Ignoring kernel slots: both kernel_a and kernel_b must be configured.");
+        }
+        return None;
+    };
+    let slot_path = |slot| match slot {
+        BootSlot::A => kernel_a.as_str(),
+        BootSlot::B => kernel_b.as_str(),
+    };
+
+    let mut slot = variable::get_u32(runtime_services, BOOT_SLOT_VARIABLE)
+        .and_then(BootSlot::from_u32)
+        .unwrap_or(BootSlot::A);
+    let mut attempts =
+        variable::get_u32(runtime_services, SLOT_ATTEMPTS_VARIABLE).unwrap_or_default();
+    if attempts >= config.max_boot_attempts {
+        warn!(
+            "Kernel slot {slot:?} failed to boot {attempts} time(s). Switching to slot {:?}.",
+            slot.other()
+        );
+        slot = slot.other();
+        attempts = 0;
+    }
+
+    let kernel_file = find_file(&[slot_path(slot)]).or_else(|error| {
+        warn!("{error} Switching to slot {:?}.", slot.other());
+        slot = slot.other();
+        attempts = 0;
+        find_file(&[slot_path(slot)])
+    });
+
+    info!("Booting kernel slot {slot:?}.");
+    variable::set_u32(runtime_services, BOOT_SLOT_VARIABLE, slot as u32);
+    // counts as failed, until the kernel marks the boot successful
+    variable::set_u32(runtime_services, SLOT_ATTEMPTS_VARIABLE, attempts + 1);
+    Some(kernel_file)
+}
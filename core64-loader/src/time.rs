@@ -0,0 +1,42 @@
+use core::arch::x86_64::_rdtsc;
+
+use uefi::{
+    prelude::BootServices,
+    table::runtime::{RuntimeServices, Time},
+};
+
+use core64_util::boot::time::WallClockTime;
+
+/// Duration used to estimate the time stamp counter frequency in microseconds
+const TSC_CALIBRATION_TIME: usize = 10_000;
+
+/// Reads the time stamp counter
+pub(super) fn timestamp() -> u64 {
+    unsafe { _rdtsc() }
+}
+
+/// Estimates the time stamp counter frequency in Hz by stalling for a fixed amount of time
+pub(super) fn tsc_frequency(bt: &BootServices) -> u64 {
+    let start = timestamp();
+    bt.stall(TSC_CALIBRATION_TIME);
+    let end = timestamp();
+
+    (end - start) * (1_000_000 / TSC_CALIBRATION_TIME as u64)
+}
+
+/// Reads the current calendar time from the firmware
+pub(super) fn wall_clock_time(rt: &RuntimeServices) -> Option<WallClockTime> {
+    rt.get_time().ok().map(|time: Time| WallClockTime {
+        year: time.year(),
+        month: time.month(),
+        day: time.day(),
+        hour: time.hour(),
+        minute: time.minute(),
+        second: time.second(),
+        daylight: time.daylight().bits(),
+        nanosecond: time.nanosecond(),
+        time_zone: time
+            .time_zone()
+            .unwrap_or(WallClockTime::UNSPECIFIED_TIME_ZONE),
+    })
+}
@@ -0,0 +1,37 @@
+use log::warn;
+use uefi::{
+    table::runtime::{RuntimeServices, VariableAttributes, VariableVendor},
+    CString16, Guid,
+};
+
+use core64_util::boot::variable::{VARIABLE_ATTRIBUTES, VARIABLE_VENDOR_GUID};
+
+const VARIABLE_VENDOR: VariableVendor = VariableVendor(Guid::from_bytes(VARIABLE_VENDOR_GUID));
+
+/// Reads a little endian `u32` core64 variable. Returns `None` if it does not exist or is malformed.
+pub(super) fn get_u32(runtime_services: &RuntimeServices, name: &str) -> Option<u32> {
+    let name = CString16::try_from(name).ok()?;
+    let mut buffer = [0; 4];
+    runtime_services
+        .get_variable(&name, &VARIABLE_VENDOR, &mut buffer)
+        .ok()
+        .and_then(|(data, _)| data.try_into().ok())
+        .map(u32::from_le_bytes)
+}
+
+/// Stores a little endian `u32` core64 variable in non volatile memory
+pub(super) fn set_u32(runtime_services: &RuntimeServices, name: &str, value: u32) {
+    let Ok(variable_name) = CString16::try_from(name) else {
+        warn!("Invalid variable name: {name}.");
+        return;
+    };
+    let attributes = VariableAttributes::from_bits_truncate(VARIABLE_ATTRIBUTES);
+    if let Err(error) = runtime_services.set_variable(
+        &variable_name,
+        &VARIABLE_VENDOR,
+        attributes,
+        &value.to_le_bytes(),
+    ) {
+        warn!("Could not store {name}: {error}.");
+    }
+}
@@ -0,0 +1,128 @@
+use alloc::{
+    borrow::Cow,
+    format,
+    string::{String, ToString},
+};
+
+use log::{info, warn};
+use uefi::prelude::BootServices;
+
+use core64_util::crypto::{
+    manifest::manifest_digest, parse_hex, sha256, verify_ed25519, ED25519_SIGNATURE_SIZE,
+};
+
+use crate::file::{FileSource, FoundFile};
+
+/// Manifest embedded at build time, in `sha256sum` format (`<hex digest>  <path>` per line)
+const EMBEDDED_MANIFEST: Option<&str> = option_env!("CORE64_MANIFEST");
+/// Hex encoded Ed25519 public key. If set, manifests read from the boot filesystem must be signed with the matching private key.
+const MANIFEST_PUBLIC_KEY: Option<&str> = option_env!("CORE64_MANIFEST_PUBLIC_KEY");
+/// `enforce`, `warn` (default) or `disabled`
+const VERIFICATION_POLICY: Option<&str> = option_env!("CORE64_VERIFICATION_POLICY");
+
+/// Reaction to files, which are missing in the manifest or do not match their digest
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum VerificationPolicy {
+    /// Files are not verified
+    Disabled,
+    /// Mismatches are logged, but the files are used anyway
+    Warn,
+    /// Mismatches abort booting
+    Enforce,
+}
+
+impl VerificationPolicy {
+    fn from_build_config() -> Result<Self, String> {
+        match VERIFICATION_POLICY {
+            None | Some("warn") => Ok(Self::Warn),
+            Some("enforce") => Ok(Self::Enforce),
+            Some("disabled") => Ok(Self::Disabled),
+            Some(policy) => Err(format!("Invalid verification policy: {policy}.")),
+        }
+    }
+}
+
+/// Manifest the kernel and its modules are verified against. It is loaded and its signature checked once per boot.
+pub(super) struct Manifest {
+    policy: VerificationPolicy,
+    /// Contents of the manifest, or why it could not be loaded. Reported for each verified file according to the policy.
+    contents: Result<Cow<'static, str>, String>,
+}
+
+impl Manifest {
+    /// Returns the embedded manifest or reads the manifest at `path` from `source`, checking its detached signature at
+    /// `<path>.sig` if a public key is configured. Only fails for an invalid verification policy.
+    pub(super) fn load(bt: &BootServices, source: FileSource, path: &str) -> Result<Self, String> {
+        let policy = VerificationPolicy::from_build_config()?;
+        let contents = match policy {
+            VerificationPolicy::Disabled => Err("Verification is disabled.".to_string()),
+            _ => load_contents(bt, source, path),
+        };
+        Ok(Self { policy, contents })
+    }
+}
+
+/// Verifies a file against its SHA-256 digest in the manifest. Returns an error, if verification fails and the policy is enforcing.
+pub(super) fn verify_file(manifest: &Manifest, file: &FoundFile) -> Result<(), String> {
+    if manifest.policy == VerificationPolicy::Disabled {
+        return Ok(());
+    }
+
+    match check_digest(manifest, file) {
+        Ok(()) => {
+            info!("Verified {} against manifest.", file.path);
+            Ok(())
+        }
+        Err(error) if manifest.policy == VerificationPolicy::Enforce => {
+            Err(format!("Refusing to boot {}: {error}", file.path))
+        }
+        Err(error) => {
+            warn!("Booting unverified {}: {error}", file.path);
+            Ok(())
+        }
+    }
+}
+
+fn check_digest(manifest: &Manifest, file: &FoundFile) -> Result<(), String> {
+    let contents = manifest.contents.as_ref().map_err(Clone::clone)?;
+    let expected = manifest_digest(contents, &file.path)
+        .ok_or_else(|| format!("{} is not listed in the manifest.", file.path))?;
+
+    if sha256(&file.data) != expected {
+        return Err("SHA-256 digest does not match the manifest.".to_string());
+    }
+    Ok(())
+}
+
+fn load_contents(
+    bt: &BootServices,
+    source: FileSource,
+    path: &str,
+) -> Result<Cow<'static, str>, String> {
+    if let Some(manifest) = EMBEDDED_MANIFEST {
+        return Ok(Cow::Borrowed(manifest));
+    }
+
+    let manifest = source
+        .read(bt, path)
+        .map_err(|_| format!("No manifest at {path}."))?;
+
+    if let Some(public_key) = MANIFEST_PUBLIC_KEY {
+        let public_key =
+            parse_hex(public_key).ok_or_else(|| "Invalid manifest public key.".to_string())?;
+        let signature_path = format!("{path}.sig");
+        let signature: [u8; ED25519_SIGNATURE_SIZE] = source
+            .read(bt, &signature_path)
+            .map_err(|_| format!("No manifest signature at {signature_path}."))?
+            .try_into()
+            .map_err(|_| "Invalid manifest signature size.".to_string())?;
+
+        if !verify_ed25519(&public_key, &manifest, &signature) {
+            return Err("Invalid manifest signature.".to_string());
+        }
+    }
+
+    String::from_utf8(manifest)
+        .map(Cow::Owned)
+        .map_err(|_| "Manifest is not valid UTF-8.".to_string())
+}
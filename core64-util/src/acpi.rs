@@ -0,0 +1,194 @@
+use core::{mem::size_of, ptr, slice};
+
+use crate::memory::PhysicalAddress;
+
+/// Signature of the Multiple APIC Description Table
+pub const MADT_SIGNATURE: [u8; 4] = *b"APIC";
+
+const MADT_LOCAL_APIC: u8 = 0;
+const MADT_LOCAL_X2APIC: u8 = 9;
+/// Offset of the interrupt controller structures within the MADT (header, local interrupt controller address, flags)
+const MADT_ENTRIES_OFFSET: usize = size_of::<SdtHeader>() + 8;
+
+const LOCAL_APIC_ENABLED: u32 = 1 << 0;
+const LOCAL_APIC_ONLINE_CAPABLE: u32 = 1 << 1;
+
+/// Root System Description Pointer (revision 2 layout)
+#[repr(C, packed)]
+#[derive(Copy, Clone, Debug)]
+pub struct Rsdp {
+    pub signature: [u8; 8],
+    pub checksum: u8,
+    pub oem_id: [u8; 6],
+    pub revision: u8,
+    pub rsdt_address: u32,
+    /// Only valid if revision >= 2
+    pub length: u32,
+    /// Only valid if revision >= 2
+    pub xsdt_address: u64,
+    pub extended_checksum: u8,
+    pub reserved: [u8; 3],
+}
+
+/// Header shared by all System Description Tables
+#[repr(C, packed)]
+#[derive(Copy, Clone, Debug)]
+pub struct SdtHeader {
+    pub signature: [u8; 4],
+    pub length: u32,
+    pub revision: u8,
+    pub checksum: u8,
+    pub oem_id: [u8; 6],
+    pub oem_table_id: [u8; 8],
+    pub oem_revision: u32,
+    pub creator_id: u32,
+    pub creator_revision: u32,
+}
+
+/// Looks up the table with the given signature via XSDT (or RSDT for ACPI 1.0). Returns the table including its header.
+///
+/// # Safety
+/// `rsdp` must point to a valid RSDP and all tables must be accessible at their physical address (e.g. identity mapped).
+pub unsafe fn find_table(rsdp: PhysicalAddress, signature: [u8; 4]) -> Option<&'static [u8]> {
+    let rsdp = unsafe { ptr::read_unaligned(rsdp as *const Rsdp) };
+    if &rsdp.signature != b"RSD PTR " {
+        return None;
+    }
+
+    let (root, entry_size) = if rsdp.revision >= 2 && rsdp.xsdt_address != 0 {
+        (rsdp.xsdt_address, size_of::<u64>())
+    } else {
+        (rsdp.rsdt_address as u64, size_of::<u32>())
+    };
+
+    let root = unsafe { table(root) }?;
+    root[size_of::<SdtHeader>()..]
+        .chunks_exact(entry_size)
+        .map(|entry| {
+            entry
+                .iter()
+                .rev()
+                .fold(0, |address, byte| (address << 8) | *byte as u64)
+        })
+        .filter_map(|address| unsafe { table(address) })
+        .find(|table| table[..4] == signature)
+}
+
+/// Returns the bytes of the table at the given address, if it has a valid checksum
+unsafe fn table(address: PhysicalAddress) -> Option<&'static [u8]> {
+    if address == 0 {
+        return None;
+    }
+    let header = unsafe { ptr::read_unaligned(address as *const SdtHeader) };
+    let length = header.length as usize;
+    if length < size_of::<SdtHeader>() {
+        return None;
+    }
+
+    let bytes = unsafe { slice::from_raw_parts(address as *const u8, length) };
+    is_checksum_valid(bytes).then_some(bytes)
+}
+
+/// Whether all bytes sum up to zero
+pub fn is_checksum_valid(bytes: &[u8]) -> bool {
+    bytes.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)) == 0
+}
+
+/// Processor local (x2)APIC described by the MADT
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct LocalApic {
+    pub processor_uid: u32,
+    pub apic_id: u32,
+    /// Processor is enabled
+    pub enabled: bool,
+    /// Processor is disabled, but can be enabled by the operating system
+    pub online_capable: bool,
+}
+
+/// Iterates over all processor local APIC and x2APIC structures of a MADT (including its header)
+pub fn madt_local_apics(madt: &[u8]) -> impl Iterator<Item = LocalApic> + '_ {
+    let mut offset = MADT_ENTRIES_OFFSET;
+    core::iter::from_fn(move || loop {
+        let r#type = *madt.get(offset)?;
+        let length = *madt.get(offset + 1)? as usize;
+        let entry = madt.get(offset..offset + length).filter(|_| length >= 2)?;
+        offset += length;
+
+        let read_u32 = |at: usize| {
+            entry
+                .get(at..at + 4)
+                .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+        };
+
+        let (processor_uid, apic_id, flags) = match r#type {
+            MADT_LOCAL_APIC if length >= 8 => (entry[2] as u32, entry[3] as u32, read_u32(4)?),
+            MADT_LOCAL_X2APIC if length >= 16 => (read_u32(12)?, read_u32(4)?, read_u32(8)?),
+            _ => continue,
+        };
+
+        return Some(LocalApic {
+            processor_uid,
+            apic_id,
+            enabled: flags & LOCAL_APIC_ENABLED != 0,
+            online_capable: flags & LOCAL_APIC_ONLINE_CAPABLE != 0,
+        });
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_madt_local_apics() {
+        let mut madt = [0u8; MADT_ENTRIES_OFFSET + 8 + 12 + 16];
+        madt[..4].copy_from_slice(&MADT_SIGNATURE);
+        let entries = &mut madt[MADT_ENTRIES_OFFSET..];
+        // local apic: uid 0, apic id 0, enabled
+        entries[..8].copy_from_slice(&[MADT_LOCAL_APIC, 8, 0, 0, 1, 0, 0, 0]);
+        // i/o apic, skipped
+        entries[8..20].copy_from_slice(&[1, 12, 0, 0, 0, 0, 0xC0, 0xFE, 0, 0, 0, 0]);
+        // local x2apic: apic id 0x100, online capable, uid 1
+        entries[20..].copy_from_slice(&[
+            MADT_LOCAL_X2APIC,
+            16,
+            0,
+            0,
+            0x00,
+            0x01,
+            0,
+            0,
+            2,
+            0,
+            0,
+            0,
+            1,
+            0,
+            0,
+            0,
+        ]);
+
+        assert!(madt_local_apics(&madt).eq([
+            LocalApic {
+                processor_uid: 0,
+                apic_id: 0,
+                enabled: true,
+                online_capable: false,
+            },
+            LocalApic {
+                processor_uid: 1,
+                apic_id: 0x100,
+                enabled: false,
+                online_capable: true,
+            },
+        ]));
+    }
+
+    #[test]
+    fn stops_at_truncated_madt_entry() {
+        let mut madt = [0u8; MADT_ENTRIES_OFFSET + 4];
+        madt[MADT_ENTRIES_OFFSET..].copy_from_slice(&[MADT_LOCAL_APIC, 8, 0, 0]);
+
+        assert_eq!(madt_local_apics(&madt).count(), 0);
+    }
+}
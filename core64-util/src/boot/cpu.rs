@@ -0,0 +1,47 @@
+use bitflags::bitflags;
+
+use crate::boot::tag::{Tag, TAG_CPU};
+
+bitflags! {
+    #[repr(transparent)]
+    #[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+    pub struct CpuFlags: u32 {
+        /// Processor the loader (and therefore the kernel entry) runs on
+        const BOOTSTRAP = 1 << 0;
+        /// Processor is enabled and can be started by the kernel
+        const ENABLED   = 1 << 1;
+        /// Processor passed its built-in self test. Always set, if the health status is unknown.
+        const HEALTHY   = 1 << 2;
+    }
+}
+
+/// Logical processor discovered by the loader. Passed to the kernel as a list in the boot info tag list.
+///
+/// The loader does not start or park application processors. They are left in the state the firmware puts them in when exiting
+/// boot services, i.e. waiting for INIT-SIPI-SIPI, and have to be started by the kernel.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct CpuInfo {
+    /// Local APIC ID (x2APIC ID, if the processor uses x2APIC mode)
+    pub apic_id: u32,
+    /// Physical package number, [`CpuInfo::UNKNOWN`] if the topology is unknown
+    pub package: u32,
+    /// Core number within package, [`CpuInfo::UNKNOWN`] if the topology is unknown
+    pub core: u32,
+    /// Thread number within core, [`CpuInfo::UNKNOWN`] if the topology is unknown
+    pub thread: u32,
+    pub flags: CpuFlags,
+}
+
+impl CpuInfo {
+    /// Location within the processor topology that could not be determined
+    pub const UNKNOWN: u32 = u32::MAX;
+
+    pub fn is_bootstrap(&self) -> bool {
+        self.flags.contains(CpuFlags::BOOTSTRAP)
+    }
+}
+
+unsafe impl Tag for CpuInfo {
+    const TYPE: u32 = TAG_CPU;
+}
@@ -0,0 +1,76 @@
+use core::str;
+
+use crate::boot::tag::{Tag, TAG_BOOT_DEVICE};
+
+/// Maximum length of the device path string in bytes. Longer paths are truncated.
+pub const BOOT_DEVICE_PATH_MAX_LEN: usize = 256;
+
+/// Device and partition the kernel image has been loaded from
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct BootDevice {
+    /// Unique partition GUID of GPT partitions in on-disk (mixed endian) byte order, zero otherwise
+    pub partition_guid: [u8; 16],
+    /// Partition number starting at 1, zero if the device is not a partition
+    pub partition_number: u32,
+    /// Length of the device path string in bytes
+    pub device_path_len: u32,
+    /// UTF-8 textual representation of the UEFI device path, e.g. `PciRoot(0x0)/Pci(0x1,0x1)/Ata(0x0)/HD(1,GPT,...)`
+    pub device_path: [u8; BOOT_DEVICE_PATH_MAX_LEN],
+}
+
+impl BootDevice {
+    /// Creates a boot device, truncating `device_path` to [`BOOT_DEVICE_PATH_MAX_LEN`] bytes at a character boundary
+    pub fn new(device_path: &str, partition_guid: Option<[u8; 16]>, partition_number: u32) -> Self {
+        let mut len = device_path.len().min(BOOT_DEVICE_PATH_MAX_LEN);
+        while !device_path.is_char_boundary(len) {
+            len -= 1;
+        }
+
+        let mut buffer = [0; BOOT_DEVICE_PATH_MAX_LEN];
+        buffer[..len].copy_from_slice(&device_path.as_bytes()[..len]);
+
+        Self {
+            partition_guid: partition_guid.unwrap_or_default(),
+            partition_number,
+            device_path_len: len as u32,
+            device_path: buffer,
+        }
+    }
+
+    /// Device path string. Empty if it is malformed.
+    pub fn device_path(&self) -> &str {
+        self.device_path
+            .get(..self.device_path_len as usize)
+            .and_then(|bytes| str::from_utf8(bytes).ok())
+            .unwrap_or_default()
+    }
+
+    /// Unique partition GUID, if the kernel has been loaded from a GPT partition
+    pub fn partition_guid(&self) -> Option<[u8; 16]> {
+        (self.partition_guid != [0; 16]).then_some(self.partition_guid)
+    }
+}
+
+unsafe impl Tag for BootDevice {
+    const TYPE: u32 = TAG_BOOT_DEVICE;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncates_device_path_at_char_boundary() {
+        let mut device_path = [b'a'; BOOT_DEVICE_PATH_MAX_LEN + 1];
+        device_path[BOOT_DEVICE_PATH_MAX_LEN - 1..].copy_from_slice("ä".as_bytes());
+        let device_path = str::from_utf8(&device_path).unwrap();
+
+        let boot_device = BootDevice::new(device_path, None, 0);
+        assert_eq!(
+            boot_device.device_path(),
+            &device_path[..BOOT_DEVICE_PATH_MAX_LEN - 1]
+        );
+        assert_eq!(boot_device.partition_guid(), None);
+    }
+}
@@ -0,0 +1,46 @@
+use core::ptr;
+
+use crate::{
+    boot::tag::{Tag, TAG_ENTROPY_SEED},
+    memory::VirtualAddress,
+};
+
+/// Size of the entropy seed provided by the loader in bytes
+pub const ENTROPY_SEED_SIZE: usize = 64;
+
+/// Random seed gathered by the loader from firmware and hardware sources. Stored in a separate kernel data page, so it can be wiped after use.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct EntropySeed {
+    /// Higher half address of the seed
+    pub address: VirtualAddress,
+    /// Size of the seed in bytes
+    pub size: u64,
+}
+
+impl EntropySeed {
+    /// Reads the seed and zeroes it in memory. Any further call returns zeroes.
+    ///
+    /// # Safety
+    /// The seed must be mapped writable at `address` and must not be accessed concurrently.
+    pub unsafe fn take(&self) -> [u8; ENTROPY_SEED_SIZE] {
+        let mut seed = [0; ENTROPY_SEED_SIZE];
+        let seed_ptr = self.address as *mut u8;
+
+        for index in 0..self.size as usize {
+            unsafe {
+                if let Some(byte) = seed.get_mut(index) {
+                    *byte = ptr::read_volatile(seed_ptr.add(index));
+                }
+                // volatile, so that the compiler does not optimize out wiping the seed
+                ptr::write_volatile(seed_ptr.add(index), 0);
+            }
+        }
+
+        seed
+    }
+}
+
+unsafe impl Tag for EntropySeed {
+    const TYPE: u32 = TAG_ENTROPY_SEED;
+}
@@ -0,0 +1,292 @@
+//! Subset of the [Limine boot protocol](https://github.com/limine-bootloader/limine/blob/trunk/PROTOCOL.md) answered by the
+//! loader's compatibility mode. All pointers in responses are higher half direct map addresses, unless noted otherwise.
+
+use crate::memory::{PhysicalAddress, VirtualAddress};
+
+/// First two words of every request identifier
+pub const COMMON_MAGIC: [u64; 2] = [0xc7b1dd30df4c8b88, 0x0a82e883a194f07b];
+/// First two words of the base revision marker, followed by the revision
+pub const BASE_REVISION_MAGIC: [u64; 2] = [0xf9562b2d5c95a6c8, 0x6a7b384944536bdc];
+/// Highest base revision supported by the loader
+pub const MAX_BASE_REVISION: u64 = 3;
+/// Base revision from which the RSDP response holds a physical address
+pub const RSDP_PHYSICAL_BASE_REVISION: u64 = 3;
+
+/// Offset of the response pointer in every request (identifier and revision precede it)
+pub const RESPONSE_POINTER_OFFSET: usize = 40;
+/// Offset of the flags of the SMP request
+pub const SMP_REQUEST_FLAGS_OFFSET: usize = 48;
+
+pub const MEMORY_MAP_USABLE: u64 = 0;
+pub const MEMORY_MAP_RESERVED: u64 = 1;
+pub const MEMORY_MAP_ACPI_RECLAIMABLE: u64 = 2;
+pub const MEMORY_MAP_ACPI_NVS: u64 = 3;
+pub const MEMORY_MAP_BAD_MEMORY: u64 = 4;
+pub const MEMORY_MAP_BOOTLOADER_RECLAIMABLE: u64 = 5;
+pub const MEMORY_MAP_KERNEL_AND_MODULES: u64 = 6;
+pub const MEMORY_MAP_FRAMEBUFFER: u64 = 7;
+
+/// Framebuffer memory model of RGB framebuffers, the only model defined
+pub const FRAMEBUFFER_RGB: u8 = 1;
+
+/// Requests answered by the loader
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RequestKind {
+    BootloaderInfo,
+    Hhdm,
+    Framebuffer,
+    MemoryMap,
+    Rsdp,
+    KernelAddress,
+    Module,
+    Smp,
+}
+
+impl RequestKind {
+    const ALL: [Self; 8] = [
+        Self::BootloaderInfo,
+        Self::Hhdm,
+        Self::Framebuffer,
+        Self::MemoryMap,
+        Self::Rsdp,
+        Self::KernelAddress,
+        Self::Module,
+        Self::Smp,
+    ];
+
+    /// Last two words of the request identifier
+    pub const fn id(self) -> [u64; 2] {
+        match self {
+            Self::BootloaderInfo => [0xf55038d8e2a1202f, 0x279426fcf5f59740],
+            Self::Hhdm => [0x48dcf1cb8ad2b852, 0x63984e959a98244b],
+            Self::Framebuffer => [0x9d5827dcd881dd75, 0xa3148604f6fab11b],
+            Self::MemoryMap => [0x67cf3d9d378a806f, 0xe304acdfc50c3c62],
+            Self::Rsdp => [0xc5e77b6b397e7b43, 0x27637845accdcf3c],
+            Self::KernelAddress => [0x71ba76863cc55f63, 0xb2644a48c516a487],
+            Self::Module => [0x3e7e279702be32af, 0xca1c4f3bd1280cee],
+            Self::Smp => [0x95a67b819a1b857e, 0xa0b61b723b6a73e0],
+        }
+    }
+
+    pub fn from_id(id: [u64; 2]) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.id() == id)
+    }
+}
+
+/// Request or base revision marker found in a kernel image
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Marker {
+    Request {
+        /// Offset of the request in the scanned image
+        offset: usize,
+        /// `None`, if the request is unknown to the loader
+        kind: Option<RequestKind>,
+        revision: u64,
+    },
+    BaseRevision {
+        /// Offset of the marker in the scanned image
+        offset: usize,
+        revision: u64,
+    },
+}
+
+/// Scans an 8 byte aligned image for requests and base revision markers. Requests are 8 byte aligned by the protocol.
+pub fn find_markers(image: &[u8]) -> impl Iterator<Item = Marker> + '_ {
+    let word = move |offset: usize| {
+        image
+            .get(offset..offset + 8)
+            .map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap()))
+    };
+
+    (0..image.len()).step_by(8).filter_map(move |offset| {
+        let magic = [word(offset)?, word(offset + 8)?];
+        if magic == COMMON_MAGIC {
+            let id = [word(offset + 16)?, word(offset + 24)?];
+            Some(Marker::Request {
+                offset,
+                kind: RequestKind::from_id(id),
+                revision: word(offset + 32)?,
+            })
+        } else if magic == BASE_REVISION_MAGIC {
+            Some(Marker::BaseRevision {
+                offset,
+                revision: word(offset + 16)?,
+            })
+        } else {
+            None
+        }
+    })
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct BootloaderInfoResponse {
+    pub revision: u64,
+    /// Null terminated loader name
+    pub name: VirtualAddress,
+    /// Null terminated loader version
+    pub version: VirtualAddress,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct HhdmResponse {
+    pub revision: u64,
+    /// Virtual address of physical address zero
+    pub offset: u64,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct FramebufferResponse {
+    pub revision: u64,
+    pub framebuffer_count: u64,
+    /// Array of `framebuffer_count` pointers to [`Framebuffer`]s
+    pub framebuffers: VirtualAddress,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct Framebuffer {
+    pub address: VirtualAddress,
+    pub width: u64,
+    pub height: u64,
+    /// Bytes per scanline
+    pub pitch: u64,
+    /// Bits per pixel
+    pub bpp: u16,
+    pub memory_model: u8,
+    pub red_mask_size: u8,
+    pub red_mask_shift: u8,
+    pub green_mask_size: u8,
+    pub green_mask_shift: u8,
+    pub blue_mask_size: u8,
+    pub blue_mask_shift: u8,
+    pub unused: [u8; 7],
+    pub edid_size: u64,
+    pub edid: VirtualAddress,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct MemoryMapResponse {
+    pub revision: u64,
+    pub entry_count: u64,
+    /// Array of `entry_count` pointers to [`MemoryMapEntry`]s
+    pub entries: VirtualAddress,
+}
+
+/// Entries are sorted by base address and do not overlap. Usable and bootloader reclaimable entries are page aligned.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct MemoryMapEntry {
+    pub base: PhysicalAddress,
+    pub length: u64,
+    /// One of the `MEMORY_MAP_*` types
+    pub r#type: u64,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct RsdpResponse {
+    pub revision: u64,
+    /// Higher half direct map address before [`RSDP_PHYSICAL_BASE_REVISION`], physical address since
+    pub address: u64,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct KernelAddressResponse {
+    pub revision: u64,
+    pub physical_base: PhysicalAddress,
+    pub virtual_base: VirtualAddress,
+}
+
+/// Revision 0 of the module response, without internal modules
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct ModuleResponse {
+    pub revision: u64,
+    pub module_count: u64,
+    /// Array of `module_count` pointers to [`File`]s
+    pub modules: VirtualAddress,
+}
+
+/// File loaded by the loader, e.g. a module
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct File {
+    pub revision: u64,
+    pub address: VirtualAddress,
+    pub size: u64,
+    /// Null terminated path of the file
+    pub path: VirtualAddress,
+    /// Null terminated command line of the file
+    pub cmdline: VirtualAddress,
+    /// 0 for generic media, 1 for optical discs and 2 for TFTP
+    pub media_type: u32,
+    pub unused: u32,
+    pub tftp_ip: u32,
+    pub tftp_port: u32,
+    /// Partition number starting at 1, zero if unknown
+    pub partition_index: u32,
+    pub mbr_disk_id: u32,
+    pub gpt_disk_uuid: [u8; 16],
+    pub gpt_part_uuid: [u8; 16],
+    pub part_uuid: [u8; 16],
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_aligned_markers() {
+        let mut words = [0u64; 24];
+        // hhdm request
+        words[2..4].copy_from_slice(&COMMON_MAGIC);
+        words[4..6].copy_from_slice(&RequestKind::Hhdm.id());
+        words[6] = 0;
+        // unknown request
+        words[8..10].copy_from_slice(&COMMON_MAGIC);
+        words[10..12].copy_from_slice(&[1, 2]);
+        words[12] = 1;
+        // base revision
+        words[15..17].copy_from_slice(&BASE_REVISION_MAGIC);
+        words[17] = 2;
+        let image: alloc::vec::Vec<u8> = words.iter().flat_map(|word| word.to_le_bytes()).collect();
+
+        let markers: alloc::vec::Vec<Marker> = find_markers(&image).collect();
+        assert_eq!(
+            markers,
+            [
+                Marker::Request {
+                    offset: 16,
+                    kind: Some(RequestKind::Hhdm),
+                    revision: 0
+                },
+                Marker::Request {
+                    offset: 64,
+                    kind: None,
+                    revision: 1
+                },
+                Marker::BaseRevision {
+                    offset: 120,
+                    revision: 2
+                },
+            ]
+        );
+
+        // markers are only searched at aligned offsets
+        assert_eq!(find_markers(&image[4..]).count(), 0);
+        // truncated request
+        assert_eq!(find_markers(&image[..48]).count(), 0);
+    }
+
+    #[test]
+    fn file_matches_protocol_layout() {
+        assert_eq!(size_of::<File>(), 112);
+        assert_eq!(core::mem::offset_of!(File, media_type), 40);
+        assert_eq!(core::mem::offset_of!(File, gpt_disk_uuid), 64);
+    }
+}
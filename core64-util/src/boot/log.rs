@@ -0,0 +1,126 @@
+use core::{fmt, slice};
+
+use crate::{
+    boot::tag::{Tag, TAG_LOADER_LOG},
+    memory::VirtualAddress,
+};
+
+/// Ring buffer of formatted loader log messages. Once the buffer is full, the oldest messages are overwritten.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct LoaderLog {
+    /// Higher half address of the ring buffer
+    pub address: VirtualAddress,
+    /// Size of the ring buffer in bytes
+    pub capacity: u64,
+    /// Total amount of bytes written to the ring buffer, including overwritten ones
+    pub written: u64,
+}
+
+impl LoaderLog {
+    /// Returns the retained log in chronological order, split into two parts at the wrap-around point.
+    /// If the log has wrapped, the first part starts in the middle of a message.
+    ///
+    /// # Safety
+    /// The ring buffer must be mapped at `address` and must not be modified while the returned slices are alive.
+    pub unsafe fn contents(&self) -> (&[u8], &[u8]) {
+        let buffer =
+            unsafe { slice::from_raw_parts(self.address as *const u8, self.capacity as usize) };
+        if self.written <= self.capacity {
+            return (&buffer[..self.written as usize], &[]);
+        }
+
+        let head = (self.written % self.capacity) as usize;
+        (&buffer[head..], &buffer[..head])
+    }
+
+    /// Whether older messages have been overwritten
+    pub fn is_truncated(&self) -> bool {
+        self.written > self.capacity
+    }
+}
+
+unsafe impl Tag for LoaderLog {
+    const TYPE: u32 = TAG_LOADER_LOG;
+}
+
+/// Writes formatted text into a ring buffer, overwriting the oldest bytes once it is full
+#[derive(Debug)]
+pub struct LogRingBuffer<'a> {
+    buffer: &'a mut [u8],
+    written: u64,
+}
+
+impl<'a> LogRingBuffer<'a> {
+    pub fn new(buffer: &'a mut [u8]) -> Self {
+        Self { buffer, written: 0 }
+    }
+
+    /// Total amount of bytes written, including overwritten ones
+    pub fn written(&self) -> u64 {
+        self.written
+    }
+
+    pub fn capacity(&self) -> u64 {
+        self.buffer.len() as u64
+    }
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        if self.buffer.is_empty() {
+            return;
+        }
+
+        for byte in bytes {
+            let index = (self.written % self.capacity()) as usize;
+            self.buffer[index] = *byte;
+            self.written += 1;
+        }
+    }
+}
+
+impl fmt::Write for LogRingBuffer<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.write_bytes(s.as_bytes());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn loader_log(ring: &LogRingBuffer) -> LoaderLog {
+        LoaderLog {
+            address: ring.buffer.as_ptr() as VirtualAddress,
+            capacity: ring.capacity(),
+            written: ring.written(),
+        }
+    }
+
+    #[test]
+    fn keeps_messages_until_full() {
+        let mut buffer = [0u8; 16];
+        let mut ring = LogRingBuffer::new(&mut buffer);
+        ring.write_bytes(b"first\n");
+        ring.write_bytes(b"second\n");
+
+        let log = loader_log(&ring);
+        assert!(!log.is_truncated());
+        assert_eq!(
+            unsafe { log.contents() },
+            (&b"first\nsecond\n"[..], &[][..])
+        );
+    }
+
+    #[test]
+    fn overwrites_oldest_bytes_when_full() {
+        let mut buffer = [0u8; 8];
+        let mut ring = LogRingBuffer::new(&mut buffer);
+        ring.write_bytes(b"abcdefgh");
+        ring.write_bytes(b"ijk");
+
+        let log = loader_log(&ring);
+        assert!(log.is_truncated());
+        assert_eq!(unsafe { log.contents() }, (&b"defgh"[..], &b"ijk"[..]));
+    }
+}
@@ -0,0 +1,557 @@
+use core::{
+    error::Error,
+    fmt::{Display, Formatter},
+    mem::{offset_of, size_of, size_of_val},
+    ptr, slice,
+};
+
+use crate::{
+    boot::{
+        cpu::CpuInfo,
+        device::BootDevice,
+        entropy::EntropySeed,
+        log::LoaderLog,
+        request::{BootFeatures, HigherHalfDirectMap},
+        tag::{align_up, Tag, TagHeader, TagIter, TAG_ALIGN, TAG_END},
+        time::{BootTimestamps, WallClockTime},
+        variable::UefiRuntime,
+    },
+    graphics::{framebuffer::FrameBufferMetadata, splash::BootSplash},
+    memory::{pmm::BitMapAllocatorState, MemoryMap, VirtualAddress, PAGE_SIZE},
+};
+
+pub mod cpu;
+pub mod device;
+pub mod entropy;
+pub mod limine;
+pub mod log;
+pub mod multiboot2;
+pub mod request;
+pub mod tag;
+pub mod time;
+pub mod variable;
+
+/// Identifies a valid boot info structure ("CORE64BI")
+pub const BOOT_INFO_MAGIC: u64 = u64::from_le_bytes(*b"CORE64BI");
+/// Changes whenever the layout of existing fields changes. Loader and kernel must agree on it.
+pub const BOOT_INFO_VERSION_MAJOR: u16 = 2;
+/// Changes whenever fields are appended to [`BootInfo`]. Kernels accept boot info of any minor version.
+pub const BOOT_INFO_VERSION_MINOR: u16 = 0;
+pub const BOOT_INFO_VERSION: u32 =
+    ((BOOT_INFO_VERSION_MAJOR as u32) << 16) | BOOT_INFO_VERSION_MINOR as u32;
+
+/// Maximum size of boot info including its tag list. The loader provides boot info in a contiguous region of at most this size.
+///
+/// Raised from one page in major version 2, as kernels of major version 1 reject larger boot info.
+pub const BOOT_INFO_MAX_SIZE: usize = 16 * PAGE_SIZE;
+
+/// Size of the fields every loader of the current major version provides
+const BOOT_INFO_MIN_SIZE: usize =
+    offset_of!(BootInfo, frame_buffer_metadata) + size_of::<FrameBufferMetadata>();
+
+const FNV_OFFSET_BASIS: u32 = 0x811c_9dc5;
+const FNV_PRIME: u32 = 0x0100_0193;
+
+/// Information passed from the loader to the kernel. Stored at the start of a page aligned region of at most [`BOOT_INFO_MAX_SIZE`], followed by the tag list.
+///
+/// Fields may only be appended (bumping [`BOOT_INFO_VERSION_MINOR`]), so that loaders and kernels of different builds stay compatible.
+#[repr(C)]
+#[derive(Clone, Debug)]
+pub struct BootInfo {
+    /// Always [`BOOT_INFO_MAGIC`]
+    pub magic: u64,
+    /// [`BOOT_INFO_VERSION`] of the loader: major version in upper 16 bits, minor version in lower 16 bits
+    pub version: u32,
+    /// Size of this struct in bytes as known to the loader
+    pub size: u32,
+    /// Size of this struct and the tag list following it in bytes
+    pub total_size: u32,
+    /// FNV-1a checksum over `total_size` bytes, computed with this field set to zero
+    pub checksum: u32,
+    pub frame_buffer_metadata: FrameBufferMetadata,
+    /// Physical memory map. The descriptors are mapped into the higher half directly after the boot info pages.
+    pub memory_map: MemoryMap,
+    /// State of the loader's page frame allocator after setting up the kernel address space.
+    pub frame_allocator: BitMapAllocatorState,
+    /// Higher half layout of the kernel stack including its guard pages.
+    pub kernel_stack: KernelStack,
+}
+
+impl BootInfo {
+    /// Creates boot info of the current version. Size and checksum are filled in by [`BootInfoWriter`].
+    pub fn new(
+        frame_buffer_metadata: FrameBufferMetadata,
+        memory_map: MemoryMap,
+        frame_allocator: BitMapAllocatorState,
+        kernel_stack: KernelStack,
+    ) -> Self {
+        Self {
+            magic: BOOT_INFO_MAGIC,
+            version: BOOT_INFO_VERSION,
+            size: size_of::<Self>() as u32,
+            total_size: 0,
+            checksum: 0,
+            frame_buffer_metadata,
+            memory_map,
+            frame_allocator,
+            kernel_stack,
+        }
+    }
+
+    /// Checks magic, version, size and checksum of boot info provided by a loader of a possibly different build.
+    pub fn validate(&self) -> Result<&Self, BootInfoError> {
+        if self.magic != BOOT_INFO_MAGIC {
+            return Err(BootInfoError::InvalidMagic(self.magic));
+        }
+
+        if self.version_major() != BOOT_INFO_VERSION_MAJOR {
+            return Err(BootInfoError::IncompatibleVersion(self.version));
+        }
+
+        if (self.size as usize) < BOOT_INFO_MIN_SIZE
+            || self.total_size < self.size
+            || self.total_size as usize > BOOT_INFO_MAX_SIZE
+        {
+            return Err(BootInfoError::InvalidSize(self.size, self.total_size));
+        }
+
+        let checksum = checksum(self.bytes());
+        if checksum != self.checksum {
+            return Err(BootInfoError::ChecksumMismatch(self.checksum, checksum));
+        }
+
+        Ok(self)
+    }
+
+    pub fn version_major(&self) -> u16 {
+        (self.version >> 16) as u16
+    }
+
+    pub fn version_minor(&self) -> u16 {
+        self.version as u16
+    }
+
+    /// Memory map, if provided by the loader
+    pub fn memory_map(&self) -> Option<&MemoryMap> {
+        self.provides(offset_of!(Self, memory_map) + size_of::<MemoryMap>())
+            .then_some(&self.memory_map)
+    }
+
+    /// Page frame allocator state of the loader, if provided by the loader. Describes the bitmap for [`Self::memory_map`].
+    pub fn frame_allocator(&self) -> Option<&BitMapAllocatorState> {
+        self.provides(offset_of!(Self, frame_allocator) + size_of::<BitMapAllocatorState>())
+            .then_some(&self.frame_allocator)
+    }
+
+    /// Iterates over all tags, including tags of types unknown to the kernel
+    pub fn tags(&self) -> TagIter<'_> {
+        let bytes = self.bytes();
+        TagIter::new(
+            bytes
+                .get(align_up(self.size as usize, TAG_ALIGN)..)
+                .unwrap_or(&[]),
+        )
+    }
+
+    /// Returns the first tag of type `T`, if the loader provided it
+    pub fn tag<T: Tag>(&self) -> Option<&T> {
+        self.tags().find_map(|tag| tag.get::<T>())
+    }
+
+    /// Returns the first tag holding a list of `T`, if the loader provided it
+    pub fn tag_slice<T: Tag>(&self) -> Option<&[T]> {
+        self.tags().find_map(|tag| tag.get_slice::<T>())
+    }
+
+    /// Kernel stack layout, if provided by the loader
+    pub fn kernel_stack(&self) -> Option<&KernelStack> {
+        self.provides(offset_of!(Self, kernel_stack) + size_of::<KernelStack>())
+            .then_some(&self.kernel_stack)
+    }
+
+    /// Optional fields and tags provided by the loader
+    pub fn features(&self) -> BootFeatures {
+        let mut features = BootFeatures::empty();
+        features.set(BootFeatures::MEMORY_MAP, self.memory_map().is_some());
+        features.set(
+            BootFeatures::FRAME_ALLOCATOR,
+            self.frame_allocator().is_some(),
+        );
+        features.set(BootFeatures::KERNEL_STACK, self.kernel_stack().is_some());
+        features.set(BootFeatures::CPUS, self.tag_slice::<CpuInfo>().is_some());
+        features.set(
+            BootFeatures::ENTROPY_SEED,
+            self.tag::<EntropySeed>().is_some(),
+        );
+        features.set(
+            BootFeatures::BOOT_TIMESTAMPS,
+            self.tag::<BootTimestamps>().is_some(),
+        );
+        features.set(
+            BootFeatures::WALL_CLOCK_TIME,
+            self.tag::<WallClockTime>().is_some(),
+        );
+        features.set(BootFeatures::LOADER_LOG, self.tag::<LoaderLog>().is_some());
+        features.set(
+            BootFeatures::BOOT_DEVICE,
+            self.tag::<BootDevice>().is_some(),
+        );
+        features.set(
+            BootFeatures::HHDM,
+            self.tag::<HigherHalfDirectMap>().is_some(),
+        );
+        features.set(
+            BootFeatures::UEFI_RUNTIME,
+            self.tag::<UefiRuntime>().is_some(),
+        );
+        features.set(
+            BootFeatures::BOOT_SPLASH,
+            self.tag::<BootSplash>().is_some(),
+        );
+        features
+    }
+
+    /// Whether the loader's boot info extends up to `end` bytes, i.e. contains a field appended in a later minor version
+    fn provides(&self, end: usize) -> bool {
+        self.size as usize >= end
+    }
+
+    /// The `total_size` bytes written by the loader, which may be less than the size of this struct for older loaders
+    fn bytes(&self) -> &[u8] {
+        let len = (self.total_size as usize).min(BOOT_INFO_MAX_SIZE);
+        unsafe { slice::from_raw_parts(self as *const Self as *const u8, len) }
+    }
+}
+
+/// Virtual layout of the kernel stack. The guard regions are never mapped, so a stack overflow causes a page fault.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct KernelStack {
+    /// Lowest mapped address of the stack
+    pub bottom: VirtualAddress,
+    /// Initial stack pointer, directly after the highest mapped address of the stack
+    pub top: VirtualAddress,
+    /// Size of the unmapped guard region directly below `bottom` in bytes
+    pub guard_below: u64,
+    /// Size of the unmapped guard region starting at `top` in bytes
+    pub guard_above: u64,
+}
+
+impl KernelStack {
+    /// Whether a faulting address lies within one of the guard regions, i.e. the fault was caused by a stack overflow (or underflow).
+    pub fn is_guard_address(&self, address: VirtualAddress) -> bool {
+        (self.bottom - self.guard_below..self.bottom).contains(&address)
+            || (self.top..self.top + self.guard_above).contains(&address)
+    }
+}
+
+/// Writes boot info and its tag list into a zeroed buffer
+#[derive(Debug)]
+pub struct BootInfoWriter<'a> {
+    buffer: &'a mut [u8],
+    offset: usize,
+}
+
+impl<'a> BootInfoWriter<'a> {
+    pub fn new(buffer: &'a mut [u8], boot_info: BootInfo) -> Result<Self, BootInfoError> {
+        let offset = align_up(size_of::<BootInfo>(), TAG_ALIGN);
+        if buffer.len() < offset + size_of::<TagHeader>() || buffer.len() > BOOT_INFO_MAX_SIZE {
+            return Err(BootInfoError::InvalidBuffer);
+        }
+        if !buffer.as_ptr().cast::<BootInfo>().is_aligned() {
+            return Err(BootInfoError::InvalidBuffer);
+        }
+
+        buffer.fill(0);
+        unsafe { ptr::write(buffer.as_mut_ptr() as *mut BootInfo, boot_info) };
+
+        Ok(Self { buffer, offset })
+    }
+
+    /// Access to the boot info, e.g. to fill in fields after creating the writer
+    pub fn boot_info(&mut self) -> &mut BootInfo {
+        unsafe { &mut *(self.buffer.as_mut_ptr() as *mut BootInfo) }
+    }
+
+    /// Appends tag to the tag list. Fails, if there is no space left in the buffer.
+    pub fn add_tag<T: Tag>(&mut self, data: &T) -> Result<(), BootInfoError> {
+        self.add_tag_slice(slice::from_ref(data))
+    }
+
+    /// Appends tag holding a list of `T` to the tag list. Fails, if there is no space left in the buffer.
+    pub fn add_tag_slice<T: Tag>(&mut self, data: &[T]) -> Result<(), BootInfoError> {
+        let data_start = self.offset + size_of::<TagHeader>();
+        let data_size = size_of_val(data);
+        let next_offset = align_up(data_start + data_size, TAG_ALIGN);
+
+        // always leave space for the end tag
+        if next_offset + size_of::<TagHeader>() > self.buffer.len() {
+            return Err(BootInfoError::InvalidBuffer);
+        }
+
+        self.write_header(T::TYPE, data_size as u32);
+        unsafe {
+            ptr::copy_nonoverlapping(
+                data.as_ptr(),
+                self.buffer.as_mut_ptr().add(data_start) as *mut T,
+                data.len(),
+            )
+        };
+        self.offset = next_offset;
+
+        Ok(())
+    }
+
+    /// Terminates the tag list and calculates the checksum
+    pub fn finish(mut self) -> &'a BootInfo {
+        self.write_header(TAG_END, 0);
+        let total_size = self.offset + size_of::<TagHeader>();
+        self.boot_info().total_size = total_size as u32;
+
+        let checksum = checksum(&self.buffer[..total_size]);
+        self.boot_info().checksum = checksum;
+
+        unsafe { &*(self.buffer.as_ptr() as *const BootInfo) }
+    }
+
+    fn write_header(&mut self, r#type: u32, size: u32) {
+        let header = TagHeader { r#type, size };
+        unsafe {
+            ptr::write(
+                self.buffer.as_mut_ptr().add(self.offset) as *mut TagHeader,
+                header,
+            )
+        };
+    }
+}
+
+/// FNV-1a hash of the boot info bytes, treating the checksum field as zero
+fn checksum(bytes: &[u8]) -> u32 {
+    let checksum_field = offset_of!(BootInfo, checksum)..offset_of!(BootInfo, checksum) + 4;
+    bytes
+        .iter()
+        .enumerate()
+        .fold(FNV_OFFSET_BASIS, |hash, (index, byte)| {
+            let byte = if checksum_field.contains(&index) {
+                0
+            } else {
+                *byte
+            };
+            (hash ^ byte as u32).wrapping_mul(FNV_PRIME)
+        })
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BootInfoError {
+    /// Boot info does not start with [`BOOT_INFO_MAGIC`]
+    InvalidMagic(u64),
+    /// Loader was built with a different major version
+    IncompatibleVersion(u32),
+    /// Struct size or total size are out of range
+    InvalidSize(u32, u32),
+    /// Stored checksum does not match calculated checksum
+    ChecksumMismatch(u32, u32),
+    /// Buffer passed to [`BootInfoWriter`] is misaligned or has an invalid size
+    InvalidBuffer,
+}
+
+impl Display for BootInfoError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl Error for BootInfoError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[repr(C, align(4096))]
+    struct Page([u8; PAGE_SIZE]);
+
+    #[repr(C)]
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    struct TestTag {
+        value: u64,
+    }
+
+    unsafe impl Tag for TestTag {
+        const TYPE: u32 = 0xFFFF;
+    }
+
+    fn frame_buffer_metadata() -> FrameBufferMetadata {
+        FrameBufferMetadata {
+            base: 0x8000_0000,
+            size: 0x1000,
+            width: 32,
+            height: 32,
+            stride: 32,
+            is_rgb: true,
+        }
+    }
+
+    fn new_boot_info() -> BootInfo {
+        let memory_map = MemoryMap {
+            descriptors: ptr::null_mut(),
+            descriptors_len: 0,
+            first_addr: 0,
+            last_addr: 0,
+            first_available_addr: 0,
+            last_available_addr: 0,
+        };
+        BootInfo::new(
+            frame_buffer_metadata(),
+            memory_map,
+            BitMapAllocatorState::default(),
+            KernelStack::default(),
+        )
+    }
+
+    fn boot_info(page: &mut Page) -> &mut BootInfo {
+        unsafe { &mut *(page.0.as_mut_ptr() as *mut BootInfo) }
+    }
+
+    #[test]
+    fn validates_written_boot_info() {
+        let mut page = Page([0; PAGE_SIZE]);
+        let mut writer = BootInfoWriter::new(&mut page.0, new_boot_info()).unwrap();
+        writer.add_tag(&TestTag { value: 42 }).unwrap();
+        let boot_info = writer.finish().validate().unwrap();
+
+        assert_eq!(boot_info.frame_buffer_metadata.width, 32);
+        assert_eq!(boot_info.tag::<TestTag>(), Some(&TestTag { value: 42 }));
+        assert_eq!(boot_info.tags().count(), 1);
+    }
+
+    #[test]
+    fn rejects_corrupted_boot_info() {
+        let mut page = Page([0; PAGE_SIZE]);
+        BootInfoWriter::new(&mut page.0, new_boot_info())
+            .unwrap()
+            .finish();
+
+        boot_info(&mut page).frame_buffer_metadata.width = 64;
+        assert!(matches!(
+            boot_info(&mut page).validate(),
+            Err(BootInfoError::ChecksumMismatch(_, _))
+        ));
+
+        boot_info(&mut page).magic = 0;
+        assert_eq!(
+            boot_info(&mut page).validate().unwrap_err(),
+            BootInfoError::InvalidMagic(0)
+        );
+    }
+
+    #[test]
+    fn rejects_other_major_version() {
+        let mut page = Page([0; PAGE_SIZE]);
+        let mut writer = BootInfoWriter::new(&mut page.0, new_boot_info()).unwrap();
+        writer.boot_info().version = (BOOT_INFO_VERSION_MAJOR as u32 + 1) << 16;
+        let boot_info = writer.finish();
+
+        assert!(matches!(
+            boot_info.validate(),
+            Err(BootInfoError::IncompatibleVersion(_))
+        ));
+    }
+
+    #[test]
+    fn omits_fields_of_older_minor_version() {
+        let mut page = Page([0; PAGE_SIZE]);
+        let mut writer = BootInfoWriter::new(&mut page.0, new_boot_info()).unwrap();
+        writer.boot_info().version = (BOOT_INFO_VERSION_MAJOR as u32) << 16;
+        writer.boot_info().size = BOOT_INFO_MIN_SIZE as u32;
+        let boot_info = writer.finish().validate().unwrap();
+
+        assert!(boot_info.memory_map().is_none());
+        assert!(boot_info.frame_allocator().is_none());
+        assert!(boot_info.kernel_stack().is_none());
+        assert!(new_boot_info().memory_map().is_some());
+    }
+
+    #[test]
+    fn validates_boot_info_of_older_loader() {
+        // an older loader only writes the fields it knows about, directly followed by its tag list
+        let mut page = Page([0; PAGE_SIZE]);
+        let size = BOOT_INFO_MIN_SIZE;
+        let total_size = align_up(size, TAG_ALIGN) + size_of::<TagHeader>();
+        assert!(total_size < size_of::<BootInfo>());
+        unsafe {
+            ptr::copy_nonoverlapping(
+                &new_boot_info() as *const BootInfo as *const u8,
+                page.0.as_mut_ptr(),
+                size,
+            )
+        };
+        boot_info(&mut page).version = (BOOT_INFO_VERSION_MAJOR as u32) << 16;
+        boot_info(&mut page).size = size as u32;
+        boot_info(&mut page).total_size = total_size as u32;
+        boot_info(&mut page).checksum = checksum(&page.0[..total_size]);
+
+        let boot_info = boot_info(&mut page).validate().unwrap();
+        assert!(boot_info.memory_map().is_none());
+        assert_eq!(boot_info.tags().count(), 0);
+    }
+
+    #[test]
+    fn detects_stack_guard_addresses() {
+        let kernel_stack = KernelStack {
+            bottom: 0xFFFF_FFFF_6000_4000,
+            top: 0xFFFF_FFFF_6010_4000,
+            guard_below: 0x4000,
+            guard_above: 0x1000,
+        };
+
+        assert!(kernel_stack.is_guard_address(0xFFFF_FFFF_6000_3FF8));
+        assert!(kernel_stack.is_guard_address(0xFFFF_FFFF_6000_0000));
+        assert!(kernel_stack.is_guard_address(0xFFFF_FFFF_6010_4000));
+        assert!(!kernel_stack.is_guard_address(0xFFFF_FFFF_6000_4000));
+        assert!(!kernel_stack.is_guard_address(0xFFFF_FFFF_6010_3FF8));
+        assert!(!kernel_stack.is_guard_address(0xFFFF_FFFF_6010_5000));
+    }
+
+    #[test]
+    fn accepts_other_minor_version_and_unknown_tags() {
+        #[repr(C)]
+        #[derive(Copy, Clone)]
+        struct UnknownTag([u8; 3]);
+
+        unsafe impl Tag for UnknownTag {
+            const TYPE: u32 = 0xFFFE;
+        }
+
+        let mut page = Page([0; PAGE_SIZE]);
+        let mut writer = BootInfoWriter::new(&mut page.0, new_boot_info()).unwrap();
+        writer.boot_info().version = BOOT_INFO_VERSION + 1;
+        writer.add_tag(&UnknownTag([1, 2, 3])).unwrap();
+        writer.add_tag(&TestTag { value: 7 }).unwrap();
+        let boot_info = writer.finish().validate().unwrap();
+
+        assert_eq!(boot_info.tag::<TestTag>(), Some(&TestTag { value: 7 }));
+    }
+
+    #[test]
+    fn reads_tag_slices() {
+        let tags = [TestTag { value: 1 }, TestTag { value: 2 }];
+        let mut page = Page([0; PAGE_SIZE]);
+        let mut writer = BootInfoWriter::new(&mut page.0, new_boot_info()).unwrap();
+        writer.add_tag_slice(&tags).unwrap();
+        let boot_info = writer.finish().validate().unwrap();
+
+        assert_eq!(boot_info.tag_slice::<TestTag>(), Some(&tags[..]));
+        assert_eq!(boot_info.tag::<TestTag>(), Some(&tags[0]));
+    }
+
+    #[test]
+    fn fails_when_tag_list_is_full() {
+        let mut page = Page([0; PAGE_SIZE]);
+        let mut writer = BootInfoWriter::new(&mut page.0, new_boot_info()).unwrap();
+        let tag = TestTag { value: 0 };
+        while writer.add_tag(&tag).is_ok() {}
+
+        let boot_info = writer.finish().validate().unwrap();
+        assert!(boot_info.total_size as usize <= PAGE_SIZE);
+    }
+}
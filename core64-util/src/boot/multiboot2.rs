@@ -0,0 +1,321 @@
+//! Subset of the [Multiboot2 specification](https://www.gnu.org/software/grub/manual/multiboot2/multiboot.html) used to boot
+//! Multiboot2 kernels: header parsing and writing of the boot information structure (MBI).
+
+use core::{
+    error::Error,
+    fmt::{Display, Formatter},
+};
+
+/// Identifies the Multiboot2 header
+pub const HEADER_MAGIC: u32 = 0xE852_50D6;
+/// Passed to the kernel in `EAX`/`RAX`
+pub const BOOTLOADER_MAGIC: u32 = 0x36D7_6289;
+/// The header must be contained in the first 32 KiB of the image
+pub const HEADER_SEARCH_LIMIT: usize = 32 * 1024;
+/// Alignment of the header, header tags and boot information tags
+pub const ALIGN: usize = 8;
+/// Architecture field of i386 (including amd64) kernels
+pub const ARCHITECTURE_I386: u32 = 0;
+
+pub const HEADER_TAG_END: u16 = 0;
+pub const HEADER_TAG_INFORMATION_REQUEST: u16 = 1;
+pub const HEADER_TAG_ADDRESS: u16 = 2;
+pub const HEADER_TAG_ENTRY_ADDRESS: u16 = 3;
+pub const HEADER_TAG_CONSOLE_FLAGS: u16 = 4;
+pub const HEADER_TAG_FRAMEBUFFER: u16 = 5;
+pub const HEADER_TAG_MODULE_ALIGN: u16 = 6;
+pub const HEADER_TAG_EFI_BOOT_SERVICES: u16 = 7;
+pub const HEADER_TAG_ENTRY_ADDRESS_EFI32: u16 = 8;
+pub const HEADER_TAG_ENTRY_ADDRESS_EFI64: u16 = 9;
+pub const HEADER_TAG_RELOCATABLE: u16 = 10;
+/// Header tag flag: the loader may ignore the tag, if it does not support it
+pub const HEADER_TAG_OPTIONAL: u16 = 1;
+
+pub const TAG_END: u32 = 0;
+pub const TAG_CMDLINE: u32 = 1;
+pub const TAG_BOOT_LOADER_NAME: u32 = 2;
+pub const TAG_MODULE: u32 = 3;
+pub const TAG_BASIC_MEMINFO: u32 = 4;
+pub const TAG_MMAP: u32 = 6;
+pub const TAG_FRAMEBUFFER: u32 = 8;
+pub const TAG_EFI64: u32 = 12;
+pub const TAG_ACPI_OLD: u32 = 14;
+pub const TAG_ACPI_NEW: u32 = 15;
+/// UEFI memory map: descriptor size, descriptor version and the raw descriptors
+pub const TAG_EFI_MMAP: u32 = 17;
+/// Empty tag, present if boot services have not been exited
+pub const TAG_EFI_BS_NOT_TERMINATED: u32 = 18;
+pub const TAG_EFI64_IMAGE_HANDLE: u32 = 20;
+
+pub const MEMORY_AVAILABLE: u32 = 1;
+pub const MEMORY_RESERVED: u32 = 2;
+pub const MEMORY_ACPI_RECLAIMABLE: u32 = 3;
+pub const MEMORY_NVS: u32 = 4;
+pub const MEMORY_BAD_RAM: u32 = 5;
+
+/// Size of a memory map entry (base address, length, type, reserved)
+pub const MMAP_ENTRY_SIZE: usize = 24;
+/// Framebuffer type of direct RGB framebuffers
+pub const FRAMEBUFFER_TYPE_RGB: u8 = 1;
+
+/// Multiboot2 header found in a kernel image
+#[derive(Copy, Clone, Debug)]
+pub struct Header<'a> {
+    /// Offset of the header in the image
+    pub offset: usize,
+    pub architecture: u32,
+    tags: &'a [u8],
+}
+
+impl<'a> Header<'a> {
+    /// Searches the first [`HEADER_SEARCH_LIMIT`] bytes of an image for a header with a valid checksum
+    pub fn find(image: &'a [u8]) -> Option<Self> {
+        let search_area = &image[..image.len().min(HEADER_SEARCH_LIMIT)];
+        (0..search_area.len())
+            .step_by(ALIGN)
+            .find_map(|offset| Self::parse(image, offset))
+    }
+
+    fn parse(image: &'a [u8], offset: usize) -> Option<Self> {
+        let field = |index: usize| read_u32(image, offset + 4 * index);
+        if field(0)? != HEADER_MAGIC {
+            return None;
+        }
+
+        let architecture = field(1)?;
+        let header_length = field(2)?;
+        let checksum = field(3)?;
+        if HEADER_MAGIC
+            .wrapping_add(architecture)
+            .wrapping_add(header_length)
+            .wrapping_add(checksum)
+            != 0
+        {
+            return None;
+        }
+
+        let tags = image.get(offset + 16..offset.checked_add(header_length as usize)?)?;
+        Some(Self {
+            offset,
+            architecture,
+            tags,
+        })
+    }
+
+    /// Iterates over the header tags, excluding the end tag
+    pub fn tags(&self) -> impl Iterator<Item = HeaderTag<'a>> {
+        let tags = self.tags;
+        let mut offset = 0;
+        core::iter::from_fn(move || {
+            let r#type = u16::from_le_bytes(tags.get(offset..offset + 2)?.try_into().unwrap());
+            let flags = u16::from_le_bytes(tags.get(offset + 2..offset + 4)?.try_into().unwrap());
+            let size = read_u32(tags, offset + 4)? as usize;
+            if r#type == HEADER_TAG_END || size < 8 {
+                return None;
+            }
+
+            let data = tags.get(offset + 8..offset + size)?;
+            offset += size.next_multiple_of(ALIGN);
+            Some(HeaderTag {
+                r#type,
+                flags,
+                data,
+            })
+        })
+    }
+}
+
+/// Header tag with the data following type, flags and size
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct HeaderTag<'a> {
+    pub r#type: u16,
+    pub flags: u16,
+    pub data: &'a [u8],
+}
+
+impl HeaderTag<'_> {
+    pub fn is_optional(&self) -> bool {
+        self.flags & HEADER_TAG_OPTIONAL != 0
+    }
+
+    /// Reads the `index`-th `u32` of the tag data
+    pub fn u32(&self, index: usize) -> Option<u32> {
+        read_u32(self.data, 4 * index)
+    }
+
+    /// All `u32` of the tag data, e.g. the requested tag types of an information request
+    pub fn u32s(&self) -> impl Iterator<Item = u32> + '_ {
+        (0..self.data.len() / 4).filter_map(|index| self.u32(index))
+    }
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    bytes
+        .get(offset..offset.checked_add(4)?)
+        .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+/// Writes the boot information structure into a buffer
+#[derive(Debug)]
+pub struct InfoWriter<'a> {
+    buffer: &'a mut [u8],
+    offset: usize,
+}
+
+impl<'a> InfoWriter<'a> {
+    /// Fails, if the buffer is not 8 byte aligned or too small for the fixed part and the end tag
+    pub fn new(buffer: &'a mut [u8]) -> Result<Self, MultibootError> {
+        if buffer.len() < 16 || !buffer.as_ptr().cast::<u64>().is_aligned() {
+            return Err(MultibootError::InvalidBuffer);
+        }
+        buffer.fill(0);
+
+        Ok(Self { buffer, offset: 8 })
+    }
+
+    /// Appends a tag with `size` bytes of data filled in by `fill`. Fails, if there is no space left in the buffer.
+    pub fn add_tag(
+        &mut self,
+        r#type: u32,
+        size: usize,
+        fill: impl FnOnce(&mut [u8]),
+    ) -> Result<(), MultibootError> {
+        let data_start = self.offset + 8;
+        let next_offset = (data_start + size).next_multiple_of(ALIGN);
+
+        // always leave space for the end tag
+        if next_offset + 8 > self.buffer.len() {
+            return Err(MultibootError::InvalidBuffer);
+        }
+
+        self.write_header(r#type, (size + 8) as u32);
+        fill(&mut self.buffer[data_start..data_start + size]);
+        self.offset = next_offset;
+
+        Ok(())
+    }
+
+    /// Appends a tag holding a null terminated string, e.g. the command line
+    pub fn add_string_tag(&mut self, r#type: u32, string: &str) -> Result<(), MultibootError> {
+        self.add_tag(r#type, string.len() + 1, |data| {
+            data[..string.len()].copy_from_slice(string.as_bytes())
+        })
+    }
+
+    /// Terminates the tag list and returns the total size
+    pub fn finish(mut self) -> usize {
+        self.write_header(TAG_END, 8);
+        let total_size = self.offset + 8;
+        self.buffer[..4].copy_from_slice(&(total_size as u32).to_le_bytes());
+        total_size
+    }
+
+    fn write_header(&mut self, r#type: u32, size: u32) {
+        self.buffer[self.offset..self.offset + 4].copy_from_slice(&r#type.to_le_bytes());
+        self.buffer[self.offset + 4..self.offset + 8].copy_from_slice(&size.to_le_bytes());
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MultibootError {
+    /// Buffer passed to [`InfoWriter`] is misaligned or too small
+    InvalidBuffer,
+}
+
+impl Display for MultibootError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl Error for MultibootError {}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::*;
+
+    fn image_with_header(checksum_offset: u32) -> Vec<u8> {
+        let mut tags = Vec::new();
+        // framebuffer tag (optional)
+        tags.extend_from_slice(&HEADER_TAG_FRAMEBUFFER.to_le_bytes());
+        tags.extend_from_slice(&HEADER_TAG_OPTIONAL.to_le_bytes());
+        tags.extend_from_slice(&20u32.to_le_bytes());
+        for value in [1024u32, 768, 32, 0] {
+            tags.extend_from_slice(&value.to_le_bytes());
+        }
+        // efi amd64 entry address tag
+        tags.extend_from_slice(&HEADER_TAG_ENTRY_ADDRESS_EFI64.to_le_bytes());
+        tags.extend_from_slice(&0u16.to_le_bytes());
+        tags.extend_from_slice(&12u32.to_le_bytes());
+        tags.extend_from_slice(&0x10_0000u32.to_le_bytes());
+        tags.extend_from_slice(&[0; 4]);
+        // end tag
+        tags.extend_from_slice(&[0, 0, 0, 0, 8, 0, 0, 0]);
+
+        let length = 16 + tags.len() as u32;
+        let checksum = 0u32
+            .wrapping_sub(HEADER_MAGIC)
+            .wrapping_sub(ARCHITECTURE_I386)
+            .wrapping_sub(length)
+            .wrapping_add(checksum_offset);
+
+        let mut image = Vec::from([0xCC; 24]);
+        for value in [HEADER_MAGIC, ARCHITECTURE_I386, length, checksum] {
+            image.extend_from_slice(&value.to_le_bytes());
+        }
+        image.extend_from_slice(&tags);
+        image
+    }
+
+    #[test]
+    fn finds_header_and_tags() {
+        let image = image_with_header(0);
+        let header = Header::find(&image).unwrap();
+        assert_eq!(header.offset, 24);
+        assert_eq!(header.architecture, ARCHITECTURE_I386);
+
+        let tags: Vec<HeaderTag> = header.tags().collect();
+        assert_eq!(tags.len(), 2);
+        assert_eq!(tags[0].r#type, HEADER_TAG_FRAMEBUFFER);
+        assert!(tags[0].is_optional());
+        assert_eq!((tags[0].u32(0), tags[0].u32(1)), (Some(1024), Some(768)));
+        assert_eq!(tags[1].r#type, HEADER_TAG_ENTRY_ADDRESS_EFI64);
+        assert!(!tags[1].is_optional());
+        assert_eq!(tags[1].u32(0), Some(0x10_0000));
+
+        assert!(Header::find(&image_with_header(1)).is_none());
+    }
+
+    #[test]
+    fn writes_info_tags() {
+        let mut buffer = [0u64; 8];
+        let bytes = unsafe {
+            core::slice::from_raw_parts_mut(buffer.as_mut_ptr() as *mut u8, size_of_val(&buffer))
+        };
+
+        let mut writer = InfoWriter::new(bytes).unwrap();
+        writer.add_string_tag(TAG_CMDLINE, "quiet").unwrap();
+        writer
+            .add_tag(TAG_EFI64, 8, |data| {
+                data.copy_from_slice(&0x1234u64.to_le_bytes())
+            })
+            .unwrap();
+        assert_eq!(
+            writer.add_tag(TAG_MMAP, 24, |_| {}),
+            Err(MultibootError::InvalidBuffer)
+        );
+        assert_eq!(writer.finish(), 48);
+
+        assert_eq!(bytes[..4], 48u32.to_le_bytes());
+        // command line: type, size, string and padding
+        assert_eq!(bytes[8..16], [1, 0, 0, 0, 14, 0, 0, 0]);
+        assert_eq!(&bytes[16..22], b"quiet\0");
+        // efi system table pointer
+        assert_eq!(bytes[24..32], [12, 0, 0, 0, 16, 0, 0, 0]);
+        assert_eq!(bytes[32..40], 0x1234u64.to_le_bytes());
+        // end tag
+        assert_eq!(bytes[40..48], [0, 0, 0, 0, 8, 0, 0, 0]);
+    }
+}
@@ -0,0 +1,211 @@
+use core::{
+    error::Error,
+    fmt::{Display, Formatter},
+    mem::size_of,
+    ptr,
+};
+
+use bitflags::bitflags;
+
+use crate::boot::tag::{Tag, TAG_HHDM};
+
+/// Name of the kernel ELF section holding [`BootRequests`]. Emitted by [`boot_requests!`](crate::boot_requests).
+pub const BOOT_REQUESTS_SECTION: &str = ".core64_requests";
+/// Identifies boot requests ("CORE64RQ")
+pub const BOOT_REQUESTS_MAGIC: u64 = u64::from_le_bytes(*b"CORE64RQ");
+
+/// Size of the fields every kernel provides
+const BOOT_REQUESTS_MIN_SIZE: usize = 16;
+
+bitflags! {
+    /// Optional boot info fields and tags
+    #[repr(transparent)]
+    #[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+    pub struct BootFeatures: u64 {
+        const MEMORY_MAP      = 1 << 0;
+        const FRAME_ALLOCATOR = 1 << 1;
+        const KERNEL_STACK    = 1 << 2;
+        const CPUS            = 1 << 3;
+        const ENTROPY_SEED    = 1 << 4;
+        const BOOT_TIMESTAMPS = 1 << 5;
+        const WALL_CLOCK_TIME = 1 << 6;
+        const LOADER_LOG      = 1 << 7;
+        const BOOT_DEVICE     = 1 << 8;
+        const HHDM            = 1 << 9;
+        const UEFI_RUNTIME    = 1 << 10;
+        const BOOT_SPLASH     = 1 << 11;
+    }
+}
+
+/// Requirements declared by the kernel in its [`BOOT_REQUESTS_SECTION`] and honored by the loader. Zero values select the loader defaults.
+///
+/// Fields may only be appended, loaders treat fields beyond `size` as zero.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BootRequests {
+    /// Always [`BOOT_REQUESTS_MAGIC`]
+    pub magic: u64,
+    /// Size of this struct in bytes as known to the kernel
+    pub size: u32,
+    pub reserved: u32,
+    /// Kernel stack size in bytes, rounded up to pages
+    pub stack_size: u64,
+    /// Preferred framebuffer resolution. The firmware's current mode is kept, if no mode with this resolution exists.
+    pub framebuffer_width: u32,
+    pub framebuffer_height: u32,
+    /// Offset at which all physical memory is mapped (higher half direct map). Must be page aligned and between
+    /// [`HIGHER_HALF_START`](crate::memory::paging::HIGHER_HALF_START) and
+    /// [`BOOT_DATA_MAPPING_OFFSET`](crate::memory::paging::BOOT_DATA_MAPPING_OFFSET).
+    pub hhdm_offset: u64,
+    /// Boot info features the kernel cannot run without. The loader refuses to boot, if it cannot provide them.
+    pub required_features: BootFeatures,
+}
+
+impl Default for BootRequests {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+impl BootRequests {
+    /// Requests nothing but the loader defaults
+    pub const DEFAULT: Self = Self {
+        magic: BOOT_REQUESTS_MAGIC,
+        size: size_of::<Self>() as u32,
+        reserved: 0,
+        stack_size: 0,
+        framebuffer_width: 0,
+        framebuffer_height: 0,
+        hhdm_offset: 0,
+        required_features: BootFeatures::empty(),
+    };
+
+    /// Reads boot requests from the contents of the kernel's [`BOOT_REQUESTS_SECTION`], which might be of an older or newer version
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, BootRequestsError> {
+        if bytes.len() < BOOT_REQUESTS_MIN_SIZE {
+            return Err(BootRequestsError::InvalidSize(bytes.len()));
+        }
+
+        let magic = u64::from_le_bytes(bytes[..8].try_into().unwrap());
+        if magic != BOOT_REQUESTS_MAGIC {
+            return Err(BootRequestsError::InvalidMagic(magic));
+        }
+
+        let size = u32::from_le_bytes(bytes[8..12].try_into().unwrap()) as usize;
+        if size < BOOT_REQUESTS_MIN_SIZE || size > bytes.len() {
+            return Err(BootRequestsError::InvalidSize(size));
+        }
+
+        // fields unknown to the kernel stay zero
+        let mut requests = Self {
+            magic: 0,
+            size: 0,
+            ..Self::DEFAULT
+        };
+        let len = size.min(size_of::<Self>());
+        unsafe {
+            ptr::copy_nonoverlapping(bytes.as_ptr(), &mut requests as *mut Self as *mut u8, len)
+        };
+        Ok(requests)
+    }
+}
+
+/// Emits [`BootRequests`] into the kernel's [`BOOT_REQUESTS_SECTION`]. Omitted fields keep their default.
+///
+/// ```ignore
+/// core64_util::boot_requests! {
+///     stack_size: 256 * 1024,
+///     required_features: BootFeatures::MEMORY_MAP.union(BootFeatures::FRAME_ALLOCATOR),
+/// }
+/// ```
+#[macro_export]
+macro_rules! boot_requests {
+    ($($field:ident: $value:expr),* $(,)?) => {
+        #[used]
+        #[link_section = ".core64_requests"]
+        static CORE64_BOOT_REQUESTS: $crate::boot::request::BootRequests =
+            $crate::boot::request::BootRequests {
+                $($field: $value,)*
+                ..$crate::boot::request::BootRequests::DEFAULT
+            };
+    };
+}
+
+/// Physical memory mapped into the higher half as requested by [`BootRequests::hhdm_offset`]
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct HigherHalfDirectMap {
+    /// Virtual address of physical address zero
+    pub offset: u64,
+    /// Physical memory is mapped from `offset` up to `offset + size`. The map is sparse: memory described by the memory map is
+    /// mapped contiguously, memory mapped IO and the framebuffer above it only in their own ranges.
+    pub size: u64,
+}
+
+unsafe impl Tag for HigherHalfDirectMap {
+    const TYPE: u32 = TAG_HHDM;
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BootRequestsError {
+    InvalidMagic(u64),
+    InvalidSize(usize),
+}
+
+impl Display for BootRequestsError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl Error for BootRequestsError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bytes(requests: &BootRequests) -> &[u8] {
+        unsafe {
+            core::slice::from_raw_parts(
+                requests as *const BootRequests as *const u8,
+                size_of::<BootRequests>(),
+            )
+        }
+    }
+
+    #[test]
+    fn reads_requests_of_older_kernel() {
+        let requests = BootRequests {
+            stack_size: 0x4000,
+            framebuffer_width: 1024,
+            framebuffer_height: 768,
+            hhdm_offset: 0xFFFF_8000_0000_0000,
+            required_features: BootFeatures::CPUS,
+            ..BootRequests::DEFAULT
+        };
+        assert_eq!(BootRequests::from_bytes(bytes(&requests)), Ok(requests));
+
+        // kernel only knows about the stack size
+        let mut older = requests;
+        older.size = 24;
+        let older = BootRequests::from_bytes(bytes(&older)).unwrap();
+        assert_eq!(older.stack_size, 0x4000);
+        assert_eq!(older.framebuffer_width, 0);
+        assert_eq!(older.hhdm_offset, 0);
+        assert_eq!(older.required_features, BootFeatures::empty());
+    }
+
+    #[test]
+    fn rejects_invalid_requests() {
+        let mut requests = BootRequests::DEFAULT;
+        requests.magic = 0;
+        assert_eq!(
+            BootRequests::from_bytes(bytes(&requests)),
+            Err(BootRequestsError::InvalidMagic(0))
+        );
+        assert_eq!(
+            BootRequests::from_bytes(&bytes(&BootRequests::DEFAULT)[..20]),
+            Err(BootRequestsError::InvalidSize(size_of::<BootRequests>()))
+        );
+    }
+}
@@ -0,0 +1,112 @@
+use core::{mem::size_of, slice};
+
+/// Alignment of every tag header in the tag list
+pub const TAG_ALIGN: usize = 8;
+/// Type of the tag terminating the tag list
+pub const TAG_END: u32 = 0;
+/// List of [`CpuInfo`](crate::boot::cpu::CpuInfo)
+pub const TAG_CPU: u32 = 1;
+/// [`EntropySeed`](crate::boot::entropy::EntropySeed)
+pub const TAG_ENTROPY_SEED: u32 = 2;
+/// [`BootTimestamps`](crate::boot::time::BootTimestamps)
+pub const TAG_BOOT_TIMESTAMPS: u32 = 3;
+/// [`WallClockTime`](crate::boot::time::WallClockTime)
+pub const TAG_WALL_CLOCK_TIME: u32 = 4;
+/// [`LoaderLog`](crate::boot::log::LoaderLog)
+pub const TAG_LOADER_LOG: u32 = 5;
+/// [`BootDevice`](crate::boot::device::BootDevice)
+pub const TAG_BOOT_DEVICE: u32 = 6;
+/// [`HigherHalfDirectMap`](crate::boot::request::HigherHalfDirectMap)
+pub const TAG_HHDM: u32 = 7;
+/// [`UefiRuntime`](crate::boot::variable::UefiRuntime)
+pub const TAG_UEFI_RUNTIME: u32 = 8;
+/// [`BootSplash`](crate::graphics::splash::BootSplash)
+pub const TAG_BOOT_SPLASH: u32 = 9;
+
+/// Optional data passed from the loader to the kernel in the boot info tag list. A tag holds either a single `T` or a list of `T`.
+///
+/// # Safety
+/// Implementors must be `#[repr(C)]`, valid for any bit pattern and have an alignment of at most [`TAG_ALIGN`].
+/// New fields may only be appended to tags holding a single `T`, so that older kernels can still read the tag.
+pub unsafe trait Tag: Copy {
+    /// Unique tag type. Must not be [`TAG_END`].
+    const TYPE: u32;
+}
+
+/// Header preceding every tag in the tag list
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct TagHeader {
+    pub r#type: u32,
+    /// Size of the tag data following the header in bytes
+    pub size: u32,
+}
+
+/// Tag of the tag list, whose type might be unknown to the kernel
+#[derive(Copy, Clone, Debug)]
+pub struct RawTag<'a> {
+    pub r#type: u32,
+    pub data: &'a [u8],
+}
+
+impl<'a> RawTag<'a> {
+    /// Interprets tag data as `T`. Returns `None` if the tag is of a different type or too small.
+    pub fn get<T: Tag>(&self) -> Option<&'a T> {
+        if self.r#type != T::TYPE
+            || self.data.len() < size_of::<T>()
+            || !self.data.as_ptr().cast::<T>().is_aligned()
+        {
+            return None;
+        }
+        Some(unsafe { &*(self.data.as_ptr() as *const T) })
+    }
+
+    /// Interprets tag data as a list of `T`. Returns `None` if the tag is of a different type.
+    pub fn get_slice<T: Tag>(&self) -> Option<&'a [T]> {
+        if self.r#type != T::TYPE || !self.data.as_ptr().cast::<T>().is_aligned() {
+            return None;
+        }
+        let len = self.data.len() / size_of::<T>();
+        Some(unsafe { slice::from_raw_parts(self.data.as_ptr() as *const T, len) })
+    }
+}
+
+/// Iterates over the tag list. Stops at the end tag or at the first malformed tag.
+#[derive(Clone, Debug)]
+pub struct TagIter<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> TagIter<'a> {
+    pub(super) fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, offset: 0 }
+    }
+}
+
+impl<'a> Iterator for TagIter<'a> {
+    type Item = RawTag<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let data_start = self.offset + size_of::<TagHeader>();
+        let header = self.bytes.get(self.offset..data_start)?;
+        let header = unsafe { (header.as_ptr() as *const TagHeader).read_unaligned() };
+
+        if header.r#type == TAG_END {
+            return None;
+        }
+
+        let data_end = data_start + header.size as usize;
+        let data = self.bytes.get(data_start..data_end)?;
+        self.offset = align_up(data_end, TAG_ALIGN);
+
+        Some(RawTag {
+            r#type: header.r#type,
+            data,
+        })
+    }
+}
+
+pub(super) const fn align_up(value: usize, align: usize) -> usize {
+    (value + align - 1) & !(align - 1)
+}
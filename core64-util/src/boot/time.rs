@@ -0,0 +1,123 @@
+use core::fmt::{self, Display, Formatter};
+
+use crate::boot::tag::{Tag, TAG_BOOT_TIMESTAMPS, TAG_WALL_CLOCK_TIME};
+
+/// Time stamp counter values recorded by the loader at each boot stage
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct BootTimestamps {
+    /// Time stamp counter frequency in Hz as estimated by the loader, 0 if unknown
+    pub tsc_frequency: u64,
+    pub loader_entry: u64,
+    pub kernel_loaded: u64,
+    pub framebuffer_ready: u64,
+    pub boot_services_exited: u64,
+    pub paging_set_up: u64,
+    /// Directly before jumping to the kernel entry
+    pub kernel_jump: u64,
+}
+
+impl BootTimestamps {
+    /// Converts the time between two time stamp counter values into microseconds, if the frequency is known
+    pub fn micros_between(&self, start: u64, end: u64) -> Option<u64> {
+        if self.tsc_frequency == 0 {
+            return None;
+        }
+        let ticks = end.saturating_sub(start) as u128;
+        Some((ticks * 1_000_000 / self.tsc_frequency as u128) as u64)
+    }
+
+    fn stages(&self) -> [(&'static str, u64); 6] {
+        [
+            ("loader entry", self.loader_entry),
+            ("kernel loaded", self.kernel_loaded),
+            ("framebuffer ready", self.framebuffer_ready),
+            ("boot services exited", self.boot_services_exited),
+            ("paging set up", self.paging_set_up),
+            ("kernel jump", self.kernel_jump),
+        ]
+    }
+}
+
+/// Boot time breakdown with the time each stage took after the previous one
+impl Display for BootTimestamps {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let stages = self.stages();
+        for window in stages.windows(2) {
+            let ((_, start), (stage, end)) = (window[0], window[1]);
+            match self.micros_between(start, end) {
+                Some(micros) => writeln!(f, "{stage}: +{micros}us")?,
+                None => writeln!(f, "{stage}: +{} ticks", end.saturating_sub(start))?,
+            }
+        }
+        match self.micros_between(self.loader_entry, self.kernel_jump) {
+            Some(micros) => write!(f, "total: {micros}us"),
+            None => write!(
+                f,
+                "total: {} ticks",
+                self.kernel_jump.saturating_sub(self.loader_entry)
+            ),
+        }
+    }
+}
+
+unsafe impl Tag for BootTimestamps {
+    const TYPE: u32 = TAG_BOOT_TIMESTAMPS;
+}
+
+/// Calendar time reported by the firmware (`GetTime`) before exiting boot services
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct WallClockTime {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+    /// Daylight saving flags as defined by UEFI
+    pub daylight: u8,
+    pub nanosecond: u32,
+    /// Offset to UTC in minutes, [`WallClockTime::UNSPECIFIED_TIME_ZONE`] if the time is local time
+    pub time_zone: i16,
+}
+
+impl WallClockTime {
+    pub const UNSPECIFIED_TIME_ZONE: i16 = 0x07FF;
+}
+
+impl Display for WallClockTime {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )?;
+        if self.time_zone != Self::UNSPECIFIED_TIME_ZONE {
+            write!(f, " (UTC{:+}min)", self.time_zone)?;
+        }
+        Ok(())
+    }
+}
+
+unsafe impl Tag for WallClockTime {
+    const TYPE: u32 = TAG_WALL_CLOCK_TIME;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_ticks_to_micros() {
+        let timestamps = BootTimestamps {
+            tsc_frequency: 2_000_000_000,
+            ..Default::default()
+        };
+
+        assert_eq!(timestamps.micros_between(0, 2_000_000_000), Some(1_000_000));
+        assert_eq!(timestamps.micros_between(1_000, 5_000), Some(2));
+        assert_eq!(timestamps.micros_between(5_000, 1_000), Some(0));
+        assert_eq!(BootTimestamps::default().micros_between(0, 1), None);
+    }
+}
@@ -0,0 +1,172 @@
+//! UEFI variables shared by loader and kernel
+
+use core::{
+    error::Error,
+    fmt::{Display, Formatter},
+};
+
+use crate::{
+    boot::tag::{Tag, TAG_UEFI_RUNTIME},
+    memory::PhysicalAddress,
+};
+
+/// Vendor GUID of all core64 variables (`c0de64b0-07a1-4b5e-9c3f-5a6e7d8f9b10`) in UEFI (mixed endian) byte order
+pub const VARIABLE_VENDOR_GUID: [u8; 16] = [
+    0xB0, 0x64, 0xDE, 0xC0, 0xA1, 0x07, 0x5E, 0x4B, 0x9C, 0x3F, 0x5A, 0x6E, 0x7D, 0x8F, 0x9B, 0x10,
+];
+/// Non volatile variable accessible during boot and at runtime
+pub const VARIABLE_ATTRIBUTES: u32 = 0x7;
+/// Maximum length of a variable name in UTF-16 code units, excluding the terminating null
+pub const VARIABLE_NAME_MAX_LEN: usize = 63;
+
+/// Consecutive core64 boots that have not been marked successful by the kernel (little endian `u32`).
+/// Incremented by the loader before every boot and reset by the kernel once it booted successfully.
+pub const BOOT_ATTEMPTS_VARIABLE: &str = "Core64BootAttempts";
+/// Active kernel slot (little endian `u32`, see [`BootSlot`])
+pub const BOOT_SLOT_VARIABLE: &str = "Core64BootSlot";
+/// Consecutive boots of the active slot that have not been marked successful by the kernel (little endian `u32`).
+/// The loader switches to the other slot once it exceeds the configured limit.
+pub const SLOT_ATTEMPTS_VARIABLE: &str = "Core64SlotAttempts";
+
+/// Kernel slot of A/B deployments
+#[repr(u32)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BootSlot {
+    A = 0,
+    B = 1,
+}
+
+impl BootSlot {
+    pub fn from_u32(value: u32) -> Option<Self> {
+        match value {
+            0 => Some(Self::A),
+            1 => Some(Self::B),
+            _ => None,
+        }
+    }
+
+    pub fn other(self) -> Self {
+        match self {
+            Self::A => Self::B,
+            Self::B => Self::A,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum VariableError {
+    /// Name is empty or longer than [`VARIABLE_NAME_MAX_LEN`]
+    InvalidName,
+    /// `SetVariable` failed with the contained EFI status
+    Firmware(usize),
+}
+
+impl Display for VariableError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl Error for VariableError {}
+
+/// Leading part of the `EFI_RUNTIME_SERVICES` table up to `SetVariable`
+#[repr(C)]
+pub struct RawRuntimeServices {
+    /// `EFI_TABLE_HEADER`
+    pub header: [u64; 3],
+    /// `GetTime` up to `GetNextVariableName`
+    pub reserved: [usize; 8],
+    pub set_variable: unsafe extern "efiapi" fn(
+        name: *const u16,
+        vendor: *const [u8; 16],
+        attributes: u32,
+        data_size: usize,
+        data: *const u8,
+    ) -> usize,
+}
+
+/// UEFI runtime services available to the kernel. The loader does not call `SetVirtualAddressMap`, so runtime services must be
+/// called through the identity map set up by the loader.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct UefiRuntime {
+    /// Physical address of the `EFI_SYSTEM_TABLE`
+    pub system_table: PhysicalAddress,
+    /// Physical address of the `EFI_RUNTIME_SERVICES` table
+    pub runtime_services: PhysicalAddress,
+}
+
+impl UefiRuntime {
+    /// Marks the current boot as successful by resetting the boot attempt counters, so that the loader neither switches
+    /// kernel slots nor starts the fallback application on the next boot.
+    ///
+    /// # Safety
+    /// Physical memory must still be identity mapped and runtime services must not be called concurrently.
+    pub unsafe fn mark_boot_successful(&self) -> Result<(), VariableError> {
+        self.set_variable(BOOT_ATTEMPTS_VARIABLE, &0u32.to_le_bytes())?;
+        self.set_variable(SLOT_ATTEMPTS_VARIABLE, &0u32.to_le_bytes())
+    }
+
+    /// Writes a core64 variable. Empty `data` deletes the variable.
+    ///
+    /// # Safety
+    /// Physical memory must still be identity mapped and runtime services must not be called concurrently.
+    pub unsafe fn set_variable(&self, name: &str, data: &[u8]) -> Result<(), VariableError> {
+        let name = encode_name(name)?;
+        let runtime_services = &*(self.runtime_services as *const RawRuntimeServices);
+        let status = (runtime_services.set_variable)(
+            name.as_ptr(),
+            &VARIABLE_VENDOR_GUID,
+            VARIABLE_ATTRIBUTES,
+            data.len(),
+            data.as_ptr(),
+        );
+        match status {
+            0 => Ok(()),
+            status => Err(VariableError::Firmware(status)),
+        }
+    }
+}
+
+unsafe impl Tag for UefiRuntime {
+    const TYPE: u32 = TAG_UEFI_RUNTIME;
+}
+
+/// Encodes `name` as null terminated UCS-2 string
+fn encode_name(name: &str) -> Result<[u16; VARIABLE_NAME_MAX_LEN + 1], VariableError> {
+    let mut buffer = [0; VARIABLE_NAME_MAX_LEN + 1];
+    let mut len = 0;
+    for unit in name.encode_utf16() {
+        if len == VARIABLE_NAME_MAX_LEN {
+            return Err(VariableError::InvalidName);
+        }
+        buffer[len] = unit;
+        len += 1;
+    }
+    if len == 0 {
+        return Err(VariableError::InvalidName);
+    }
+    Ok(buffer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_variable_names() {
+        let name = encode_name(BOOT_SLOT_VARIABLE).unwrap();
+        assert!(name
+            .iter()
+            .zip(BOOT_SLOT_VARIABLE.bytes())
+            .all(|(&unit, byte)| unit == byte as u16));
+        assert_eq!(name[BOOT_SLOT_VARIABLE.len()], 0);
+
+        assert_eq!(encode_name(""), Err(VariableError::InvalidName));
+        assert!(encode_name(&"a".repeat(VARIABLE_NAME_MAX_LEN)).is_ok());
+        assert_eq!(
+            encode_name(&"a".repeat(VARIABLE_NAME_MAX_LEN + 1)),
+            Err(VariableError::InvalidName)
+        );
+    }
+}
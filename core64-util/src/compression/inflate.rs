@@ -0,0 +1,311 @@
+use alloc::vec::Vec;
+
+use crate::compression::DecompressionError;
+
+/// Maximum bit length of a deflate huffman code
+const MAX_CODE_LENGTH: usize = 15;
+const LITERAL_LENGTH_CODES: usize = 288;
+const DISTANCE_CODES: usize = 30;
+const END_OF_BLOCK: u16 = 256;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+/// Order in which code length code lengths are stored in dynamic blocks
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+/// Decompresses a raw deflate stream (RFC 1951) and appends it to `output`. Returns the amount of consumed input bytes.
+pub(super) fn inflate(input: &[u8], output: &mut Vec<u8>) -> Result<usize, DecompressionError> {
+    let mut reader = BitReader::new(input);
+    let window_start = output.len();
+
+    loop {
+        let last = reader.bits(1)? == 1;
+        match reader.bits(2)? {
+            0 => stored_block(&mut reader, output)?,
+            1 => {
+                let (literal_length, distance) = fixed_codes()?;
+                compressed_block(
+                    &mut reader,
+                    output,
+                    window_start,
+                    &literal_length,
+                    &distance,
+                )?
+            }
+            2 => {
+                let (literal_length, distance) = dynamic_codes(&mut reader)?;
+                compressed_block(
+                    &mut reader,
+                    output,
+                    window_start,
+                    &literal_length,
+                    &distance,
+                )?
+            }
+            _ => return Err(DecompressionError::InvalidData),
+        }
+
+        if last {
+            reader.align_to_byte();
+            return Ok(reader.position);
+        }
+    }
+}
+
+fn stored_block(reader: &mut BitReader, output: &mut Vec<u8>) -> Result<(), DecompressionError> {
+    reader.align_to_byte();
+    let header = reader.bytes(4)?;
+    let len = u16::from_le_bytes([header[0], header[1]]);
+    let complement = u16::from_le_bytes([header[2], header[3]]);
+    if len != !complement {
+        return Err(DecompressionError::InvalidData);
+    }
+
+    output.extend_from_slice(reader.bytes(len as usize)?);
+    Ok(())
+}
+
+fn compressed_block(
+    reader: &mut BitReader,
+    output: &mut Vec<u8>,
+    window_start: usize,
+    literal_length: &Huffman,
+    distance: &Huffman,
+) -> Result<(), DecompressionError> {
+    loop {
+        let symbol = literal_length.decode(reader)?;
+        if symbol < END_OF_BLOCK {
+            output.push(symbol as u8);
+            continue;
+        }
+        if symbol == END_OF_BLOCK {
+            return Ok(());
+        }
+
+        let index = (symbol - END_OF_BLOCK - 1) as usize;
+        let (Some(base), Some(extra)) = (LENGTH_BASE.get(index), LENGTH_EXTRA.get(index)) else {
+            return Err(DecompressionError::InvalidData);
+        };
+        let length = *base as usize + reader.bits(*extra)? as usize;
+
+        let index = distance.decode(reader)? as usize;
+        let (Some(base), Some(extra)) = (DISTANCE_BASE.get(index), DISTANCE_EXTRA.get(index))
+        else {
+            return Err(DecompressionError::InvalidData);
+        };
+        let distance = *base as usize + reader.bits(*extra)? as usize;
+
+        copy_match(output, window_start, distance, length)?;
+    }
+}
+
+/// Appends `length` bytes starting `distance` bytes before the end of `output`. The copied range may overlap the appended bytes.
+pub(super) fn copy_match(
+    output: &mut Vec<u8>,
+    window_start: usize,
+    distance: usize,
+    length: usize,
+) -> Result<(), DecompressionError> {
+    if distance == 0 || distance > output.len() - window_start {
+        return Err(DecompressionError::InvalidData);
+    }
+
+    output
+        .try_reserve(length)
+        .map_err(|_| DecompressionError::OutOfMemory)?;
+    let start = output.len() - distance;
+    for index in start..start + length {
+        output.push(output[index]);
+    }
+    Ok(())
+}
+
+fn fixed_codes() -> Result<(Huffman, Huffman), DecompressionError> {
+    let mut lengths = [0; LITERAL_LENGTH_CODES];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+
+    Ok((Huffman::new(&lengths)?, Huffman::new(&[5; DISTANCE_CODES])?))
+}
+
+fn dynamic_codes(reader: &mut BitReader) -> Result<(Huffman, Huffman), DecompressionError> {
+    let literal_length_count = reader.bits(5)? as usize + 257;
+    let distance_count = reader.bits(5)? as usize + 1;
+    let code_length_count = reader.bits(4)? as usize + 4;
+    if literal_length_count > 286 || distance_count > DISTANCE_CODES {
+        return Err(DecompressionError::InvalidData);
+    }
+
+    let mut code_length_lengths = [0; CODE_LENGTH_ORDER.len()];
+    for index in CODE_LENGTH_ORDER.iter().take(code_length_count) {
+        code_length_lengths[*index] = reader.bits(3)? as u8;
+    }
+    let code_length = Huffman::new(&code_length_lengths)?;
+
+    let mut lengths = [0u8; LITERAL_LENGTH_CODES + DISTANCE_CODES];
+    let lengths = &mut lengths[..literal_length_count + distance_count];
+    let mut index = 0;
+    while index < lengths.len() {
+        let symbol = code_length.decode(reader)?;
+        let (length, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => {
+                let previous = *index
+                    .checked_sub(1)
+                    .and_then(|previous| lengths.get(previous))
+                    .ok_or(DecompressionError::InvalidData)?;
+                (previous, 3 + reader.bits(2)? as usize)
+            }
+            17 => (0, 3 + reader.bits(3)? as usize),
+            18 => (0, 11 + reader.bits(7)? as usize),
+            _ => return Err(DecompressionError::InvalidData),
+        };
+
+        lengths
+            .get_mut(index..index + repeat)
+            .ok_or(DecompressionError::InvalidData)?
+            .fill(length);
+        index += repeat;
+    }
+
+    // a block without end of block code could never be terminated
+    if lengths[END_OF_BLOCK as usize] == 0 {
+        return Err(DecompressionError::InvalidData);
+    }
+
+    let (literal_length, distance) = lengths.split_at(literal_length_count);
+    Ok((Huffman::new(literal_length)?, Huffman::new(distance)?))
+}
+
+/// Canonical huffman code, decoded bit by bit
+struct Huffman {
+    /// Amount of codes of each bit length
+    counts: [u16; MAX_CODE_LENGTH + 1],
+    /// Symbols ordered by code
+    symbols: [u16; LITERAL_LENGTH_CODES],
+}
+
+impl Huffman {
+    /// Builds the code from the code length of every symbol. Incomplete codes are allowed, over-subscribed ones are not.
+    fn new(lengths: &[u8]) -> Result<Self, DecompressionError> {
+        let mut counts = [0u16; MAX_CODE_LENGTH + 1];
+        for length in lengths {
+            counts[*length as usize] += 1;
+        }
+
+        let mut left = 1i32;
+        for count in &counts[1..] {
+            left = (left << 1) - *count as i32;
+            if left < 0 {
+                return Err(DecompressionError::InvalidData);
+            }
+        }
+
+        let mut offsets = [0u16; MAX_CODE_LENGTH + 1];
+        for length in 1..MAX_CODE_LENGTH {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+
+        let mut symbols = [0u16; LITERAL_LENGTH_CODES];
+        for (symbol, length) in lengths.iter().enumerate() {
+            if *length != 0 {
+                symbols[offsets[*length as usize] as usize] = symbol as u16;
+                offsets[*length as usize] += 1;
+            }
+        }
+
+        counts[0] = 0;
+        Ok(Self { counts, symbols })
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Result<u16, DecompressionError> {
+        let mut code = 0i32;
+        let mut first = 0i32;
+        let mut index = 0i32;
+
+        for count in &self.counts[1..] {
+            code |= reader.bits(1)? as i32;
+            let count = *count as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+
+        Err(DecompressionError::InvalidData)
+    }
+}
+
+/// Reads bits least significant bit first
+struct BitReader<'a> {
+    data: &'a [u8],
+    /// Index of the next byte to be loaded into the bit buffer
+    position: usize,
+    bit_buffer: u32,
+    bit_count: u8,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            position: 0,
+            bit_buffer: 0,
+            bit_count: 0,
+        }
+    }
+
+    /// Reads up to 16 bits
+    fn bits(&mut self, count: u8) -> Result<u32, DecompressionError> {
+        while self.bit_count < count {
+            let byte = *self
+                .data
+                .get(self.position)
+                .ok_or(DecompressionError::UnexpectedEnd)?;
+            self.bit_buffer |= (byte as u32) << self.bit_count;
+            self.bit_count += 8;
+            self.position += 1;
+        }
+
+        let value = self.bit_buffer & ((1 << count) - 1);
+        self.bit_buffer >>= count;
+        self.bit_count -= count;
+        Ok(value)
+    }
+
+    /// Discards buffered bits, so that reading continues at the next byte boundary
+    fn align_to_byte(&mut self) {
+        // bytes are only loaded on demand, so fewer than 8 bits are buffered
+        self.bit_buffer = 0;
+        self.bit_count = 0;
+    }
+
+    /// Reads whole bytes. The reader must be aligned to a byte boundary.
+    fn bytes(&mut self, count: usize) -> Result<&'a [u8], DecompressionError> {
+        let bytes = self
+            .data
+            .get(self.position..self.position + count)
+            .ok_or(DecompressionError::UnexpectedEnd)?;
+        self.position += count;
+        Ok(bytes)
+    }
+}
@@ -0,0 +1,273 @@
+use alloc::vec::Vec;
+
+use crate::compression::{inflate::copy_match, read_u32_le, DecompressionError};
+
+pub(super) const FRAME_MAGIC: u32 = 0x184D_2204;
+pub(super) const LEGACY_FRAME_MAGIC: u32 = 0x184C_2102;
+/// Skippable frames use the magic numbers 0x184D2A50 to 0x184D2A5F
+const SKIPPABLE_FRAME_MAGIC: u32 = 0x184D_2A50;
+const SKIPPABLE_FRAME_MAGIC_MASK: u32 = 0xFFFF_FFF0;
+
+const FLAG_VERSION_MASK: u8 = 0b1100_0000;
+const FLAG_VERSION: u8 = 0b0100_0000;
+const FLAG_BLOCK_CHECKSUM: u8 = 1 << 4;
+const FLAG_CONTENT_SIZE: u8 = 1 << 3;
+const FLAG_CONTENT_CHECKSUM: u8 = 1 << 2;
+const FLAG_DICTIONARY_ID: u8 = 1 << 0;
+/// Uncompressed blocks have the highest bit of their size set
+const BLOCK_UNCOMPRESSED: u32 = 1 << 31;
+/// Shortest possible match
+const MIN_MATCH: usize = 4;
+/// Upper bound of the decompressed size per compressed byte, as every length extension byte adds at most 255 bytes
+const MAX_EXPANSION: usize = 255;
+
+const XXH32_PRIME_1: u32 = 0x9E37_79B1;
+const XXH32_PRIME_2: u32 = 0x85EB_CA77;
+const XXH32_PRIME_3: u32 = 0xC2B2_AE3D;
+const XXH32_PRIME_4: u32 = 0x27D4_EB2F;
+const XXH32_PRIME_5: u32 = 0x1656_67B1;
+
+/// Decompresses concatenated LZ4 frames (including legacy frames) and appends them to `output`.
+/// Header, block and content checksums of frames are verified, legacy frames do not have any.
+pub(super) fn decompress(mut input: &[u8], output: &mut Vec<u8>) -> Result<(), DecompressionError> {
+    while !input.is_empty() {
+        let magic = read_u32_le(input, 0)?;
+        let consumed = if magic == FRAME_MAGIC {
+            frame(&input[4..], output)?
+        } else if magic == LEGACY_FRAME_MAGIC {
+            legacy_frame(&input[4..], output)?
+        } else if magic & SKIPPABLE_FRAME_MAGIC_MASK == SKIPPABLE_FRAME_MAGIC {
+            4 + read_u32_le(input, 4)? as usize
+        } else {
+            return Err(DecompressionError::InvalidHeader);
+        };
+
+        input = input
+            .get(4 + consumed..)
+            .ok_or(DecompressionError::UnexpectedEnd)?;
+    }
+
+    Ok(())
+}
+
+/// Decompresses a frame following its magic number. Returns the amount of consumed input bytes.
+fn frame(input: &[u8], output: &mut Vec<u8>) -> Result<usize, DecompressionError> {
+    let (flags, _block_descriptor) = match input {
+        [flags, block_descriptor, ..] => (*flags, *block_descriptor),
+        _ => return Err(DecompressionError::UnexpectedEnd),
+    };
+    if flags & FLAG_VERSION_MASK != FLAG_VERSION {
+        return Err(DecompressionError::InvalidHeader);
+    }
+    if flags & FLAG_DICTIONARY_ID != 0 {
+        return Err(DecompressionError::Unsupported);
+    }
+
+    let mut position = 2;
+    let content_size = if flags & FLAG_CONTENT_SIZE != 0 {
+        let bytes = input
+            .get(position..position + 8)
+            .ok_or(DecompressionError::UnexpectedEnd)?;
+        position += 8;
+        Some(u64::from_le_bytes(bytes.try_into().unwrap()))
+    } else {
+        None
+    };
+    // second byte of the hash of the frame descriptor
+    let header_checksum = *input
+        .get(position)
+        .ok_or(DecompressionError::UnexpectedEnd)?;
+    if header_checksum != (xxh32(&input[..position], 0) >> 8) as u8 {
+        return Err(DecompressionError::ChecksumMismatch);
+    }
+    position += 1;
+
+    let window_start = output.len();
+    if let Some(content_size) = content_size {
+        // the content size is untrusted, but cannot exceed the expansion of the input
+        let size = usize::try_from(content_size)
+            .unwrap_or(usize::MAX)
+            .min(input.len().saturating_mul(MAX_EXPANSION));
+        output
+            .try_reserve(size)
+            .map_err(|_| DecompressionError::OutOfMemory)?;
+    }
+
+    loop {
+        let block_size = read_u32_le(input, position)?;
+        position += 4;
+        if block_size == 0 {
+            break;
+        }
+
+        let size = (block_size & !BLOCK_UNCOMPRESSED) as usize;
+        let block = input
+            .get(position..position + size)
+            .ok_or(DecompressionError::UnexpectedEnd)?;
+        if block_size & BLOCK_UNCOMPRESSED != 0 {
+            output.extend_from_slice(block);
+        } else {
+            decompress_block(block, output, window_start)?;
+        }
+
+        position += size;
+        if flags & FLAG_BLOCK_CHECKSUM != 0 {
+            // hash of the stored block data
+            if read_u32_le(input, position)? != xxh32(block, 0) {
+                return Err(DecompressionError::ChecksumMismatch);
+            }
+            position += 4;
+        }
+    }
+
+    if flags & FLAG_CONTENT_CHECKSUM != 0 {
+        if read_u32_le(input, position)? != xxh32(&output[window_start..], 0) {
+            return Err(DecompressionError::ChecksumMismatch);
+        }
+        position += 4;
+    }
+    if content_size.is_some_and(|size| size != (output.len() - window_start) as u64) {
+        return Err(DecompressionError::SizeMismatch);
+    }
+
+    Ok(position)
+}
+
+/// Decompresses a legacy frame (`lz4 -l`, used by Linux) following its magic number. Returns the amount of consumed input bytes.
+fn legacy_frame(input: &[u8], output: &mut Vec<u8>) -> Result<usize, DecompressionError> {
+    let mut position = 0;
+
+    // legacy frames end with the input or at the next magic number
+    while position < input.len() {
+        let block_size = read_u32_le(input, position)?;
+        if block_size == LEGACY_FRAME_MAGIC {
+            break;
+        }
+        position += 4;
+
+        let block = input
+            .get(position..position + block_size as usize)
+            .ok_or(DecompressionError::UnexpectedEnd)?;
+        // blocks are independent
+        let window_start = output.len();
+        decompress_block(block, output, window_start)?;
+        position += block.len();
+    }
+
+    Ok(position)
+}
+
+/// Decompresses a single LZ4 block. Matches may reach back to `window_start` in `output`.
+fn decompress_block(
+    block: &[u8],
+    output: &mut Vec<u8>,
+    window_start: usize,
+) -> Result<(), DecompressionError> {
+    let mut position = 0;
+
+    while position < block.len() {
+        let token = block[position];
+        position += 1;
+
+        let literals = extended_length(block, &mut position, (token >> 4) as usize)?;
+        output.extend_from_slice(
+            block
+                .get(position..position + literals)
+                .ok_or(DecompressionError::UnexpectedEnd)?,
+        );
+        position += literals;
+
+        // the last sequence only consists of literals
+        if position == block.len() {
+            break;
+        }
+
+        let offset = block
+            .get(position..position + 2)
+            .ok_or(DecompressionError::UnexpectedEnd)?;
+        let offset = u16::from_le_bytes([offset[0], offset[1]]) as usize;
+        position += 2;
+
+        let length = extended_length(block, &mut position, (token & 0xF) as usize)? + MIN_MATCH;
+        copy_match(output, window_start, offset, length)?;
+    }
+
+    Ok(())
+}
+
+/// Adds the extension bytes following a 4 bit length of 15
+fn extended_length(
+    block: &[u8],
+    position: &mut usize,
+    mut length: usize,
+) -> Result<usize, DecompressionError> {
+    if length != 0xF {
+        return Ok(length);
+    }
+
+    loop {
+        let byte = *block
+            .get(*position)
+            .ok_or(DecompressionError::UnexpectedEnd)?;
+        *position += 1;
+        length += byte as usize;
+        if byte != 0xFF {
+            return Ok(length);
+        }
+    }
+}
+
+/// xxHash32 as used by the LZ4 frame format
+fn xxh32(data: &[u8], seed: u32) -> u32 {
+    fn round(accumulator: u32, lane: u32) -> u32 {
+        accumulator
+            .wrapping_add(lane.wrapping_mul(XXH32_PRIME_2))
+            .rotate_left(13)
+            .wrapping_mul(XXH32_PRIME_1)
+    }
+    let lane = |bytes: &[u8]| u32::from_le_bytes(bytes[..4].try_into().unwrap());
+
+    let mut stripes = data.chunks_exact(16);
+    let mut hash = if data.len() >= 16 {
+        let mut accumulators = [
+            seed.wrapping_add(XXH32_PRIME_1).wrapping_add(XXH32_PRIME_2),
+            seed.wrapping_add(XXH32_PRIME_2),
+            seed,
+            seed.wrapping_sub(XXH32_PRIME_1),
+        ];
+        for stripe in &mut stripes {
+            for (index, accumulator) in accumulators.iter_mut().enumerate() {
+                *accumulator = round(*accumulator, lane(&stripe[index * 4..]));
+            }
+        }
+        accumulators[0]
+            .rotate_left(1)
+            .wrapping_add(accumulators[1].rotate_left(7))
+            .wrapping_add(accumulators[2].rotate_left(12))
+            .wrapping_add(accumulators[3].rotate_left(18))
+    } else {
+        seed.wrapping_add(XXH32_PRIME_5)
+    };
+    // the length is added modulo 2^32
+    hash = hash.wrapping_add(data.len() as u32);
+
+    let mut lanes = stripes.remainder().chunks_exact(4);
+    for bytes in &mut lanes {
+        hash = hash
+            .wrapping_add(lane(bytes).wrapping_mul(XXH32_PRIME_3))
+            .rotate_left(17)
+            .wrapping_mul(XXH32_PRIME_4);
+    }
+    for byte in lanes.remainder() {
+        hash = hash
+            .wrapping_add((*byte as u32).wrapping_mul(XXH32_PRIME_5))
+            .rotate_left(11)
+            .wrapping_mul(XXH32_PRIME_1);
+    }
+
+    hash ^= hash >> 15;
+    hash = hash.wrapping_mul(XXH32_PRIME_2);
+    hash ^= hash >> 13;
+    hash = hash.wrapping_mul(XXH32_PRIME_3);
+    hash ^ (hash >> 16)
+}
@@ -0,0 +1,340 @@
+use alloc::vec::Vec;
+use core::{
+    error::Error,
+    fmt::{Display, Formatter},
+};
+
+mod inflate;
+mod lz4;
+
+const GZIP_MAGIC: [u8; 2] = [0x1F, 0x8B];
+const GZIP_METHOD_DEFLATE: u8 = 8;
+const GZIP_HEADER_SIZE: usize = 10;
+const GZIP_FLAG_HEADER_CRC: u8 = 1 << 1;
+const GZIP_FLAG_EXTRA: u8 = 1 << 2;
+const GZIP_FLAG_NAME: u8 = 1 << 3;
+const GZIP_FLAG_COMMENT: u8 = 1 << 4;
+
+const ZLIB_METHOD_DEFLATE: u8 = 8;
+const ZLIB_FLAG_DICTIONARY: u8 = 1 << 5;
+
+const CRC32_TABLE: [u32; 256] = crc32_table();
+
+/// Compression formats supported for kernel and module images
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Compression {
+    /// gzip member(s) (RFC 1952), e.g. `gzip kernel.elf`
+    Gzip,
+    /// zlib stream (RFC 1950)
+    Zlib,
+    /// LZ4 frame format, including legacy frames, e.g. `lz4 kernel.elf`
+    Lz4,
+}
+
+impl Compression {
+    /// Detects the compression format by its magic number. Returns `None` for uncompressed data.
+    pub fn detect(data: &[u8]) -> Option<Self> {
+        match data {
+            [0x1F, 0x8B, ..] => Some(Self::Gzip),
+            [cmf, flg, ..]
+                if cmf & 0xF == ZLIB_METHOD_DEFLATE
+                    && cmf >> 4 <= 7
+                    && u16::from_be_bytes([*cmf, *flg]) % 31 == 0 =>
+            {
+                Some(Self::Zlib)
+            }
+            _ => match read_u32_le(data, 0) {
+                Ok(lz4::FRAME_MAGIC | lz4::LEGACY_FRAME_MAGIC) => Some(Self::Lz4),
+                _ => None,
+            },
+        }
+    }
+
+    /// Decompresses `data`, which must be entirely in this format
+    pub fn decompress(self, data: &[u8]) -> Result<Vec<u8>, DecompressionError> {
+        let mut output = Vec::new();
+        match self {
+            Self::Gzip => gunzip(data, &mut output)?,
+            Self::Zlib => zlib(data, &mut output)?,
+            Self::Lz4 => lz4::decompress(data, &mut output)?,
+        }
+        Ok(output)
+    }
+}
+
+/// Decompresses concatenated gzip members
+fn gunzip(mut input: &[u8], output: &mut Vec<u8>) -> Result<(), DecompressionError> {
+    while !input.is_empty() {
+        let header = input
+            .get(..GZIP_HEADER_SIZE)
+            .ok_or(DecompressionError::UnexpectedEnd)?;
+        if header[..2] != GZIP_MAGIC || header[2] != GZIP_METHOD_DEFLATE {
+            return Err(DecompressionError::InvalidHeader);
+        }
+        let flags = header[3];
+
+        let mut position = GZIP_HEADER_SIZE;
+        if flags & GZIP_FLAG_EXTRA != 0 {
+            let extra = input
+                .get(position..position + 2)
+                .ok_or(DecompressionError::UnexpectedEnd)?;
+            position += 2 + u16::from_le_bytes([extra[0], extra[1]]) as usize;
+        }
+        for flag in [GZIP_FLAG_NAME, GZIP_FLAG_COMMENT] {
+            if flags & flag != 0 {
+                // zero terminated string
+                let len = input
+                    .get(position..)
+                    .and_then(|rest| rest.iter().position(|byte| *byte == 0))
+                    .ok_or(DecompressionError::UnexpectedEnd)?;
+                position += len + 1;
+            }
+        }
+        if flags & GZIP_FLAG_HEADER_CRC != 0 {
+            position += 2;
+        }
+
+        let start = output.len();
+        let data = input
+            .get(position..)
+            .ok_or(DecompressionError::UnexpectedEnd)?;
+        position += inflate::inflate(data, output)?;
+
+        let crc = read_u32_le(input, position)?;
+        let size = read_u32_le(input, position + 4)?;
+        if crc != crc32(&output[start..]) {
+            return Err(DecompressionError::ChecksumMismatch);
+        }
+        // the size is stored modulo 2^32
+        if size != (output.len() - start) as u32 {
+            return Err(DecompressionError::SizeMismatch);
+        }
+
+        input = &input[position + 8..];
+    }
+
+    Ok(())
+}
+
+fn zlib(input: &[u8], output: &mut Vec<u8>) -> Result<(), DecompressionError> {
+    let [_, flags, ..] = *input else {
+        return Err(DecompressionError::UnexpectedEnd);
+    };
+    if flags & ZLIB_FLAG_DICTIONARY != 0 {
+        return Err(DecompressionError::Unsupported);
+    }
+
+    let position = 2 + inflate::inflate(&input[2..], output)?;
+    let checksum = input
+        .get(position..position + 4)
+        .ok_or(DecompressionError::UnexpectedEnd)?;
+    if u32::from_be_bytes(checksum.try_into().unwrap()) != adler32(output) {
+        return Err(DecompressionError::ChecksumMismatch);
+    }
+
+    Ok(())
+}
+
+/// CRC-32 (ISO-HDLC) as used by gzip
+pub fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, byte| {
+        CRC32_TABLE[((crc ^ *byte as u32) & 0xFF) as usize] ^ (crc >> 8)
+    })
+}
+
+const fn crc32_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut index = 0;
+    while index < table.len() {
+        let mut crc = index as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                0xEDB8_8320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[index] = crc;
+        index += 1;
+    }
+    table
+}
+
+/// Adler-32 as used by zlib
+fn adler32(data: &[u8]) -> u32 {
+    const MODULUS: u32 = 65521;
+    // largest amount of bytes, which can be summed up without overflowing
+    const CHUNK_SIZE: usize = 5552;
+
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(CHUNK_SIZE) {
+        for byte in chunk {
+            a += *byte as u32;
+            b += a;
+        }
+        a %= MODULUS;
+        b %= MODULUS;
+    }
+    (b << 16) | a
+}
+
+fn read_u32_le(data: &[u8], offset: usize) -> Result<u32, DecompressionError> {
+    data.get(offset..offset + 4)
+        .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+        .ok_or(DecompressionError::UnexpectedEnd)
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DecompressionError {
+    /// The input ends in the middle of the compressed data
+    UnexpectedEnd,
+    /// Magic number or header of the format are invalid
+    InvalidHeader,
+    /// The compressed data is malformed
+    InvalidData,
+    /// The checksum of the decompressed data does not match
+    ChecksumMismatch,
+    /// The size of the decompressed data does not match the size stored in the input
+    SizeMismatch,
+    /// The input uses a feature that is not supported (e.g. preset dictionaries)
+    Unsupported,
+    /// The decompressed data does not fit into memory
+    OutOfMemory,
+}
+
+impl Display for DecompressionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl Error for DecompressionError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `gzip` of 60 bytes, using a dynamic huffman block
+    const GZIP_DYNAMIC: [u8; 52] = [
+        0x1F, 0x8B, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0x25, 0x8A, 0xC1, 0x11, 0x00,
+        0x00, 0x0C, 0xC1, 0x66, 0x45, 0xF7, 0x9F, 0xA1, 0xD2, 0x7A, 0x90, 0xCB, 0x91, 0x93, 0xA8,
+        0x69, 0x59, 0x47, 0x03, 0x56, 0x9A, 0x69, 0x0F, 0xD2, 0x2C, 0xE6, 0x5F, 0x46, 0x2D, 0xEC,
+        0x50, 0x0D, 0x84, 0x3C, 0x00, 0x00, 0x00,
+    ];
+    const GZIP_DYNAMIC_DATA: &[u8] =
+        b"abcccaaaacaabacaaaadcaabccabaabcabadaaaabbadabaababacaabaaab";
+
+    #[test]
+    fn decompresses_gzip_with_dynamic_codes() {
+        assert_eq!(Compression::detect(&GZIP_DYNAMIC), Some(Compression::Gzip));
+        assert_eq!(
+            Compression::Gzip.decompress(&GZIP_DYNAMIC).unwrap(),
+            GZIP_DYNAMIC_DATA
+        );
+    }
+
+    #[test]
+    fn decompresses_gzip_with_fixed_codes_and_stored_blocks() {
+        // `abcabcabcabc` with fixed codes, followed by a member with a stored block
+        let members = [
+            0x1F, 0x8B, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0x4B, 0x4C, 0x4A, 0x4E,
+            0x84, 0x21, 0x00, 0x34, 0x2A, 0x6E, 0x5A, 0x0C, 0x00, 0x00, 0x00, 0x1F, 0x8B, 0x08,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x04, 0x03, 0x01, 0x0C, 0x00, 0xF3, 0xFF, 0x73, 0x74,
+            0x6F, 0x72, 0x65, 0x64, 0x20, 0x62, 0x6C, 0x6F, 0x63, 0x6B, 0x94, 0xA3, 0x24, 0x3D,
+            0x0C, 0x00, 0x00, 0x00,
+        ];
+        assert_eq!(
+            Compression::Gzip.decompress(&members).unwrap(),
+            b"abcabcabcabcstored block"
+        );
+    }
+
+    #[test]
+    fn rejects_corrupted_gzip() {
+        let mut corrupted = GZIP_DYNAMIC;
+        corrupted[GZIP_DYNAMIC.len() - 8] ^= 1;
+        assert_eq!(
+            Compression::Gzip.decompress(&corrupted),
+            Err(DecompressionError::ChecksumMismatch)
+        );
+        assert_eq!(
+            Compression::Gzip.decompress(&GZIP_DYNAMIC[..30]),
+            Err(DecompressionError::UnexpectedEnd)
+        );
+    }
+
+    #[test]
+    fn decompresses_zlib() {
+        let stream = [
+            0x78, 0xDA, 0xCB, 0x48, 0xCD, 0xC9, 0xC9, 0x57, 0xC8, 0x40, 0x27, 0x01, 0x68, 0x03,
+            0x08, 0xB1,
+        ];
+        assert_eq!(Compression::detect(&stream), Some(Compression::Zlib));
+        assert_eq!(
+            Compression::Zlib.decompress(&stream).unwrap(),
+            b"hello hello hello hello"
+        );
+    }
+
+    #[test]
+    fn decompresses_lz4_frame() {
+        let frame = [
+            0x04, 0x22, 0x4D, 0x18, // magic
+            0x60, 0x40, 0x82, // flags, block descriptor, header checksum
+            0x0C, 0x00, 0x00, 0x00, // block size
+            0x35, b'a', b'b', b'c', 0x03, 0x00, // 3 literals, match of 9 bytes at offset 3
+            0x50, b'x', b'y', b'z', b'1', b'2', // 5 literals
+            0x00, 0x00, 0x00, 0x00, // end mark
+        ];
+        assert_eq!(Compression::detect(&frame), Some(Compression::Lz4));
+        assert_eq!(
+            Compression::Lz4.decompress(&frame).unwrap(),
+            b"abcabcabcabcxyz12"
+        );
+    }
+
+    #[test]
+    fn verifies_lz4_checksums() {
+        // `lz4 -BX --content-size` of the same data, with content size, block and content checksums
+        let frame = [
+            0x04, 0x22, 0x4D, 0x18, 0x7C, 0x40, 0x11, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0xC5, 0x0C, 0x00, 0x00, 0x00, 0x35, 0x61, 0x62, 0x63, 0x03, 0x00, 0x50, 0x78, 0x79,
+            0x7A, 0x31, 0x32, 0x1D, 0xBE, 0x2E, 0x03, 0x00, 0x00, 0x00, 0x00, 0x36, 0x41, 0x44,
+            0xD2,
+        ];
+        assert_eq!(
+            Compression::Lz4.decompress(&frame).unwrap(),
+            b"abcabcabcabcxyz12"
+        );
+
+        // header, block data and content checksum
+        for index in [14, 20, 39] {
+            let mut corrupted = frame;
+            corrupted[index] ^= 1;
+            assert_eq!(
+                Compression::Lz4.decompress(&corrupted),
+                Err(DecompressionError::ChecksumMismatch)
+            );
+        }
+    }
+
+    #[test]
+    fn does_not_trust_lz4_content_size() {
+        // content size of 2^64 - 1 for an empty frame
+        let frame = [
+            0x04, 0x22, 0x4D, 0x18, 0x68, 0x40, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+            0xA7, 0x00, 0x00, 0x00, 0x00,
+        ];
+        assert_eq!(
+            Compression::Lz4.decompress(&frame),
+            Err(DecompressionError::SizeMismatch)
+        );
+    }
+
+    #[test]
+    fn does_not_detect_elf_as_compressed() {
+        assert_eq!(Compression::detect(b"\x7FELF\x02\x01\x01\x00"), None);
+    }
+}
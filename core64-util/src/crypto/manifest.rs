@@ -0,0 +1,62 @@
+use crate::crypto::{parse_hex, SHA256_DIGEST_SIZE};
+
+/// Looks up the digest of `path` in a manifest of `sha256sum` format (`<hex digest>  <path>` per line). Entries are matched by
+/// their full path relative to the root of the volume or TFTP server, ignoring case, leading separators and the kind of
+/// separator. Empty lines and lines starting with `#` are ignored.
+pub fn manifest_digest(manifest: &str, path: &str) -> Option<[u8; SHA256_DIGEST_SIZE]> {
+    manifest
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.split_once(char::is_whitespace))
+        .find(|(_, entry_path)| {
+            // `sha256sum` marks files hashed in binary mode with `*`
+            let entry_path = entry_path.trim_start();
+            let entry_path = entry_path.strip_prefix('*').unwrap_or(entry_path);
+            normalized(entry_path).eq(normalized(path))
+        })
+        .and_then(|(digest, _)| parse_hex(digest))
+}
+
+/// Characters of `path` relative to the root, with lowercase ASCII letters and backslash separators
+fn normalized(path: &str) -> impl Iterator<Item = char> + '_ {
+    path.trim_start_matches(['\\', '/'])
+        .chars()
+        .map(|char| match char {
+            '/' => '\\',
+            char => char.to_ascii_lowercase(),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::manifest_digest;
+    use crate::crypto::parse_hex;
+
+    const DIGEST_A: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+    const DIGEST_B: &str = "4e4c294b331f7a2099a379bec34b9f9fc03dc46ab465d998f4d683da53487e6d";
+
+    #[test]
+    fn matches_full_paths() {
+        let manifest = alloc::format!(
+            "# kernel slots\n{DIGEST_A}  \\a\\kernel.elf\n\n{DIGEST_B} *b/KERNEL.ELF\n{DIGEST_A}  boot/initrd\n"
+        );
+
+        assert_eq!(
+            manifest_digest(&manifest, "\\a\\kernel.elf"),
+            parse_hex(DIGEST_A)
+        );
+        assert_eq!(
+            manifest_digest(&manifest, "\\B\\kernel.elf"),
+            parse_hex(DIGEST_B)
+        );
+        assert_eq!(
+            manifest_digest(&manifest, "/boot/initrd"),
+            parse_hex(DIGEST_A)
+        );
+        // files with the same name in other directories are not listed
+        assert_eq!(manifest_digest(&manifest, "\\kernel.elf"), None);
+        assert_eq!(manifest_digest(&manifest, "\\c\\kernel.elf"), None);
+        assert_eq!(manifest_digest(&manifest, "\\boot\\a\\kernel.elf"), None);
+    }
+}
@@ -0,0 +1,142 @@
+use ed25519_dalek::{Signature, VerifyingKey};
+use sha2::{Digest, Sha256};
+
+pub mod manifest;
+
+/// Size of a SHA-256 digest in bytes
+pub const SHA256_DIGEST_SIZE: usize = 32;
+/// Size of an Ed25519 public key in bytes
+pub const ED25519_PUBLIC_KEY_SIZE: usize = 32;
+/// Size of an Ed25519 signature in bytes
+pub const ED25519_SIGNATURE_SIZE: usize = 64;
+
+/// SHA-256 digest of `data`
+pub fn sha256(data: &[u8]) -> [u8; SHA256_DIGEST_SIZE] {
+    Sha256::digest(data).into()
+}
+
+/// Verifies the Ed25519 `signature` of `message`. Uses strict verification, which rejects non-canonical signatures as well
+/// as small order public keys and signature points.
+pub fn verify_ed25519(
+    public_key: &[u8; ED25519_PUBLIC_KEY_SIZE],
+    message: &[u8],
+    signature: &[u8; ED25519_SIGNATURE_SIZE],
+) -> bool {
+    VerifyingKey::from_bytes(public_key).is_ok_and(|public_key| {
+        public_key
+            .verify_strict(message, &Signature::from_bytes(signature))
+            .is_ok()
+    })
+}
+
+/// Parses exactly `N` bytes from hexadecimal digits (case insensitive)
+pub fn parse_hex<const N: usize>(hex: &str) -> Option<[u8; N]> {
+    if hex.len() != 2 * N {
+        return None;
+    }
+
+    let digit = |digit: u8| (digit as char).to_digit(16).map(|value| value as u8);
+    let mut bytes = [0; N];
+    for (byte, digits) in bytes.iter_mut().zip(hex.as_bytes().chunks_exact(2)) {
+        *byte = (digit(digits[0])? << 4) | digit(digits[1])?;
+    }
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_hex, sha256, verify_ed25519};
+
+    fn message<const N: usize>() -> [u8; N] {
+        core::array::from_fn(|index| (index % 251) as u8)
+    }
+
+    #[test]
+    fn computes_sha256() {
+        assert_eq!(
+            sha256(b"abc"),
+            parse_hex("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad").unwrap()
+        );
+        assert_eq!(
+            sha256(&message::<1000>()),
+            parse_hex("4e4c294b331f7a2099a379bec34b9f9fc03dc46ab465d998f4d683da53487e6d").unwrap()
+        );
+    }
+
+    #[test]
+    fn verifies_ed25519_signatures() {
+        // RFC 8032 test vectors 1 to 3
+        let public_key =
+            parse_hex("d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a").unwrap();
+        let signature = parse_hex(
+            "e5564300c360ac729086e2cc806e828a84877f1eb8e5d974d873e06522490155\
+             5fb8821590a33bacc61e39701cf9b46bd25bf5f0595bbe24655141438e7a100b",
+        )
+        .unwrap();
+        assert!(verify_ed25519(&public_key, b"", &signature));
+        assert!(!verify_ed25519(&public_key, b"\x00", &signature));
+
+        let public_key =
+            parse_hex("3d4017c3e843895a92b70aa74d1b7ebc9c982ccf2ec4968cc0cd55f12af4660c").unwrap();
+        let mut signature = parse_hex(
+            "92a009a9f0d4cab8720e820b5f642540a2b27b5416503f8fb3762223ebdb69da\
+             085ac1e43e15996e458f3613d0f11d8c387b2eaeb4302aeeb00d291612bb0c00",
+        )
+        .unwrap();
+        assert!(verify_ed25519(&public_key, b"\x72", &signature));
+        signature[40] ^= 1;
+        assert!(!verify_ed25519(&public_key, b"\x72", &signature));
+
+        let public_key =
+            parse_hex("fc51cd8e6218a1a38da47ed00230f0580816ed13ba3303ac5deb911548908025").unwrap();
+        let signature = parse_hex(
+            "6291d657deec24024827e69c3abe01a30ce548a284743a445e3680d7db5ac3ac\
+             18ff9b538d16f290ae67f760984dc6594a7c15e9716ed28dc027beceea1ec40a",
+        )
+        .unwrap();
+        assert!(verify_ed25519(&public_key, b"\xaf\x82", &signature));
+
+        // multi-block message signed with the key of test vector 3
+        let signature = parse_hex(
+            "90a199a965bdb74b69ed5ced73eb230d093176a1b1fce0dd979bcb291ce0dd28\
+             3c44e8fc0aaab62672c2d507704b66616c86dbd89c38e61de1f4efc5e0357b0e",
+        )
+        .unwrap();
+        assert!(verify_ed25519(&public_key, &message::<1023>(), &signature));
+        assert!(!verify_ed25519(&public_key, &message::<1022>(), &signature));
+    }
+
+    #[test]
+    fn rejects_malleable_ed25519_signatures() {
+        // RFC 8032 test vector 1 with S + L instead of S
+        let public_key =
+            parse_hex("d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a").unwrap();
+        let signature = parse_hex(
+            "e5564300c360ac729086e2cc806e828a84877f1eb8e5d974d873e06522490155\
+             4c8c7872aa064e049dbb3013fbf29380d25bf5f0595bbe24655141438e7a101b",
+        )
+        .unwrap();
+        assert!(!verify_ed25519(&public_key, b"", &signature));
+
+        // identity public key and R with S = 0 satisfy the verification equation for every message
+        let public_key =
+            parse_hex("0100000000000000000000000000000000000000000000000000000000000000").unwrap();
+        let mut signature = [0; 64];
+        signature[0] = 1;
+        assert!(!verify_ed25519(&public_key, b"", &signature));
+        assert!(!verify_ed25519(&public_key, b"\x72", &signature));
+
+        // public key of order 8
+        let public_key =
+            parse_hex("c7176a703d4dd84fba3c0b760d10670f2a2053fa2c39ccc64ec7fd7792ac037a").unwrap();
+        assert!(!verify_ed25519(&public_key, b"", &signature));
+    }
+
+    #[test]
+    fn rejects_invalid_hex() {
+        assert_eq!(parse_hex::<2>("0aFf"), Some([0x0A, 0xFF]));
+        assert_eq!(parse_hex::<2>("0aF"), None);
+        assert_eq!(parse_hex::<2>("0aFg"), None);
+        assert_eq!(parse_hex::<1>("+1"), None);
+    }
+}
@@ -3,10 +3,15 @@ use core::{
     fmt::{Debug, Formatter},
 };
 
+use crate::graphics::Color;
+
 pub const BPP: usize = 4; // bytes per pixel = pixel_stride
 
+#[repr(C)]
 #[derive(Copy, Clone)]
 pub struct FrameBufferMetadata {
+    /// Base address. In boot info, the write combining mapping at
+    /// [`FRAMEBUFFER_MAPPING_ADDRESS`](crate::memory::paging::FRAMEBUFFER_MAPPING_ADDRESS).
     pub base: u64,
     pub size: usize,
     pub width: usize,
@@ -23,3 +28,22 @@ impl Debug for FrameBufferMetadata {
         ))
     }
 }
+
+impl FrameBufferMetadata {
+    /// Writes a pixel. Coordinates outside the framebuffer are ignored.
+    ///
+    /// # Safety
+    /// The framebuffer must be mapped at its base address.
+    pub unsafe fn write_pixel(&self, x: usize, y: usize, color: Color) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+        let value = if self.is_rgb {
+            u32::from_le_bytes([color.red, color.green, color.blue, 0])
+        } else {
+            u32::from_le_bytes([color.blue, color.green, color.red, 0])
+        };
+        let pixel = (self.base as *mut u8).add((y * self.stride + x) * BPP) as *mut u32;
+        pixel.write_volatile(value);
+    }
+}
@@ -0,0 +1,241 @@
+use crate::graphics::{
+    image::{blend, ImageError},
+    Color,
+};
+
+const FILE_HEADER_SIZE: usize = 14;
+/// Size of `BITMAPINFOHEADER`, the oldest supported info header. Later versions extend it.
+const INFO_HEADER_SIZE: usize = 40;
+const COMPRESSION_RGB: u32 = 0;
+const COMPRESSION_BITFIELDS: u32 = 3;
+const COMPRESSION_ALPHA_BITFIELDS: u32 = 6;
+/// Size of a palette entry (blue, green, red, reserved)
+const PALETTE_ENTRY_SIZE: usize = 4;
+
+/// File and info header of a bitmap
+#[derive(Copy, Clone, Debug)]
+pub(super) struct Header {
+    pixel_offset: usize,
+    info_size: usize,
+    width: usize,
+    height: usize,
+    /// Rows are stored bottom up, unless the height is negative
+    bottom_up: bool,
+    bits_per_pixel: u16,
+    /// Red, green, blue and alpha masks of 32 bit pixels
+    masks: [u32; 4],
+    palette_size: usize,
+}
+
+impl Header {
+    pub(super) fn parse(data: &[u8]) -> Result<Self, ImageError> {
+        if !data.starts_with(b"BM") {
+            return Err(ImageError::InvalidHeader);
+        }
+        let pixel_offset = read_u32(data, 10)? as usize;
+        let info_size = read_u32(data, FILE_HEADER_SIZE)? as usize;
+        if info_size < INFO_HEADER_SIZE {
+            return Err(ImageError::Unsupported);
+        }
+        let width = read_u32(data, FILE_HEADER_SIZE + 4)? as i32;
+        let height = read_u32(data, FILE_HEADER_SIZE + 8)? as i32;
+        let bits_per_pixel = read_u16(data, FILE_HEADER_SIZE + 14)?;
+        let compression = read_u32(data, FILE_HEADER_SIZE + 16)?;
+        let colors_used = read_u32(data, FILE_HEADER_SIZE + 32)? as usize;
+        if width <= 0 || height == 0 {
+            return Err(ImageError::InvalidHeader);
+        }
+
+        let masks = match (compression, bits_per_pixel) {
+            (COMPRESSION_RGB, 8 | 24) => [0; 4],
+            // the fourth byte is unused
+            (COMPRESSION_RGB, 32) => [0xFF_0000, 0xFF00, 0xFF, 0],
+            (COMPRESSION_BITFIELDS | COMPRESSION_ALPHA_BITFIELDS, 32) => {
+                // masks directly follow BITMAPINFOHEADER, either as part of a later info header or as separate fields
+                let offset = FILE_HEADER_SIZE + INFO_HEADER_SIZE;
+                let has_alpha =
+                    compression == COMPRESSION_ALPHA_BITFIELDS || info_size > INFO_HEADER_SIZE + 12;
+                let alpha = if has_alpha {
+                    read_u32(data, offset + 12)?
+                } else {
+                    0
+                };
+                [
+                    read_u32(data, offset)?,
+                    read_u32(data, offset + 4)?,
+                    read_u32(data, offset + 8)?,
+                    alpha,
+                ]
+            }
+            _ => return Err(ImageError::Unsupported),
+        };
+        let palette_size = match bits_per_pixel {
+            8 if colors_used == 0 => 256,
+            8 => colors_used.min(256),
+            _ => 0,
+        };
+
+        Ok(Self {
+            pixel_offset,
+            info_size,
+            width: width as usize,
+            height: height.unsigned_abs() as usize,
+            bottom_up: height > 0,
+            bits_per_pixel,
+            masks,
+            palette_size,
+        })
+    }
+
+    pub(super) fn dimensions(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    /// Decodes all pixels into `pixels`, which holds exactly `width * height` colors
+    pub(super) fn decode(&self, data: &[u8], pixels: &mut [Color]) -> Result<(), ImageError> {
+        let bytes_per_pixel = self.bits_per_pixel as usize / 8;
+        // rows are padded to 4 bytes
+        let row_size = (self.width * self.bits_per_pixel as usize).div_ceil(32) * 4;
+        let palette_offset = FILE_HEADER_SIZE + self.info_size;
+        let palette = data
+            .get(palette_offset..palette_offset + self.palette_size * PALETTE_ENTRY_SIZE)
+            .ok_or(ImageError::UnexpectedEnd)?;
+
+        for (y, row_pixels) in pixels.chunks_exact_mut(self.width).enumerate() {
+            let row = if self.bottom_up {
+                self.height - 1 - y
+            } else {
+                y
+            };
+            let row_offset = self.pixel_offset + row * row_size;
+            let row_data = data
+                .get(row_offset..row_offset + self.width * bytes_per_pixel)
+                .ok_or(ImageError::UnexpectedEnd)?;
+
+            for (pixel, bytes) in row_pixels
+                .iter_mut()
+                .zip(row_data.chunks_exact(bytes_per_pixel))
+            {
+                *pixel = match bytes {
+                    &[index] => {
+                        let offset = index as usize * PALETTE_ENTRY_SIZE;
+                        let entry = palette
+                            .get(offset..offset + PALETTE_ENTRY_SIZE)
+                            .ok_or(ImageError::InvalidHeader)?;
+                        Color {
+                            red: entry[2],
+                            green: entry[1],
+                            blue: entry[0],
+                        }
+                    }
+                    &[blue, green, red] => Color { red, green, blue },
+                    bytes => {
+                        let value = u32::from_le_bytes(bytes.try_into().unwrap());
+                        let [red, green, blue, alpha] = self.masks.map(|mask| channel(value, mask));
+                        match self.masks[3] {
+                            0 => Color { red, green, blue },
+                            _ => blend(red, green, blue, alpha),
+                        }
+                    }
+                };
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Extracts the channel selected by `mask` from a pixel and scales it to 8 bits
+fn channel(value: u32, mask: u32) -> u8 {
+    if mask == 0 {
+        return 0;
+    }
+    let value = (value & mask) >> mask.trailing_zeros();
+    let max = mask >> mask.trailing_zeros();
+    ((value as u64 * 255 + max as u64 / 2) / max as u64) as u8
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Result<u16, ImageError> {
+    data.get(offset..offset + 2)
+        .map(|bytes| u16::from_le_bytes(bytes.try_into().unwrap()))
+        .ok_or(ImageError::InvalidHeader)
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32, ImageError> {
+    data.get(offset..offset + 4)
+        .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+        .ok_or(ImageError::InvalidHeader)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graphics::image::ImageFormat;
+
+    use super::*;
+
+    /// 2x2 bottom up bitmap with 24 bits per pixel, i.e. two padding bytes per row
+    fn bitmap() -> [u8; 70] {
+        let mut data = [0; 70];
+        data[..2].copy_from_slice(b"BM");
+        data[2..6].copy_from_slice(&70u32.to_le_bytes());
+        data[10..14].copy_from_slice(&54u32.to_le_bytes());
+        data[14..18].copy_from_slice(&40u32.to_le_bytes());
+        data[18..22].copy_from_slice(&2i32.to_le_bytes());
+        data[22..26].copy_from_slice(&2i32.to_le_bytes());
+        data[26..28].copy_from_slice(&1u16.to_le_bytes());
+        data[28..30].copy_from_slice(&24u16.to_le_bytes());
+        // bottom row: blue, green; top row: red, white
+        data[54..62].copy_from_slice(&[0xFF, 0, 0, 0, 0xFF, 0, 0, 0]);
+        data[62..70].copy_from_slice(&[0, 0, 0xFF, 0xFF, 0xFF, 0xFF, 0, 0]);
+        data
+    }
+
+    #[test]
+    fn decodes_bottom_up_bitmap() {
+        let data = bitmap();
+        let mut pixels = [Color::black(); 4];
+        let info = ImageFormat::detect(&data)
+            .unwrap()
+            .decode(&data, &mut pixels)
+            .unwrap();
+        assert_eq!((info.width, info.height), (2, 2));
+        assert_eq!(
+            pixels,
+            [Color::red(), Color::white(), Color::blue(), Color::green()]
+        );
+
+        assert_eq!(
+            ImageFormat::Bmp.decode(&data[..66], &mut pixels),
+            Err(ImageError::UnexpectedEnd)
+        );
+        assert_eq!(
+            ImageFormat::Bmp.decode(&data, &mut pixels[..3]),
+            Err(ImageError::BufferTooSmall)
+        );
+    }
+
+    #[test]
+    fn rejects_index_beyond_palette() {
+        // 1x1 bitmap with 8 bits per pixel and a palette of one color
+        let mut data = [0; 62];
+        data[..2].copy_from_slice(b"BM");
+        data[2..6].copy_from_slice(&62u32.to_le_bytes());
+        data[10..14].copy_from_slice(&58u32.to_le_bytes());
+        data[14..18].copy_from_slice(&40u32.to_le_bytes());
+        data[18..22].copy_from_slice(&1i32.to_le_bytes());
+        data[22..26].copy_from_slice(&1i32.to_le_bytes());
+        data[26..28].copy_from_slice(&1u16.to_le_bytes());
+        data[28..30].copy_from_slice(&8u16.to_le_bytes());
+        data[46..50].copy_from_slice(&1u32.to_le_bytes());
+        data[54..58].copy_from_slice(&[0xFF, 0, 0, 0]);
+
+        let mut pixels = [Color::black(); 1];
+        ImageFormat::Bmp.decode(&data, &mut pixels).unwrap();
+        assert_eq!(pixels, [Color::blue()]);
+
+        data[58] = 1;
+        assert_eq!(
+            ImageFormat::Bmp.decode(&data, &mut pixels),
+            Err(ImageError::InvalidHeader)
+        );
+    }
+}
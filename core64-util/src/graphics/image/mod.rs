@@ -0,0 +1,104 @@
+use core::{
+    error::Error,
+    fmt::{Display, Formatter},
+};
+
+use crate::graphics::Color;
+
+mod bmp;
+mod qoi;
+
+/// Image formats supported for the boot splash
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ImageFormat {
+    /// Windows bitmap with 8 (palette), 24 or 32 bits per pixel, uncompressed or with bit fields
+    Bmp,
+    /// Quite OK Image Format
+    Qoi,
+}
+
+/// Dimensions of an image in pixels
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ImageInfo {
+    pub format: ImageFormat,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl ImageInfo {
+    pub fn pixel_count(&self) -> usize {
+        self.width * self.height
+    }
+}
+
+impl ImageFormat {
+    /// Detects the image format by its magic number
+    pub fn detect(data: &[u8]) -> Option<Self> {
+        match data {
+            [b'B', b'M', ..] => Some(Self::Bmp),
+            [b'q', b'o', b'i', b'f', ..] => Some(Self::Qoi),
+            _ => None,
+        }
+    }
+
+    /// Reads the dimensions of an image of this format
+    pub fn info(self, data: &[u8]) -> Result<ImageInfo, ImageError> {
+        let (width, height) = match self {
+            Self::Bmp => bmp::Header::parse(data)?.dimensions(),
+            Self::Qoi => qoi::dimensions(data)?,
+        };
+        width
+            .checked_mul(height)
+            .filter(|&pixel_count| pixel_count > 0)
+            .ok_or(ImageError::InvalidHeader)?;
+        Ok(ImageInfo {
+            format: self,
+            width,
+            height,
+        })
+    }
+
+    /// Decodes an image of this format into `pixels`, row by row starting at the top left. `pixels` must hold at least
+    /// [`ImageInfo::pixel_count`] colors. Transparent pixels are blended onto black.
+    pub fn decode(self, data: &[u8], pixels: &mut [Color]) -> Result<ImageInfo, ImageError> {
+        let info = self.info(data)?;
+        let pixels = pixels
+            .get_mut(..info.pixel_count())
+            .ok_or(ImageError::BufferTooSmall)?;
+        match self {
+            Self::Bmp => bmp::Header::parse(data)?.decode(data, pixels)?,
+            Self::Qoi => qoi::decode(data, pixels)?,
+        }
+        Ok(info)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ImageError {
+    /// Magic number or header of the format are invalid
+    InvalidHeader,
+    /// The image ends in the middle of the pixel data
+    UnexpectedEnd,
+    /// The image uses a feature that is not supported (e.g. RLE compression)
+    Unsupported,
+    /// The output buffer cannot hold all pixels of the image
+    BufferTooSmall,
+}
+
+impl Display for ImageError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl Error for ImageError {}
+
+/// Blends a color with the given alpha value onto black
+fn blend(red: u8, green: u8, blue: u8, alpha: u8) -> Color {
+    let scale = |channel: u8| ((channel as u16 * alpha as u16 + 127) / 255) as u8;
+    Color {
+        red: scale(red),
+        green: scale(green),
+        blue: scale(blue),
+    }
+}
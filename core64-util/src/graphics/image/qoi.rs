@@ -0,0 +1,118 @@
+use crate::graphics::{
+    image::{blend, ImageError},
+    Color,
+};
+
+const MAGIC: &[u8; 4] = b"qoif";
+const HEADER_SIZE: usize = 14;
+const OP_RGB: u8 = 0xFE;
+const OP_RGBA: u8 = 0xFF;
+const OP_MASK: u8 = 0xC0;
+const OP_INDEX: u8 = 0x00;
+const OP_DIFF: u8 = 0x40;
+const OP_LUMA: u8 = 0x80;
+const OP_RUN: u8 = 0xC0;
+/// Number of previously seen pixels, that can be referenced by `OP_INDEX`
+const INDEX_SIZE: usize = 64;
+
+pub(super) fn dimensions(data: &[u8]) -> Result<(usize, usize), ImageError> {
+    if data.len() < HEADER_SIZE || !data.starts_with(MAGIC) {
+        return Err(ImageError::InvalidHeader);
+    }
+    let width = u32::from_be_bytes(data[4..8].try_into().unwrap());
+    let height = u32::from_be_bytes(data[8..12].try_into().unwrap());
+    Ok((width as usize, height as usize))
+}
+
+/// Decodes all pixels into `pixels`, which holds exactly `width * height` colors
+pub(super) fn decode(data: &[u8], pixels: &mut [Color]) -> Result<(), ImageError> {
+    let mut input = data
+        .get(HEADER_SIZE..)
+        .ok_or(ImageError::InvalidHeader)?
+        .iter()
+        .copied();
+    let mut next = || input.next().ok_or(ImageError::UnexpectedEnd);
+
+    let mut index = [[0u8; 4]; INDEX_SIZE];
+    let mut pixel = [0, 0, 0, 0xFF];
+    let mut run = 0;
+    for output in pixels {
+        if run > 0 {
+            run -= 1;
+        } else {
+            let op = next()?;
+            match op {
+                OP_RGB => pixel[..3].copy_from_slice(&[next()?, next()?, next()?]),
+                OP_RGBA => pixel = [next()?, next()?, next()?, next()?],
+                // the current pixel is repeated
+                OP_RUN.. => run = op & !OP_MASK,
+                OP_LUMA.. => {
+                    let green = (op & !OP_MASK).wrapping_sub(32);
+                    let red_blue = next()?;
+                    pixel[0] =
+                        pixel[0].wrapping_add(green.wrapping_add(red_blue >> 4).wrapping_sub(8));
+                    pixel[1] = pixel[1].wrapping_add(green);
+                    pixel[2] =
+                        pixel[2].wrapping_add(green.wrapping_add(red_blue & 0xF).wrapping_sub(8));
+                }
+                OP_DIFF.. => {
+                    pixel[0] = pixel[0].wrapping_add((op >> 4) & 0x3).wrapping_sub(2);
+                    pixel[1] = pixel[1].wrapping_add((op >> 2) & 0x3).wrapping_sub(2);
+                    pixel[2] = pixel[2].wrapping_add(op & 0x3).wrapping_sub(2);
+                }
+                OP_INDEX.. => pixel = index[op as usize],
+            }
+            index[hash(pixel)] = pixel;
+        }
+
+        let [red, green, blue, alpha] = pixel;
+        *output = blend(red, green, blue, alpha);
+    }
+    Ok(())
+}
+
+fn hash([red, green, blue, alpha]: [u8; 4]) -> usize {
+    (red as usize * 3 + green as usize * 5 + blue as usize * 7 + alpha as usize * 11) % INDEX_SIZE
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graphics::image::ImageFormat;
+
+    use super::*;
+
+    #[test]
+    fn decodes_all_operations() {
+        #[rustfmt::skip]
+        let data = [
+            b'q', b'o', b'i', b'f', 0, 0, 0, 7, 0, 0, 0, 1, 4, 0,
+            OP_RGB, 0xFF, 0, 0,             // red
+            OP_RUN | 1,                     // red, red
+            OP_DIFF | 0b01_11_10,           // red - 1, green + 1
+            OP_LUMA | 34, 0x88,             // all channels + 2
+            OP_INDEX | hash([0xFF, 0, 0, 0xFF]) as u8, // red
+            OP_RGBA, 0xFF, 0xFF, 0xFF, 0,   // transparent white
+            0, 0, 0, 0, 0, 0, 0, 1,
+        ];
+        let mut pixels = [Color::black(); 7];
+        let info = ImageFormat::detect(&data)
+            .unwrap()
+            .decode(&data, &mut pixels)
+            .unwrap();
+        assert_eq!((info.width, info.height), (7, 1));
+
+        let color = |red, green, blue| Color { red, green, blue };
+        assert_eq!(
+            pixels,
+            [
+                Color::red(),
+                Color::red(),
+                Color::red(),
+                color(0xFE, 1, 0),
+                color(0x0, 3, 2),
+                Color::red(),
+                Color::black(),
+            ]
+        );
+    }
+}
@@ -1,6 +1,8 @@
 pub mod framebuffer;
+pub mod image;
+pub mod splash;
 
-#[derive(Copy, Clone, Debug, Default)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
 pub struct Color {
     pub red: u8,
     pub green: u8,
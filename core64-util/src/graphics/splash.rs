@@ -0,0 +1,140 @@
+use crate::{
+    boot::tag::{Tag, TAG_BOOT_SPLASH},
+    graphics::{framebuffer::FrameBufferMetadata, Color},
+};
+
+/// Progress of a completed boot
+pub const PROGRESS_MAX: u32 = 100;
+const PROGRESS_BAR_HEIGHT: usize = 6;
+/// Vertical space between image and progress bar
+const PROGRESS_BAR_GAP: usize = 32;
+const PROGRESS_BAR_COLOR: Color = Color::white();
+const PROGRESS_BAR_BACKGROUND: Color = Color::dark_grey();
+
+/// Boot splash drawn by the loader: an image centered on the framebuffer and a progress bar below it. Passed to the kernel, so
+/// that it can keep advancing the progress bar until its own console takes over.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct BootSplash {
+    pub image_x: u32,
+    pub image_y: u32,
+    pub image_width: u32,
+    pub image_height: u32,
+    pub bar_x: u32,
+    pub bar_y: u32,
+    pub bar_width: u32,
+    pub bar_height: u32,
+    /// Progress shown by the progress bar, up to [`PROGRESS_MAX`]
+    pub progress: u32,
+    pub reserved: u32,
+}
+
+impl BootSplash {
+    /// Centers an image and its progress bar on the framebuffer. Returns `None`, if they do not fit.
+    pub fn new(
+        framebuffer: &FrameBufferMetadata,
+        image_width: usize,
+        image_height: usize,
+    ) -> Option<Self> {
+        let total_height = image_height + PROGRESS_BAR_GAP + PROGRESS_BAR_HEIGHT;
+        if image_width > framebuffer.width || total_height > framebuffer.height {
+            return None;
+        }
+        let bar_width = image_width.max(framebuffer.width / 4);
+        let image_y = (framebuffer.height - total_height) / 2;
+
+        Some(Self {
+            image_x: ((framebuffer.width - image_width) / 2) as u32,
+            image_y: image_y as u32,
+            image_width: image_width as u32,
+            image_height: image_height as u32,
+            bar_x: ((framebuffer.width - bar_width) / 2) as u32,
+            bar_y: (image_y + image_height + PROGRESS_BAR_GAP) as u32,
+            bar_width: bar_width as u32,
+            bar_height: PROGRESS_BAR_HEIGHT as u32,
+            progress: 0,
+            reserved: 0,
+        })
+    }
+
+    /// Draws the image, whose pixels are stored row by row starting at the top left, and an empty progress bar
+    ///
+    /// # Safety
+    /// The framebuffer must be mapped at its base address and the splash must have been created for it.
+    pub unsafe fn draw(&mut self, framebuffer: &FrameBufferMetadata, pixels: &[Color]) {
+        let rows = pixels.chunks_exact(self.image_width as usize);
+        for (y, row) in rows.take(self.image_height as usize).enumerate() {
+            for (x, &color) in row.iter().enumerate() {
+                framebuffer.write_pixel(
+                    self.image_x as usize + x,
+                    self.image_y as usize + y,
+                    color,
+                );
+            }
+        }
+        self.progress = 0;
+        self.draw_progress_bar(framebuffer);
+    }
+
+    /// Advances the progress bar. Progress never decreases.
+    ///
+    /// # Safety
+    /// The framebuffer must be mapped at its base address and the splash must have been created for it.
+    pub unsafe fn set_progress(&mut self, framebuffer: &FrameBufferMetadata, progress: u32) {
+        self.progress = self.progress.max(progress.min(PROGRESS_MAX));
+        self.draw_progress_bar(framebuffer);
+    }
+
+    unsafe fn draw_progress_bar(&self, framebuffer: &FrameBufferMetadata) {
+        let filled = (self.bar_width * self.progress / PROGRESS_MAX) as usize;
+        for x in 0..self.bar_width as usize {
+            let color = if x < filled {
+                PROGRESS_BAR_COLOR
+            } else {
+                PROGRESS_BAR_BACKGROUND
+            };
+            for y in 0..self.bar_height as usize {
+                framebuffer.write_pixel(self.bar_x as usize + x, self.bar_y as usize + y, color);
+            }
+        }
+    }
+}
+
+unsafe impl Tag for BootSplash {
+    const TYPE: u32 = TAG_BOOT_SPLASH;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graphics::framebuffer::BPP;
+
+    use super::*;
+
+    #[test]
+    fn centers_image_and_progress_bar() {
+        let mut buffer = [0u32; 64 * 64];
+        let framebuffer = FrameBufferMetadata {
+            base: buffer.as_mut_ptr() as u64,
+            size: buffer.len() * BPP,
+            width: 64,
+            height: 64,
+            stride: 64,
+            is_rgb: true,
+        };
+        assert_eq!(BootSplash::new(&framebuffer, 65, 1), None);
+
+        let mut splash = BootSplash::new(&framebuffer, 2, 2).unwrap();
+        assert_eq!((splash.image_x, splash.image_y), (31, 12));
+        assert_eq!((splash.bar_x, splash.bar_y, splash.bar_width), (24, 46, 16));
+
+        unsafe {
+            splash.draw(&framebuffer, &[Color::red(); 4]);
+            splash.set_progress(&framebuffer, 50);
+            splash.set_progress(&framebuffer, 25);
+        }
+        assert_eq!(splash.progress, 50);
+        assert_eq!(buffer[12 * 64 + 31], 0xFF);
+        assert_eq!(buffer[46 * 64 + 24 + 7], 0xFF_FFFF);
+        assert_eq!(buffer[46 * 64 + 24 + 8], 0x1D_1D1D);
+    }
+}
@@ -1,11 +1,15 @@
 #![no_std]
 
-use crate::graphics::framebuffer::FrameBufferMetadata;
+#[cfg(any(feature = "alloc", test))]
+extern crate alloc;
 
+pub use crate::boot::BootInfo;
+
+pub mod acpi;
+pub mod boot;
+#[cfg(any(feature = "alloc", test))]
+pub mod compression;
+pub mod crypto;
 pub mod graphics;
 pub mod memory;
-
-#[derive(Clone, Debug)]
-pub struct BootInfo {
-    pub frame_buffer_metadata: FrameBufferMetadata,
-}
+pub mod serial;
@@ -0,0 +1,267 @@
+use core::{
+    error::Error,
+    fmt::{Debug, Display, Formatter, LowerHex, UpperHex},
+    ops::{Add, AddAssign, Sub, SubAssign},
+};
+
+use crate::memory::paging::PagingMode;
+
+/// Physical addresses are limited to 52 bits by the page table entry format
+const PHYSICAL_ADDRESS_MASK: u64 = (1 << 52) - 1;
+
+/// Physical address of at most 52 bits. Same layout as a `u64`, so it may be used in structs shared between loader and kernel.
+#[repr(transparent)]
+#[derive(Copy, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PhysAddr(u64);
+
+impl PhysAddr {
+    pub const ZERO: Self = Self(0);
+
+    /// Panics, if `address` exceeds 52 bits
+    pub const fn new(address: u64) -> Self {
+        match Self::try_new(address) {
+            Ok(address) => address,
+            Err(_) => panic!("physical address exceeds 52 bits"),
+        }
+    }
+
+    pub const fn try_new(address: u64) -> Result<Self, AddressError> {
+        if address & !PHYSICAL_ADDRESS_MASK == 0 {
+            Ok(Self(address))
+        } else {
+            Err(AddressError::InvalidPhysical(address))
+        }
+    }
+
+    /// Clears all bits above bit 51
+    pub const fn new_truncate(address: u64) -> Self {
+        Self(address & PHYSICAL_ADDRESS_MASK)
+    }
+
+    pub const fn as_u64(self) -> u64 {
+        self.0
+    }
+
+    /// Rounds down to a multiple of `align`, which must be a power of two
+    pub const fn align_down(self, align: u64) -> Self {
+        Self(align_down(self.0, align))
+    }
+
+    /// Rounds up to a multiple of `align`, which must be a power of two
+    pub const fn align_up(self, align: u64) -> Self {
+        Self::new(align_up(self.0, align))
+    }
+
+    pub const fn is_aligned(self, align: u64) -> bool {
+        align_down(self.0, align) == self.0
+    }
+}
+
+/// Virtual address in canonical form, i.e. all bits above the significant bits equal the highest significant bit.
+///
+/// Addresses are checked against 5-level paging, which is a superset of 4-level paging. Use [`VirtAddr::is_canonical`] to check
+/// an address against a specific [`PagingMode`].
+#[repr(transparent)]
+#[derive(Copy, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct VirtAddr(u64);
+
+impl VirtAddr {
+    pub const ZERO: Self = Self(0);
+
+    /// Panics, if `address` is not canonical
+    pub const fn new(address: u64) -> Self {
+        match Self::try_new(address) {
+            Ok(address) => address,
+            Err(_) => panic!("virtual address is not canonical"),
+        }
+    }
+
+    pub const fn try_new(address: u64) -> Result<Self, AddressError> {
+        let canonical = Self::new_truncate(address, PagingMode::FiveLevel);
+        if canonical.0 == address {
+            Ok(canonical)
+        } else {
+            Err(AddressError::NonCanonical(address))
+        }
+    }
+
+    /// Sign extends the highest significant bit of `address` in the given paging mode
+    pub const fn new_truncate(address: u64, paging_mode: PagingMode) -> Self {
+        let shift = u64::BITS - paging_mode.virtual_address_bits();
+        Self((((address << shift) as i64) >> shift) as u64)
+    }
+
+    pub fn from_ptr<T: ?Sized>(ptr: *const T) -> Self {
+        Self::new(ptr as *const () as u64)
+    }
+
+    pub const fn as_u64(self) -> u64 {
+        self.0
+    }
+
+    pub const fn as_ptr<T>(self) -> *const T {
+        self.0 as *const T
+    }
+
+    pub const fn as_mut_ptr<T>(self) -> *mut T {
+        self.0 as *mut T
+    }
+
+    /// Whether the address is canonical in the given paging mode
+    pub const fn is_canonical(self, paging_mode: PagingMode) -> bool {
+        Self::new_truncate(self.0, paging_mode).0 == self.0
+    }
+
+    /// Rounds down to a multiple of `align`, which must be a power of two
+    pub const fn align_down(self, align: u64) -> Self {
+        Self(align_down(self.0, align))
+    }
+
+    /// Rounds up to a multiple of `align`, which must be a power of two
+    pub const fn align_up(self, align: u64) -> Self {
+        Self::new(align_up(self.0, align))
+    }
+
+    pub const fn is_aligned(self, align: u64) -> bool {
+        align_down(self.0, align) == self.0
+    }
+}
+
+const fn align_down(address: u64, align: u64) -> u64 {
+    assert!(align.is_power_of_two(), "alignment must be a power of two");
+    address & !(align - 1)
+}
+
+const fn align_up(address: u64, align: u64) -> u64 {
+    assert!(align.is_power_of_two(), "alignment must be a power of two");
+    match address.checked_add(align - 1) {
+        Some(address) => address & !(align - 1),
+        None => panic!("address overflows while aligning up"),
+    }
+}
+
+macro_rules! impl_address {
+    ($address:ident) => {
+        impl Debug for $address {
+            fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+                write!(f, "{}({:#x})", stringify!($address), self.0)
+            }
+        }
+
+        impl Display for $address {
+            fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+                write!(f, "{:#x}", self.0)
+            }
+        }
+
+        impl LowerHex for $address {
+            fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+                LowerHex::fmt(&self.0, f)
+            }
+        }
+
+        impl UpperHex for $address {
+            fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+                UpperHex::fmt(&self.0, f)
+            }
+        }
+
+        impl Add<u64> for $address {
+            type Output = Self;
+
+            fn add(self, rhs: u64) -> Self {
+                Self::new(self.0 + rhs)
+            }
+        }
+
+        impl AddAssign<u64> for $address {
+            fn add_assign(&mut self, rhs: u64) {
+                *self = *self + rhs;
+            }
+        }
+
+        impl Sub<u64> for $address {
+            type Output = Self;
+
+            fn sub(self, rhs: u64) -> Self {
+                Self::new(self.0 - rhs)
+            }
+        }
+
+        impl SubAssign<u64> for $address {
+            fn sub_assign(&mut self, rhs: u64) {
+                *self = *self - rhs;
+            }
+        }
+
+        /// Distance in bytes
+        impl Sub for $address {
+            type Output = u64;
+
+            fn sub(self, rhs: Self) -> u64 {
+                self.0 - rhs.0
+            }
+        }
+    };
+}
+
+impl_address!(PhysAddr);
+impl_address!(VirtAddr);
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AddressError {
+    /// Physical address exceeding 52 bits
+    InvalidPhysical(u64),
+    /// Virtual address, which is not sign extended from its highest significant bit
+    NonCanonical(u64),
+    /// Address is not aligned to the page size
+    Unaligned(u64),
+}
+
+impl Display for AddressError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl Error for AddressError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checks_canonical_form() {
+        assert_eq!(
+            VirtAddr::try_new(0xFFFF_8000_0000_0000),
+            Ok(VirtAddr(0xFFFF_8000_0000_0000))
+        );
+        assert_eq!(
+            VirtAddr::try_new(0x0100_0000_0000_0000),
+            Err(AddressError::NonCanonical(0x0100_0000_0000_0000))
+        );
+        assert!(!VirtAddr::new(0x0000_8000_0000_0000).is_canonical(PagingMode::FourLevel));
+        assert_eq!(
+            VirtAddr::new_truncate(0x0000_8000_0000_0000, PagingMode::FourLevel),
+            VirtAddr(0xFFFF_8000_0000_0000)
+        );
+        assert_eq!(
+            PhysAddr::try_new(1 << 52),
+            Err(AddressError::InvalidPhysical(1 << 52))
+        );
+    }
+
+    #[test]
+    fn aligns_addresses() {
+        let address = PhysAddr::new(0x1234);
+        assert_eq!(address.align_down(0x1000), PhysAddr(0x1000));
+        assert_eq!(address.align_up(0x1000), PhysAddr(0x2000));
+        assert!(!address.is_aligned(0x1000));
+        assert!(PhysAddr(0x20_0000).is_aligned(0x20_0000));
+        assert_eq!(
+            VirtAddr::new(0xFFFF_8000_0000_0001).align_up(0x1000)
+                - VirtAddr::new(0xFFFF_8000_0000_0000),
+            0x1000
+        );
+    }
+}
@@ -0,0 +1,297 @@
+use core::{
+    error::Error,
+    fmt::{Display, Formatter},
+};
+
+use crate::memory::{
+    address::PhysAddr, MemoryDescriptor, MemoryMap, MemoryType, PhysicalAddress, PAGE_SIZE,
+};
+
+/// Builds a [`MemoryMap`] inside of a fixed, caller provided descriptor buffer. Does not allocate, so it can be used after boot services have been exited.
+#[derive(Debug)]
+pub struct MemoryMapBuilder<'a> {
+    buffer: &'a mut [MemoryDescriptor],
+    len: usize,
+}
+
+impl<'a> MemoryMapBuilder<'a> {
+    pub fn new(buffer: &'a mut [MemoryDescriptor]) -> Self {
+        Self { buffer, len: 0 }
+    }
+
+    /// Amount of descriptors currently stored in the builder
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Adds a descriptor covering `num_pages` pages starting at `phys_start`.
+    pub fn push(
+        &mut self,
+        phys_start: PhysicalAddress,
+        num_pages: u64,
+        r#type: MemoryType,
+    ) -> Result<(), MemoryMapError> {
+        if num_pages == 0 {
+            return Ok(());
+        }
+        let phys_start = PhysAddr::new(phys_start);
+        self.push_range(
+            phys_start,
+            phys_start + num_pages * PAGE_SIZE as u64,
+            r#type,
+        )
+    }
+
+    /// Changes the type of the physical range `phys_start..phys_end` (rounded outwards to page boundaries) to `r#type`.
+    /// Descriptors that only partially overlap the range are split at the exact range boundaries, so memory outside the range keeps its type.
+    /// Parts of the range that are not covered by any descriptor are left untouched.
+    pub fn mark(
+        &mut self,
+        phys_start: PhysicalAddress,
+        phys_end: PhysicalAddress,
+        r#type: MemoryType,
+    ) -> Result<(), MemoryMapError> {
+        let start = PhysAddr::new(phys_start).align_down(PAGE_SIZE as u64);
+        let end = PhysAddr::new(phys_end).align_up(PAGE_SIZE as u64);
+
+        if start >= end {
+            return Ok(());
+        }
+
+        // split pieces are appended to the end of the buffer and never overlap the range, so they do not have to be visited again
+        for index in 0..self.len {
+            let desc = self.buffer[index];
+            if desc.phys_end <= start || desc.phys_start >= end || desc.r#type == r#type {
+                continue;
+            }
+
+            let head = desc.phys_start < start;
+            let tail = desc.phys_end > end;
+            if self.len + head as usize + tail as usize > self.buffer.len() {
+                return Err(MemoryMapError::BufferTooSmall);
+            }
+
+            let overlap_start = desc.phys_start.max(start);
+            let overlap_end = desc.phys_end.min(end);
+            self.buffer[index] = descriptor(overlap_start, overlap_end, r#type);
+
+            if head {
+                self.push_range(desc.phys_start, overlap_start, desc.r#type)?;
+            }
+            if tail {
+                self.push_range(overlap_end, desc.phys_end, desc.r#type)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Sorts descriptors by address, merges adjacent descriptors of the same type and returns the resulting memory map.
+    pub fn build(self) -> MemoryMap {
+        let descriptors = &mut self.buffer[..self.len];
+        descriptors.sort_unstable_by_key(|desc| desc.phys_start);
+
+        let mut len = 0;
+        for index in 0..descriptors.len() {
+            let desc = descriptors[index];
+            if len > 0 {
+                let previous = &mut descriptors[len - 1];
+                if previous.r#type == desc.r#type && previous.phys_end == desc.phys_start {
+                    *previous = descriptor(previous.phys_start, desc.phys_end, desc.r#type);
+                    continue;
+                }
+            }
+            descriptors[len] = desc;
+            len += 1;
+        }
+
+        let mut first_addr = u64::MAX;
+        let mut first_available_addr = u64::MAX;
+        let mut last_addr = u64::MIN;
+        let mut last_available_addr = u64::MIN;
+
+        for desc in &descriptors[..len] {
+            first_addr = first_addr.min(desc.phys_start.as_u64());
+            last_addr = last_addr.max(desc.phys_end.as_u64());
+
+            if desc.r#type == MemoryType::Available {
+                first_available_addr = first_available_addr.min(desc.phys_start.as_u64());
+                last_available_addr = last_available_addr.max(desc.phys_end.as_u64());
+            }
+        }
+
+        MemoryMap {
+            descriptors: descriptors.as_mut_ptr(),
+            descriptors_len: len as u64,
+            first_addr,
+            last_addr,
+            first_available_addr,
+            last_available_addr,
+        }
+    }
+
+    fn push_range(
+        &mut self,
+        phys_start: PhysAddr,
+        phys_end: PhysAddr,
+        r#type: MemoryType,
+    ) -> Result<(), MemoryMapError> {
+        let slot = self
+            .buffer
+            .get_mut(self.len)
+            .ok_or(MemoryMapError::BufferTooSmall)?;
+        *slot = descriptor(phys_start, phys_end, r#type);
+        self.len += 1;
+
+        Ok(())
+    }
+}
+
+fn descriptor(phys_start: PhysAddr, phys_end: PhysAddr, r#type: MemoryType) -> MemoryDescriptor {
+    MemoryDescriptor {
+        phys_start,
+        phys_end,
+        num_pages: (phys_end - phys_start) / PAGE_SIZE as u64,
+        r#type,
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MemoryMapError {
+    /// The descriptor buffer cannot hold any more descriptors
+    BufferTooSmall,
+}
+
+impl Display for MemoryMapError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl Error for MemoryMapError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAGE: u64 = PAGE_SIZE as u64;
+
+    fn entries(map: &MemoryMap) -> impl Iterator<Item = (u64, u64, MemoryType)> + '_ {
+        map.descriptors().iter().map(|desc| {
+            (
+                desc.phys_start.as_u64(),
+                desc.phys_end.as_u64(),
+                desc.r#type,
+            )
+        })
+    }
+
+    #[test]
+    fn splits_descriptor_around_range() {
+        let mut buffer = [MemoryDescriptor::default(); 8];
+        let mut builder = MemoryMapBuilder::new(&mut buffer);
+        builder.push(0x10_0000, 256, MemoryType::Reserved).unwrap();
+        builder
+            .mark(0x14_0000, 0x14_0000 + 16 * PAGE, MemoryType::KernelStack)
+            .unwrap();
+
+        let map = builder.build();
+        assert!(entries(&map).eq([
+            (0x10_0000, 0x14_0000, MemoryType::Reserved),
+            (0x14_0000, 0x15_0000, MemoryType::KernelStack),
+            (0x15_0000, 0x20_0000, MemoryType::Reserved),
+        ]));
+        assert!(map
+            .descriptors()
+            .iter()
+            .all(|desc| desc.num_pages * PAGE == desc.size()));
+    }
+
+    #[test]
+    fn marks_range_straddling_descriptors() {
+        let mut buffer = [MemoryDescriptor::default(); 8];
+        let mut builder = MemoryMapBuilder::new(&mut buffer);
+        builder.push(0x20_0000, 16, MemoryType::Available).unwrap();
+        builder.push(0x21_0000, 16, MemoryType::Reserved).unwrap();
+        builder
+            .mark(0x20_8000, 0x21_8000, MemoryType::KernelCode)
+            .unwrap();
+
+        let map = builder.build();
+        assert!(entries(&map).eq([
+            (0x20_0000, 0x20_8000, MemoryType::Available),
+            (0x20_8000, 0x21_8000, MemoryType::KernelCode),
+            (0x21_8000, 0x22_0000, MemoryType::Reserved),
+        ]));
+    }
+
+    #[test]
+    fn rounds_unaligned_range_to_pages() {
+        let mut buffer = [MemoryDescriptor::default(); 8];
+        let mut builder = MemoryMapBuilder::new(&mut buffer);
+        builder.push(0x0, 16, MemoryType::Available).unwrap();
+        builder
+            .mark(0x1234, 0x2010, MemoryType::KernelData)
+            .unwrap();
+
+        let map = builder.build();
+        assert!(entries(&map).eq([
+            (0x0, 0x1000, MemoryType::Available),
+            (0x1000, 0x3000, MemoryType::KernelData),
+            (0x3000, 0x10000, MemoryType::Available),
+        ]));
+    }
+
+    #[test]
+    fn sorts_and_merges_adjacent_descriptors() {
+        let mut buffer = [MemoryDescriptor::default(); 8];
+        let mut builder = MemoryMapBuilder::new(&mut buffer);
+        builder.push(0x3000, 1, MemoryType::Available).unwrap();
+        builder.push(0x1000, 1, MemoryType::Available).unwrap();
+        builder.push(0x2000, 1, MemoryType::Available).unwrap();
+        builder.push(0x5000, 1, MemoryType::Available).unwrap();
+        builder.push(0x6000, 1, MemoryType::Reserved).unwrap();
+
+        let map = builder.build();
+        assert!(entries(&map).eq([
+            (0x1000, 0x4000, MemoryType::Available),
+            (0x5000, 0x6000, MemoryType::Available),
+            (0x6000, 0x7000, MemoryType::Reserved),
+        ]));
+        assert_eq!(map.first_addr, 0x1000);
+        assert_eq!(map.last_addr, 0x7000);
+        assert_eq!(map.first_available_addr, 0x1000);
+        assert_eq!(map.last_available_addr, 0x6000);
+    }
+
+    #[test]
+    fn merges_marked_range_with_neighbours() {
+        let mut buffer = [MemoryDescriptor::default(); 8];
+        let mut builder = MemoryMapBuilder::new(&mut buffer);
+        builder.push(0x0, 4, MemoryType::Available).unwrap();
+        builder.push(0x4000, 4, MemoryType::Reserved).unwrap();
+        builder.mark(0x2000, 0x4000, MemoryType::Reserved).unwrap();
+
+        let map = builder.build();
+        assert!(entries(&map).eq([
+            (0x0, 0x2000, MemoryType::Available),
+            (0x2000, 0x8000, MemoryType::Reserved),
+        ]));
+    }
+
+    #[test]
+    fn fails_when_buffer_is_too_small() {
+        let mut buffer = [MemoryDescriptor::default(); 2];
+        let mut builder = MemoryMapBuilder::new(&mut buffer);
+        builder.push(0x0, 16, MemoryType::Available).unwrap();
+        assert_eq!(
+            builder.mark(0x4000, 0x5000, MemoryType::KernelData),
+            Err(MemoryMapError::BufferTooSmall)
+        );
+        assert_eq!(builder.len(), 1);
+    }
+}
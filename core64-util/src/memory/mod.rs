@@ -1,11 +1,21 @@
 use core::fmt::{Debug, Display, Formatter};
 use core::slice;
 
+use crate::memory::{
+    address::PhysAddr,
+    page::{PhysFrame, PhysFrameRange},
+};
+
+pub mod address;
+pub mod builder;
+pub mod page;
 pub mod paging;
 pub mod pmm;
 
 pub const PAGE_SIZE: usize = 0x1000;
+/// Raw virtual address, see [`address::VirtAddr`] for the checked type
 pub type VirtualAddress = u64;
+/// Raw physical address, see [`address::PhysAddr`] for the checked type
 pub type PhysicalAddress = u64;
 
 #[repr(C)]
@@ -30,14 +40,18 @@ impl MemoryMap {
     pub fn descriptors(&self) -> &[MemoryDescriptor] {
         unsafe { slice::from_raw_parts(self.descriptors, self.descriptors_len as usize) }
     }
+
+    pub fn descriptors_mut(&mut self) -> &mut [MemoryDescriptor] {
+        unsafe { slice::from_raw_parts_mut(self.descriptors, self.descriptors_len as usize) }
+    }
 }
 
 
 #[repr(C)]
-#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
 pub struct MemoryDescriptor {
-    pub phys_start: PhysicalAddress,
-    pub phys_end: PhysicalAddress,
+    pub phys_start: PhysAddr,
+    pub phys_end: PhysAddr,
     pub num_pages: u64,
     pub r#type: MemoryType,
 }
@@ -47,6 +61,12 @@ impl MemoryDescriptor {
     pub fn size(&self) -> u64 {
         self.phys_end - self.phys_start
     }
+
+    /// Frames covered by the descriptor
+    pub fn frames(&self) -> PhysFrameRange {
+        let start = PhysFrame::containing_address(self.phys_start);
+        PhysFrame::range(start, start + self.num_pages)
+    }
 }
 
 impl Debug for MemoryDescriptor {
@@ -63,9 +83,10 @@ impl Display for MemoryDescriptor {
 }
 
 #[repr(u8)]
-#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy)]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy, Default)]
 pub enum MemoryType {
     Available = 0,
+    #[default]
     Reserved = 1,
     /// kernel code file
     KernelCode = 2,
@@ -73,4 +94,6 @@ pub enum MemoryType {
     KernelStack = 3,
     /// boot info, memory map
     KernelData = 4,
+    /// transient loader allocations (loader image, heap, kernel file buffer) that the kernel may reclaim once it no longer needs boot data
+    LoaderReclaimable = 5,
 }
@@ -0,0 +1,227 @@
+use core::{
+    fmt::{Debug, Formatter},
+    marker::PhantomData,
+    ops::{Add, AddAssign, Sub, SubAssign},
+};
+
+use crate::memory::address::{AddressError, PhysAddr, VirtAddr};
+
+/// Size of a page or frame, mapped by a page table entry of the corresponding level
+pub trait PageSize: Copy + Eq + Ord {
+    const SIZE: u64;
+    /// Used in debug output
+    const NAME: &'static str;
+}
+
+/// Mapped by a page table entry
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Size4KiB {}
+
+/// Mapped by a page directory entry with the page size bit set
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Size2MiB {}
+
+/// Mapped by a page directory pointer entry with the page size bit set
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Size1GiB {}
+
+impl PageSize for Size4KiB {
+    const SIZE: u64 = 0x1000;
+    const NAME: &'static str = "4KiB";
+}
+
+impl PageSize for Size2MiB {
+    const SIZE: u64 = 0x20_0000;
+    const NAME: &'static str = "2MiB";
+}
+
+impl PageSize for Size1GiB {
+    const SIZE: u64 = 0x4000_0000;
+    const NAME: &'static str = "1GiB";
+}
+
+/// Virtual memory page
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Page<S: PageSize = Size4KiB> {
+    start_address: VirtAddr,
+    _size: PhantomData<S>,
+}
+
+/// Physical memory frame
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PhysFrame<S: PageSize = Size4KiB> {
+    start_address: PhysAddr,
+    _size: PhantomData<S>,
+}
+
+/// Pages `start..end`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PageRange<S: PageSize = Size4KiB> {
+    pub start: Page<S>,
+    pub end: Page<S>,
+}
+
+/// Frames `start..end`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PhysFrameRange<S: PageSize = Size4KiB> {
+    pub start: PhysFrame<S>,
+    pub end: PhysFrame<S>,
+}
+
+macro_rules! impl_page {
+    ($page:ident, $range:ident, $address:ident) => {
+        impl<S: PageSize> $page<S> {
+            pub const SIZE: u64 = S::SIZE;
+
+            /// Returns an error, if `address` is not aligned to the page size
+            pub fn from_start_address(address: $address) -> Result<Self, AddressError> {
+                if address.is_aligned(S::SIZE) {
+                    Ok(Self::containing_address(address))
+                } else {
+                    Err(AddressError::Unaligned(address.as_u64()))
+                }
+            }
+
+            pub fn containing_address(address: $address) -> Self {
+                Self {
+                    start_address: address.align_down(S::SIZE),
+                    _size: PhantomData,
+                }
+            }
+
+            pub fn start_address(self) -> $address {
+                self.start_address
+            }
+
+            pub fn size(self) -> u64 {
+                S::SIZE
+            }
+
+            /// `start..end`
+            pub fn range(start: Self, end: Self) -> $range<S> {
+                $range { start, end }
+            }
+
+            /// `start..=end`, `end` must not be the last page of the address space
+            pub fn range_inclusive(start: Self, end: Self) -> $range<S> {
+                $range {
+                    start,
+                    end: end + 1,
+                }
+            }
+        }
+
+        impl<S: PageSize> Debug for $page<S> {
+            fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+                write!(
+                    f,
+                    "{}[{}]({:#x})",
+                    stringify!($page),
+                    S::NAME,
+                    self.start_address
+                )
+            }
+        }
+
+        /// Advances by `rhs` pages
+        impl<S: PageSize> Add<u64> for $page<S> {
+            type Output = Self;
+
+            fn add(self, rhs: u64) -> Self {
+                Self::containing_address(self.start_address + rhs * S::SIZE)
+            }
+        }
+
+        impl<S: PageSize> AddAssign<u64> for $page<S> {
+            fn add_assign(&mut self, rhs: u64) {
+                *self = *self + rhs;
+            }
+        }
+
+        impl<S: PageSize> Sub<u64> for $page<S> {
+            type Output = Self;
+
+            fn sub(self, rhs: u64) -> Self {
+                Self::containing_address(self.start_address - rhs * S::SIZE)
+            }
+        }
+
+        impl<S: PageSize> SubAssign<u64> for $page<S> {
+            fn sub_assign(&mut self, rhs: u64) {
+                *self = *self - rhs;
+            }
+        }
+
+        /// Number of pages in between
+        impl<S: PageSize> Sub for $page<S> {
+            type Output = u64;
+
+            fn sub(self, rhs: Self) -> u64 {
+                (self.start_address - rhs.start_address) / S::SIZE
+            }
+        }
+
+        impl<S: PageSize> $range<S> {
+            pub fn is_empty(&self) -> bool {
+                self.start >= self.end
+            }
+
+            /// Number of pages left in the range
+            pub fn len(&self) -> u64 {
+                if self.is_empty() {
+                    0
+                } else {
+                    self.end - self.start
+                }
+            }
+        }
+
+        impl<S: PageSize> Iterator for $range<S> {
+            type Item = $page<S>;
+
+            fn next(&mut self) -> Option<$page<S>> {
+                if self.is_empty() {
+                    return None;
+                }
+                let page = self.start;
+                self.start += 1;
+                Some(page)
+            }
+
+            fn size_hint(&self) -> (usize, Option<usize>) {
+                let len = self.len() as usize;
+                (len, Some(len))
+            }
+        }
+    };
+}
+
+impl_page!(Page, PageRange, VirtAddr);
+impl_page!(PhysFrame, PhysFrameRange, PhysAddr);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn iterates_page_ranges() {
+        let start = Page::<Size4KiB>::containing_address(VirtAddr::new(0xFFFF_8000_0000_1234));
+        assert_eq!(start.start_address(), VirtAddr::new(0xFFFF_8000_0000_1000));
+
+        let range = Page::range(start, start + 3);
+        assert_eq!(range.len(), 3);
+        assert!(range.map(|page| page.start_address().as_u64()).eq([
+            0xFFFF_8000_0000_1000,
+            0xFFFF_8000_0000_2000,
+            0xFFFF_8000_0000_3000
+        ]));
+
+        let frame = PhysFrame::<Size2MiB>::containing_address(PhysAddr::new(0x43_0000));
+        assert_eq!(frame.start_address(), PhysAddr::new(0x40_0000));
+        assert_eq!(PhysFrame::range_inclusive(frame, frame + 1).count(), 2);
+        assert_eq!(
+            PhysFrame::<Size1GiB>::from_start_address(PhysAddr::new(0x20_0000)),
+            Err(AddressError::Unaligned(0x20_0000))
+        );
+    }
+}
@@ -0,0 +1,434 @@
+//! Walks page tables to list and compare the mappings of address spaces, e.g. to debug the address space set up by the loader
+
+use core::fmt::{Display, Formatter};
+
+use crate::memory::{
+    address::{PhysAddr, VirtAddr},
+    paging::{pat::CacheType, PageEntry, PageEntryFlags, PageTable, PagingMode},
+};
+
+/// Bit of huge page entries selecting the PAT entry, as bit 7 is the page size bit
+const HUGE_PAGE_PAT: u64 = 1 << 12;
+/// Entries per page table
+const ENTRY_COUNT: usize = 512;
+
+/// Contiguous virtual range mapped to a contiguous physical range with the same attributes
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Mapping {
+    pub virtual_start: VirtAddr,
+    pub physical_start: PhysAddr,
+    /// Size in bytes
+    pub size: u64,
+    /// Writable according to all levels of the page table walk
+    pub writable: bool,
+    /// Not marked execute disable at any level of the page table walk
+    pub executable: bool,
+    /// Accessible by user mode according to all levels of the page table walk
+    pub user: bool,
+    pub cache_type: CacheType,
+}
+
+impl Mapping {
+    /// First virtual address past the mapping, wraps to zero at the end of the address space
+    pub fn virtual_end(&self) -> u64 {
+        self.virtual_start.as_u64().wrapping_add(self.size)
+    }
+
+    pub fn physical_end(&self) -> u64 {
+        self.physical_start.as_u64() + self.size
+    }
+
+    /// Whether `next` directly follows this mapping in both address spaces with the same attributes
+    pub fn is_continued_by(&self, next: &Mapping) -> bool {
+        self.virtual_end() == next.virtual_start.as_u64()
+            && self.physical_end() == next.physical_start.as_u64()
+            && (self.writable, self.executable, self.user, self.cache_type)
+                == (next.writable, next.executable, next.user, next.cache_type)
+    }
+
+    /// Splits off the first `size` bytes, returning them and the rest of the mapping, if any
+    fn split_at(self, size: u64) -> (Mapping, Option<Mapping>) {
+        if size >= self.size {
+            return (self, None);
+        }
+        let head = Mapping { size, ..self };
+        let tail = Mapping {
+            virtual_start: self.virtual_start + size,
+            physical_start: self.physical_start + size,
+            size: self.size - size,
+            ..self
+        };
+        (head, Some(tail))
+    }
+}
+
+/// `<virtual range> -> <physical range> <size> <R/W/X/U> <cache type>`, unset permissions are printed as `-`
+impl Display for Mapping {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        let permission = |set: bool, name: char| if set { name } else { '-' };
+        write!(
+            f,
+            "{:#018x}-{:#018x} -> {:#x}-{:#x} {} R{}{}{} {:?}",
+            self.virtual_start,
+            self.virtual_end(),
+            self.physical_start,
+            self.physical_end(),
+            ByteSize(self.size),
+            permission(self.writable, 'W'),
+            permission(self.executable, 'X'),
+            permission(self.user, 'U'),
+            self.cache_type,
+        )
+    }
+}
+
+/// Formats a size in the largest binary unit dividing it
+struct ByteSize(u64);
+
+impl Display for ByteSize {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        for (unit, shift) in [("GiB", 30), ("MiB", 20), ("KiB", 10)] {
+            if self.0 != 0 && self.0.trailing_zeros() >= shift {
+                return write!(f, "{} {unit}", self.0 >> shift);
+            }
+        }
+        write!(f, "{} B", self.0)
+    }
+}
+
+/// Present mappings of an address space in ascending virtual address order (lower half first), coalesced into contiguous ranges
+#[derive(Debug)]
+pub struct Mappings {
+    leaves: Leaves,
+    /// Leaf read ahead while coalescing the previous mapping
+    pending: Option<Mapping>,
+}
+
+impl Mappings {
+    /// # Safety
+    /// `top_level_table` (PML4, or PML5 with 5-level paging) and all tables referenced by it must be accessible at their
+    /// physical addresses and must not be modified while iterating.
+    pub unsafe fn new(top_level_table: *const PageTable, paging_mode: PagingMode) -> Self {
+        let mut tables = [core::ptr::null(); 5];
+        tables[0] = top_level_table;
+        Self {
+            leaves: Leaves {
+                paging_mode,
+                tables,
+                indices: [0; 5],
+                depth: 0,
+            },
+            pending: None,
+        }
+    }
+}
+
+impl Iterator for Mappings {
+    type Item = Mapping;
+
+    fn next(&mut self) -> Option<Mapping> {
+        let mut mapping = self.pending.take().or_else(|| self.leaves.next())?;
+        for next in self.leaves.by_ref() {
+            if !mapping.is_continued_by(&next) {
+                self.pending = Some(next);
+                break;
+            }
+            mapping.size += next.size;
+        }
+        Some(mapping)
+    }
+}
+
+/// Depth first walk yielding every present page (4 KiB, 2 MiB or 1 GiB) as a mapping of its own
+#[derive(Debug)]
+struct Leaves {
+    paging_mode: PagingMode,
+    /// Tables on the path to the current entry, starting at the top level table
+    tables: [*const PageTable; 5],
+    /// Index of the current entry in each of `tables`
+    indices: [usize; 5],
+    /// Index of the current table in `tables`
+    depth: usize,
+}
+
+impl Leaves {
+    /// Entry at `depth` on the path to the current entry
+    fn entry(&self, depth: usize) -> PageEntry {
+        unsafe { &*self.tables[depth] }.entries[self.indices[depth]]
+    }
+
+    /// Builds the mapping of the current entry, a page of the given level
+    fn leaf(&self, entry: PageEntry, level: usize) -> Mapping {
+        let path = (0..=self.depth).map(|depth| self.entry(depth).flags());
+        let (mut writable, mut executable, mut user) = (true, true, true);
+        for flags in path {
+            writable &= flags.contains(PageEntryFlags::READ_WRITE);
+            executable &= !flags.contains(PageEntryFlags::EXECUTE_DISABLE);
+            user &= flags.contains(PageEntryFlags::USER_SUPER);
+        }
+
+        let levels = self.paging_mode.levels();
+        let virtual_address = (0..=self.depth).fold(0, |address, depth| {
+            address | (self.indices[depth] as u64) << (12 + 9 * (levels - 1 - depth))
+        });
+        let size = 1 << (12 + 9 * (level - 1));
+
+        // the PAT bit of huge pages is located in the address field
+        let mut flags = entry.flags();
+        if level > 1 {
+            flags.set(
+                PageEntryFlags::PAT_PAGE_SIZE,
+                entry.address().as_u64() & HUGE_PAGE_PAT != 0,
+            );
+        }
+
+        Mapping {
+            virtual_start: VirtAddr::new_truncate(virtual_address, self.paging_mode),
+            physical_start: entry.address().align_down(size),
+            size,
+            writable,
+            executable,
+            user,
+            cache_type: flags.cache_type(),
+        }
+    }
+}
+
+impl Iterator for Leaves {
+    type Item = Mapping;
+
+    fn next(&mut self) -> Option<Mapping> {
+        loop {
+            let depth = self.depth;
+            if self.indices[depth] == ENTRY_COUNT {
+                if depth == 0 {
+                    return None;
+                }
+                // continue with the entry after the table just walked
+                self.depth -= 1;
+                self.indices[self.depth] += 1;
+                continue;
+            }
+
+            let entry = self.entry(depth);
+            let flags = entry.flags();
+            if !flags.contains(PageEntryFlags::PRESENT) {
+                self.indices[depth] += 1;
+                continue;
+            }
+
+            // page directory pointer and page directory entries map 1 GiB and 2 MiB pages with the page size bit set
+            let level = self.paging_mode.levels() - depth;
+            let huge = (2..=3).contains(&level) && flags.contains(PageEntryFlags::PAT_PAGE_SIZE);
+            if level > 1 && !huge {
+                self.depth += 1;
+                self.tables[self.depth] = entry.address().as_u64() as *const PageTable;
+                self.indices[self.depth] = 0;
+                continue;
+            }
+
+            let mapping = self.leaf(entry, level);
+            self.indices[depth] += 1;
+            return Some(mapping);
+        }
+    }
+}
+
+/// Difference between two address spaces for a virtual range
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MappingDiff {
+    /// Mapped only in the new address space
+    Added(Mapping),
+    /// Mapped only in the old address space
+    Removed(Mapping),
+    /// Mapped to another physical range or with other attributes
+    Changed { old: Mapping, new: Mapping },
+}
+
+/// `+ <mapping>`, `- <mapping>` or `~ <old mapping> => <new mapping>`
+impl Display for MappingDiff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Added(mapping) => write!(f, "+ {mapping}"),
+            Self::Removed(mapping) => write!(f, "- {mapping}"),
+            Self::Changed { old, new } => write!(f, "~ {old} => {new}"),
+        }
+    }
+}
+
+/// Compares two address spaces given by their mappings in ascending virtual address order, e.g. [`Mappings`]. Ranges mapped
+/// identically in both are skipped, all other ranges are reported in ascending order, split where the mappings of either
+/// address space begin or end.
+pub fn diff<O, N>(old: O, new: N) -> MappingDiffs<O::IntoIter, N::IntoIter>
+where
+    O: IntoIterator<Item = Mapping>,
+    N: IntoIterator<Item = Mapping>,
+{
+    MappingDiffs {
+        old_mappings: old.into_iter(),
+        new_mappings: new.into_iter(),
+        old: None,
+        new: None,
+    }
+}
+
+/// Iterator returned by [`diff`]
+#[derive(Debug)]
+pub struct MappingDiffs<O, N> {
+    old_mappings: O,
+    new_mappings: N,
+    /// Remaining part of the current old mapping
+    old: Option<Mapping>,
+    /// Remaining part of the current new mapping
+    new: Option<Mapping>,
+}
+
+impl<O: Iterator<Item = Mapping>, N: Iterator<Item = Mapping>> Iterator for MappingDiffs<O, N> {
+    type Item = MappingDiff;
+
+    fn next(&mut self) -> Option<MappingDiff> {
+        loop {
+            if self.old.is_none() {
+                self.old = self.old_mappings.next();
+            }
+            if self.new.is_none() {
+                self.new = self.new_mappings.next();
+            }
+
+            let (old, new) = match (self.old, self.new) {
+                (None, None) => return None,
+                (Some(old), None) => {
+                    self.old = None;
+                    return Some(MappingDiff::Removed(old));
+                }
+                (None, Some(new)) => {
+                    self.new = None;
+                    return Some(MappingDiff::Added(new));
+                }
+                (Some(old), Some(new)) => (old, new),
+            };
+
+            // the part of one mapping below the start of the other is only mapped in one address space
+            let (old_start, new_start) = (old.virtual_start, new.virtual_start);
+            if old_start < new_start {
+                let (head, tail) = old.split_at(new_start - old_start);
+                self.old = tail;
+                return Some(MappingDiff::Removed(head));
+            }
+            if new_start < old_start {
+                let (head, tail) = new.split_at(old_start - new_start);
+                self.new = tail;
+                return Some(MappingDiff::Added(head));
+            }
+
+            let size = old.size.min(new.size);
+            let (old, old_tail) = old.split_at(size);
+            let (new, new_tail) = new.split_at(size);
+            self.old = old_tail;
+            self.new = new_tail;
+            if old != new {
+                return Some(MappingDiff::Changed { old, new });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{format, vec::Vec};
+
+    use crate::memory::{
+        page::{Page, PhysFrame},
+        paging::manager::{tests::TestAllocator, PageTableManager},
+    };
+
+    use super::*;
+
+    fn mapping(virtual_start: u64, physical_start: u64, size: u64) -> Mapping {
+        Mapping {
+            virtual_start: VirtAddr::new(virtual_start),
+            physical_start: PhysAddr::new(physical_start),
+            size,
+            writable: true,
+            executable: true,
+            user: false,
+            cache_type: CacheType::WriteBack,
+        }
+    }
+
+    #[test]
+    fn coalesces_contiguous_pages() {
+        let mut manager = PageTableManager::new(TestAllocator::table(), TestAllocator(Vec::new()));
+        let mut map = |virtual_address: u64, physical_address: u64, flags: PageEntryFlags| {
+            manager
+                .map_memory(
+                    Page::containing_address(VirtAddr::new(virtual_address)),
+                    PhysFrame::containing_address(PhysAddr::new(physical_address)),
+                    flags,
+                )
+                .unwrap()
+        };
+        for page in 0..4 {
+            map(
+                0xFFFF_8000_0000_0000 + page * 0x1000,
+                0x10_0000 + page * 0x1000,
+                PageEntryFlags::default(),
+            );
+        }
+        map(0xFFFF_8000_0000_4000, 0x20_0000, PageEntryFlags::default());
+        let write_combining = PageEntryFlags::default().with_cache_type(CacheType::WriteCombining);
+        map(0xFFFF_8000_0000_5000, 0x20_1000, write_combining);
+        map(0x1000, 0x1000, PageEntryFlags::PRESENT);
+
+        let mappings: Vec<Mapping> = manager.mappings().collect();
+        assert_eq!(
+            mappings,
+            [
+                Mapping {
+                    writable: false,
+                    ..mapping(0x1000, 0x1000, 0x1000)
+                },
+                mapping(0xFFFF_8000_0000_0000, 0x10_0000, 0x4000),
+                mapping(0xFFFF_8000_0000_4000, 0x20_0000, 0x1000),
+                Mapping {
+                    cache_type: CacheType::WriteCombining,
+                    ..mapping(0xFFFF_8000_0000_5000, 0x20_1000, 0x1000)
+                },
+            ]
+        );
+        assert_eq!(
+            format!("{}", mappings[1]),
+            "0xffff800000000000-0xffff800000004000 -> 0x100000-0x104000 16 KiB RWX- WriteBack"
+        );
+
+        manager.frame_allocator().free();
+    }
+
+    #[test]
+    fn diffs_address_spaces() {
+        let old = [
+            mapping(0x1000, 0x10_1000, 0x4000),
+            mapping(0x10_0000, 0x10_0000, 0x1000),
+        ];
+        let new = [
+            mapping(0x3000, 0x10_3000, 0x4000),
+            Mapping {
+                executable: false,
+                ..mapping(0x10_0000, 0x10_0000, 0x1000)
+            },
+        ];
+
+        let diffs: Vec<MappingDiff> = diff(old, new).collect();
+        assert_eq!(
+            diffs,
+            [
+                MappingDiff::Removed(mapping(0x1000, 0x10_1000, 0x2000)),
+                MappingDiff::Added(mapping(0x5000, 0x10_5000, 0x2000)),
+                MappingDiff::Changed {
+                    old: old[1],
+                    new: new[1],
+                },
+            ]
+        );
+    }
+}
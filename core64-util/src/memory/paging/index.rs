@@ -1,8 +1,9 @@
-use crate::memory::VirtualAddress;
+use crate::memory::address::VirtAddr;
 
 /// Used to convert virtual address to page map indices
 #[derive(Copy, Clone, Debug)]
 pub struct PageMapIndexer {
+    page_map_level5_index: u64,        // level 5
     page_directory_pointer_index: u64, // level 4
     page_directory_index: u64,         // level 3
     page_table_index: u64,             // level 2
@@ -10,8 +11,8 @@ pub struct PageMapIndexer {
 }
 
 impl PageMapIndexer {
-    pub fn new(mut virtual_address: VirtualAddress) -> Self {
-        virtual_address >>= 12;
+    pub fn new(virtual_address: VirtAddr) -> Self {
+        let mut virtual_address = virtual_address.as_u64() >> 12;
         let page_index = virtual_address & 0x1ff;
         virtual_address >>= 9;
         let page_table_index = virtual_address & 0x1ff;
@@ -19,8 +20,11 @@ impl PageMapIndexer {
         let page_directory_index = virtual_address & 0x1ff;
         virtual_address >>= 9;
         let page_directory_pointer_index = virtual_address & 0x1ff;
+        virtual_address >>= 9;
+        let page_map_level5_index = virtual_address & 0x1ff;
 
         Self {
+            page_map_level5_index,
             page_directory_pointer_index,
             page_directory_index,
             page_table_index,
@@ -46,4 +50,35 @@ impl PageMapIndexer {
     pub fn pdp_i(&self) -> u64 {
         self.page_directory_pointer_index
     }
+
+    /// Returns Page Map Level 5 Index, only used with 5-level paging
+    pub fn pml5_i(&self) -> u64 {
+        self.page_map_level5_index
+    }
+
+    /// Returns the index into the page table of the given level, from 1 (page index) to 5 (page map level 5 index)
+    pub fn index(&self, level: usize) -> u64 {
+        match level {
+            1 => self.p_i(),
+            2 => self.pt_i(),
+            3 => self.pd_i(),
+            4 => self.pdp_i(),
+            5 => self.pml5_i(),
+            _ => panic!("invalid page table level {level}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn indexes_all_levels() {
+        let indexer = PageMapIndexer::new(VirtAddr::new(0xFF23_4567_89AB_CDEF));
+        let expected = [0x0BC, 0x04D, 0x19E, 0x08A, 0x123];
+        for (level, index) in (1..=5).zip(expected) {
+            assert_eq!(indexer.index(level), index, "level {level}");
+        }
+    }
 }
@@ -1,64 +1,130 @@
 use core::marker::PhantomData;
 
-use crate::memory::{PhysicalAddress, VirtualAddress};
-use crate::memory::paging::{PageEntryFlags, PageTable};
+use crate::memory::address::{PhysAddr, VirtAddr};
+use crate::memory::page::{Page, PageSize, PhysFrame, Size4KiB};
 use crate::memory::paging::index::PageMapIndexer;
+use crate::memory::paging::pat::CacheType;
+use crate::memory::paging::{dump::Mappings, PageEntryFlags, PageTable, PagingMode};
 
 pub trait PageFrameAllocator<'a, E> {
-    fn request_page(&mut self) -> Result<PhysicalAddress, E>;
+    fn request_page(&mut self) -> Result<PhysFrame, E>;
 }
 
 /// Manages page tables
 #[derive(Debug)]
 pub struct PageTableManager<A, E> {
+    /// PML4, or PML5 with 5-level paging
     page_map_level4: *mut PageTable,
+    paging_mode: PagingMode,
     page_frame_allocator: A,
     _marker: PhantomData<E>,
 }
 
 impl<'a, A: PageFrameAllocator<'a, E>, E> PageTableManager<A, E> {
+    /// Manages 4-level page tables
     pub fn new(page_map_level4: *mut PageTable, page_frame_allocator: A) -> Self {
+        Self::with_paging_mode(page_map_level4, PagingMode::FourLevel, page_frame_allocator)
+    }
+
+    /// Manages page tables of the given paging mode, `top_level_table` is the PML4 or PML5
+    pub fn with_paging_mode(
+        top_level_table: *mut PageTable,
+        paging_mode: PagingMode,
+        page_frame_allocator: A,
+    ) -> Self {
         Self {
-            page_map_level4,
+            page_map_level4: top_level_table,
+            paging_mode,
             page_frame_allocator,
             _marker: PhantomData,
         }
     }
 
+    /// Top level table, i.e. the PML4 or PML5 with 5-level paging
     pub fn pml4(&self) -> *mut PageTable {
         self.page_map_level4
     }
 
-    /// Maps given virtual address to physical address
+    pub fn paging_mode(&self) -> PagingMode {
+        self.paging_mode
+    }
+
+    /// Maps given page to physical frame
     pub fn map_memory(
         &mut self,
-        virtual_memory: VirtualAddress,
-        physical_memory: PhysicalAddress,
-        flags: PageEntryFlags
+        page: Page,
+        frame: PhysFrame,
+        flags: PageEntryFlags,
     ) -> Result<(), E> {
-        let indexer = PageMapIndexer::new(virtual_memory);
-        let page_map_level4 = self.page_map_level4;
+        let indexer = PageMapIndexer::new(page.start_address());
 
-        // Map Level 3
-        let page_map_level3 =
-            self.get_or_create_next_table(page_map_level4, indexer.pdp_i())?;
-        // Map Level 2
-        let page_map_level2 = self.get_or_create_next_table(page_map_level3, indexer.pd_i())?;
-        // Map Level 1
-        let page_map_level1 = self.get_or_create_next_table(page_map_level2, indexer.pt_i())?;
+        // walk down to the page table (level 1), creating missing tables
+        let mut table = self.page_map_level4;
+        for level in (2..=self.paging_mode.levels()).rev() {
+            table = self.get_or_create_next_table(table, indexer.index(level))?;
+        }
 
-        let page_entry = &mut unsafe { &mut *page_map_level1 }.entries[indexer.p_i() as usize];
+        let page_entry = &mut unsafe { &mut *table }.entries[indexer.p_i() as usize];
 
-        page_entry.set_address(physical_memory);
+        page_entry.set_address(frame.start_address());
         page_entry.set_flags(flags);
 
         Ok(())
     }
 
+    /// Maps the memory mapped IO range `physical_memory..physical_memory + size` uncached starting at `page`.
+    /// Returns the virtual address of `physical_memory`. Stale TLB entries of previously mapped pages are not flushed.
+    pub fn map_mmio(
+        &mut self,
+        page: Page,
+        physical_memory: PhysAddr,
+        size: usize,
+    ) -> Result<VirtAddr, E> {
+        let frame = PhysFrame::containing_address(physical_memory);
+        let offset = physical_memory - frame.start_address();
+        let page_count = (offset + size as u64).div_ceil(Size4KiB::SIZE);
+        let flags = PageEntryFlags::default().with_cache_type(CacheType::Uncacheable);
+        let pages = Page::range(page, page + page_count);
+        for (page, frame) in pages.zip(PhysFrame::range(frame, frame + page_count)) {
+            self.map_memory(page, frame, flags)?;
+        }
+        Ok(page.start_address() + offset)
+    }
+
+    /// Returns physical address the given virtual address is mapped to, if it is mapped
+    pub fn translate(&self, virtual_memory: VirtAddr) -> Option<PhysAddr> {
+        let indexer = PageMapIndexer::new(virtual_memory);
+
+        let mut table = self.page_map_level4;
+        for level in (2..=self.paging_mode.levels()).rev() {
+            table = Self::next_table(table, indexer.index(level))?;
+        }
+
+        let page_entry = unsafe { &*table }.entries[indexer.p_i() as usize];
+        page_entry
+            .flags()
+            .contains(PageEntryFlags::PRESENT)
+            .then(|| {
+                page_entry.address() + (virtual_memory - virtual_memory.align_down(Size4KiB::SIZE))
+            })
+    }
+
+    /// Present mappings coalesced into contiguous ranges, see [`Mappings`]
+    pub fn mappings(&self) -> Mappings {
+        unsafe { Mappings::new(self.page_map_level4, self.paging_mode) }
+    }
+
     pub fn frame_allocator(&mut self) -> &mut A {
         &mut self.page_frame_allocator
     }
 
+    fn next_table(current_table: *mut PageTable, index: u64) -> Option<*mut PageTable> {
+        let entry = unsafe { &*current_table }.entries[index as usize];
+        entry
+            .flags()
+            .contains(PageEntryFlags::PRESENT)
+            .then(|| entry.address().as_u64() as *mut PageTable)
+    }
 
     fn get_or_create_next_table(
         &mut self,
@@ -68,19 +134,124 @@ impl<'a, A: PageFrameAllocator<'a, E>, E> PageTableManager<A, E> {
         let entry = &mut unsafe { &mut *current_table }.entries[index as usize];
 
         if entry.flags().contains(PageEntryFlags::PRESENT) {
-            Ok(entry.address() as *mut PageTable)
+            Ok(entry.address().as_u64() as *mut PageTable)
         } else {
-            let new_page = self.page_frame_allocator.request_page()?;
-            let new_table = new_page as *mut PageTable;
+            let new_frame = self.page_frame_allocator.request_page()?;
+            let new_table = new_frame.start_address().as_u64() as *mut PageTable;
             unsafe {
                 // Zero out the new table
                 core::ptr::write_bytes(new_table, 0, 1);
             }
 
-            entry.set_address(new_page);
+            entry.set_address(new_frame.start_address());
             entry.set_flags(PageEntryFlags::PRESENT | PageEntryFlags::READ_WRITE);
 
             Ok(new_table)
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use alloc::{boxed::Box, vec::Vec};
+
+    use crate::memory::paging::PageEntry;
+
+    use super::*;
+
+    /// Hands out leaked page tables, whose addresses double as physical addresses
+    pub(in crate::memory::paging) struct TestAllocator(
+        pub(in crate::memory::paging) Vec<*mut PageTable>,
+    );
+
+    impl TestAllocator {
+        /// Leaks an empty top level table, which is freed together with the allocated tables
+        pub(in crate::memory::paging) fn table() -> *mut PageTable {
+            Box::into_raw(Box::new(PageTable {
+                entries: [PageEntry::new(PhysAddr::ZERO, PageEntryFlags::empty()); 512],
+            }))
+        }
+
+        pub(in crate::memory::paging) fn free(&mut self) {
+            for table in self.0.drain(..) {
+                drop(unsafe { Box::from_raw(table) });
+            }
+        }
+    }
+
+    impl PageFrameAllocator<'_, ()> for TestAllocator {
+        fn request_page(&mut self) -> Result<PhysFrame, ()> {
+            let table = Self::table();
+            self.0.push(table);
+            Ok(PhysFrame::containing_address(PhysAddr::new(table as u64)))
+        }
+    }
+
+    fn table(frame: PhysFrame) -> *mut PageTable {
+        frame.start_address().as_u64() as *mut PageTable
+    }
+
+    fn page(address: u64) -> Page {
+        Page::containing_address(VirtAddr::new(address))
+    }
+
+    fn virt(address: u64) -> VirtAddr {
+        VirtAddr::new(address)
+    }
+
+    fn phys(address: u64) -> Option<PhysAddr> {
+        Some(PhysAddr::new(address))
+    }
+
+    #[test]
+    fn maps_mmio_uncached() {
+        let mut allocator = TestAllocator(Vec::new());
+        let pml4 = table(allocator.request_page().unwrap());
+        let mut manager = PageTableManager::new(pml4, allocator);
+
+        let address = manager
+            .map_mmio(
+                page(0xFFFF_C000_0000_0000),
+                PhysAddr::new(0xFEE0_0F00),
+                0x200,
+            )
+            .unwrap();
+        assert_eq!(address, virt(0xFFFF_C000_0000_0F00));
+        assert_eq!(
+            manager.translate(virt(0xFFFF_C000_0000_0F00)),
+            phys(0xFEE0_0F00)
+        );
+        assert_eq!(
+            manager.translate(virt(0xFFFF_C000_0000_1000)),
+            phys(0xFEE0_1000)
+        );
+        assert_eq!(manager.translate(virt(0xFFFF_C000_0000_2000)), None);
+
+        manager.frame_allocator().free();
+    }
+
+    #[test]
+    fn walks_five_levels() {
+        let mut allocator = TestAllocator(Vec::new());
+        let pml5 = table(allocator.request_page().unwrap());
+        let mut manager =
+            PageTableManager::with_paging_mode(pml5, PagingMode::FiveLevel, allocator);
+
+        manager
+            .map_memory(
+                page(0xFF12_3456_7890_0000),
+                PhysFrame::containing_address(PhysAddr::new(0x20_0000)),
+                PageEntryFlags::default(),
+            )
+            .unwrap();
+        assert_eq!(manager.frame_allocator().0.len(), 5);
+        assert_eq!(
+            manager.translate(virt(0xFF12_3456_7890_0123)),
+            phys(0x20_0123)
+        );
+        // differs only in the PML5 index
+        assert_eq!(manager.translate(virt(0xFF02_3456_7890_0000)), None);
+
+        manager.frame_allocator().free();
+    }
+}
@@ -1,10 +1,88 @@
+use core::arch::asm;
+
 use bitflags::bitflags;
 
+use crate::memory::{
+    address::PhysAddr,
+    paging::pat::{CacheType, PAT_ENTRIES},
+    VirtualAddress,
+};
+
+pub mod dump;
 pub mod index;
 pub mod manager;
+pub mod pat;
 
+/// Lowest canonical higher half address with 4-level paging. Also canonical with 5-level paging.
+pub const HIGHER_HALF_START: u64 = 0xFFFF_8000_0000_0000;
 pub const KERNEL_MAPPING_OFFSET: u64 = 0xFFFF_FFFF_8000_0000;
 pub const KERNEL_STACK_MAPPING_OFFSET: u64 = 0xFFFF_FFFF_6000_0000;
+/// Additional boot data pages (e.g. entropy seed) are mapped at this offset plus their physical address
+pub const BOOT_DATA_MAPPING_OFFSET: u64 = 0xFFFF_9000_0000_0000;
+/// The framebuffer is mapped write combining at this address plus the offset of its physical base into the first page
+pub const FRAMEBUFFER_MAPPING_ADDRESS: u64 = 0xFFFF_A000_0000_0000;
+
+/// CR4 bit enabling 5-level paging
+const CR4_LA57: u64 = 1 << 12;
+/// Bits of a page entry holding [`PageEntryFlags`], the lower 12 bits and execute disable
+const PAGE_ENTRY_FLAGS_MASK: u64 = 0xfff | PageEntryFlags::EXECUTE_DISABLE.bits();
+
+/// Number of page table levels used for address translation
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum PagingMode {
+    /// 48-bit virtual addresses, the top level table is the PML4
+    #[default]
+    FourLevel,
+    /// 57-bit virtual addresses (LA57), the top level table is the PML5
+    FiveLevel,
+}
+
+impl PagingMode {
+    /// Paging mode of the current processor
+    ///
+    /// # Safety
+    /// Must run in ring 0 with paging enabled.
+    pub unsafe fn current() -> Self {
+        let cr4: u64;
+        asm!("mov {}, cr4", out(reg) cr4, options(nomem, nostack, preserves_flags));
+        if cr4 & CR4_LA57 != 0 {
+            Self::FiveLevel
+        } else {
+            Self::FourLevel
+        }
+    }
+
+    pub const fn levels(self) -> usize {
+        match self {
+            Self::FourLevel => 4,
+            Self::FiveLevel => 5,
+        }
+    }
+
+    /// Number of significant virtual address bits
+    pub const fn virtual_address_bits(self) -> u32 {
+        match self {
+            Self::FourLevel => 48,
+            Self::FiveLevel => 57,
+        }
+    }
+
+    /// Lowest canonical higher half address
+    pub fn higher_half_start(self) -> VirtualAddress {
+        !0 << (self.virtual_address_bits() - 1)
+    }
+
+    /// Whether all bits above the significant bits equal the highest significant bit
+    pub fn is_canonical(self, address: VirtualAddress) -> bool {
+        self.canonicalize(address) == address
+    }
+
+    /// Sign extends the highest significant bit of `address`
+    pub fn canonicalize(self, address: VirtualAddress) -> VirtualAddress {
+        let shift = u64::BITS - self.virtual_address_bits();
+        (((address << shift) as i64) >> shift) as u64
+    }
+}
 
 bitflags! {
     #[derive(Copy, Clone, Debug)]
@@ -53,6 +131,37 @@ impl PageEntryFlags {
     pub fn default_nx() -> Self {
         PageEntryFlags::PRESENT | PageEntryFlags::READ_WRITE | PageEntryFlags::EXECUTE_DISABLE
     }
+
+    /// Index of the PAT entry selected by the PAT, PCD and PWT bits of a page table entry (4 KiB page)
+    pub fn pat_index(self) -> usize {
+        (self.contains(Self::PAT_PAGE_SIZE) as usize) << 2
+            | (self.contains(Self::CACHE_DISABLED) as usize) << 1
+            | self.contains(Self::WRITE_THROUGH) as usize
+    }
+
+    /// Selects the PAT entry `index` in a page table entry (4 KiB page)
+    pub fn with_pat_index(self, index: usize) -> Self {
+        let mut flags = self - (Self::PAT_PAGE_SIZE | Self::CACHE_DISABLED | Self::WRITE_THROUGH);
+        flags.set(Self::PAT_PAGE_SIZE, index & 0b100 != 0);
+        flags.set(Self::CACHE_DISABLED, index & 0b010 != 0);
+        flags.set(Self::WRITE_THROUGH, index & 0b001 != 0);
+        flags
+    }
+
+    /// Memory type of a page table entry (4 KiB page), once [`PAT_ENTRIES`] have been loaded
+    pub fn cache_type(self) -> CacheType {
+        PAT_ENTRIES[self.pat_index()]
+    }
+
+    /// Selects the first PAT entry of [`PAT_ENTRIES`] with the given memory type in a page table entry (4 KiB page).
+    /// Entries 4 to 7 require [`pat::load`]. Memory types missing from the table select uncacheable.
+    pub fn with_cache_type(self, cache_type: CacheType) -> Self {
+        let index = PAT_ENTRIES
+            .iter()
+            .position(|&entry| entry == cache_type)
+            .unwrap_or(PAT_ENTRIES.len() - 1);
+        self.with_pat_index(index)
+    }
 }
 
 /// Page Directory or Page Table
@@ -62,32 +171,32 @@ pub struct PageEntry(u64);
 
 impl PageEntry {
     /// Create new page entry based on address and flags
-    pub fn new(address: u64, flags: PageEntryFlags) -> Self {
-        let address_shifted = address & 0x000f_ffff_ffff_f000;
+    pub fn new(address: PhysAddr, flags: PageEntryFlags) -> Self {
+        let address_shifted = address.as_u64() & 0x000f_ffff_ffff_f000;
         let flags_bits = flags.bits();
         PageEntry(address_shifted | flags_bits)
     }
 
     /// Set address of page entry
-    pub fn set_address(&mut self, address: u64) {
-        let address = address & 0x000f_ffff_ffff_f000;
-        self.0 = (self.0 & 0xfff) | address;
+    pub fn set_address(&mut self, address: PhysAddr) {
+        let address = address.as_u64() & 0x000f_ffff_ffff_f000;
+        self.0 = (self.0 & PAGE_ENTRY_FLAGS_MASK) | address;
     }
 
     /// Set flags of page entry
     pub fn set_flags(&mut self, flags: PageEntryFlags) {
-        let flags_bits = flags.bits() & 0xfff; // only use lower 12 bits
-        self.0 = (self.0 & !0xfff) | flags_bits;
+        let flags_bits = flags.bits() & PAGE_ENTRY_FLAGS_MASK;
+        self.0 = (self.0 & !PAGE_ENTRY_FLAGS_MASK) | flags_bits;
     }
 
     /// Get address of page entry
-    pub fn address(&self) -> u64 {
-        self.0 & 0x000f_ffff_ffff_f000
+    pub fn address(&self) -> PhysAddr {
+        PhysAddr::new(self.0 & 0x000f_ffff_ffff_f000)
     }
 
-    /// Get address of page entry
+    /// Get flags of page entry, the lower 12 bits and execute disable
     pub fn flags(&self) -> PageEntryFlags {
-        PageEntryFlags::from_bits_truncate(self.0 & 0xfff) // Mask to get only the lower 12 bits for flags
+        PageEntryFlags::from_bits_truncate(self.0 & PAGE_ENTRY_FLAGS_MASK)
     }
 }
 
@@ -96,3 +205,48 @@ impl PageEntry {
 pub struct PageTable {
     pub entries: [PageEntry; 512],
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checks_canonical_addresses() {
+        let four_level = PagingMode::FourLevel;
+        let five_level = PagingMode::FiveLevel;
+        assert_eq!(four_level.higher_half_start(), HIGHER_HALF_START);
+        assert_eq!(five_level.higher_half_start(), 0xFF00_0000_0000_0000);
+
+        assert!(four_level.is_canonical(0x0000_7FFF_FFFF_FFFF));
+        assert!(!four_level.is_canonical(0x0000_8000_0000_0000));
+        assert!(five_level.is_canonical(0x0000_8000_0000_0000));
+        assert!(!five_level.is_canonical(0x0100_0000_0000_0000));
+        assert_eq!(
+            four_level.canonicalize(0x0000_8000_0000_0000),
+            HIGHER_HALF_START
+        );
+        assert_eq!(
+            five_level.canonicalize(0x01FF_8000_0000_0000),
+            0xFFFF_8000_0000_0000
+        );
+    }
+
+    #[test]
+    fn round_trips_page_entry_flags() {
+        let address = PhysAddr::new(0x0008_0000_1234_5000);
+        let flags = PageEntryFlags::default_nx().with_cache_type(CacheType::WriteCombining);
+        let mut entry = PageEntry::new(address, PageEntryFlags::default());
+
+        entry.set_flags(flags);
+        assert_eq!(entry.flags().bits(), flags.bits());
+        entry.set_flags(entry.flags());
+        assert_eq!(entry.flags().bits(), flags.bits());
+        entry.set_address(PhysAddr::new(0x2000));
+        assert_eq!(entry.flags().bits(), flags.bits());
+        assert_eq!(entry.address(), PhysAddr::new(0x2000));
+
+        entry.set_flags(PageEntryFlags::default());
+        assert_eq!(entry.flags().bits(), PageEntryFlags::default().bits());
+        assert_eq!(entry.address(), PhysAddr::new(0x2000));
+    }
+}
@@ -0,0 +1,88 @@
+//! Page attribute table (PAT): selects the memory type of a page by the PAT, PCD and PWT bits of its page table entry
+
+use core::arch::{asm, x86_64::__cpuid};
+
+/// Model specific register holding the page attribute table
+const IA32_PAT: u32 = 0x277;
+/// CPUID leaf 1 EDX bit reporting PAT support
+const CPUID_PAT: u32 = 1 << 16;
+
+/// Memory type of a PAT entry
+#[repr(u8)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CacheType {
+    Uncacheable = 0,
+    WriteCombining = 1,
+    WriteThrough = 4,
+    WriteProtected = 5,
+    WriteBack = 6,
+    /// Uncacheable, unless overridden by a write combining MTRR
+    UncachedMinus = 7,
+}
+
+/// Entries programmed by [`load`]. Entries 0 to 3 keep their power-on default, so that page tables not setting the PAT bit
+/// behave as without PAT. Entry 4 selects write combining.
+pub const PAT_ENTRIES: [CacheType; 8] = [
+    CacheType::WriteBack,
+    CacheType::WriteThrough,
+    CacheType::UncachedMinus,
+    CacheType::Uncacheable,
+    CacheType::WriteCombining,
+    CacheType::WriteThrough,
+    CacheType::UncachedMinus,
+    CacheType::Uncacheable,
+];
+
+/// Value of the PAT MSR with [`PAT_ENTRIES`]
+pub const fn pat_value() -> u64 {
+    let mut value = 0;
+    let mut index = 0;
+    while index < PAT_ENTRIES.len() {
+        value |= (PAT_ENTRIES[index] as u64) << (index * 8);
+        index += 1;
+    }
+    value
+}
+
+/// Whether the processor supports the page attribute table
+pub fn is_supported() -> bool {
+    __cpuid(1).edx & CPUID_PAT != 0
+}
+
+/// Programs [`PAT_ENTRIES`] into the PAT MSR of the current processor and flushes its caches. Every processor has to load the
+/// PAT before using page tables that select entries 4 to 7.
+///
+/// # Safety
+/// The processor must support PAT and run in ring 0. Existing mappings selecting entries 4 to 7 change their memory type.
+pub unsafe fn load() {
+    let value = pat_value();
+    asm!(
+        "wrmsr",
+        "wbinvd",
+        in("ecx") IA32_PAT,
+        in("eax") value as u32,
+        in("edx") (value >> 32) as u32,
+        options(nostack, preserves_flags),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::memory::paging::PageEntryFlags;
+
+    use super::*;
+
+    #[test]
+    fn selects_pat_entries() {
+        assert_eq!(pat_value(), 0x0007_0401_0007_0406);
+
+        let flags = PageEntryFlags::default().with_cache_type(CacheType::WriteCombining);
+        assert_eq!(
+            flags.bits(),
+            (PageEntryFlags::default() | PageEntryFlags::PAT_PAGE_SIZE).bits()
+        );
+        assert_eq!(flags.cache_type(), CacheType::WriteCombining);
+        assert_eq!(flags.with_cache_type(CacheType::Uncacheable).pat_index(), 3);
+        assert_eq!(PageEntryFlags::default().cache_type(), CacheType::WriteBack);
+    }
+}
@@ -37,7 +37,8 @@ impl<'a> BitMap<'a> {
         Ok(())
     }
 
+    /// Amount of pages the bitmap buffer occupies
     pub fn pages(&self) -> usize {
-        (size_of::<BitMap>() + PAGE_SIZE - 1) / PAGE_SIZE
+        self.buffer.len().div_ceil(PAGE_SIZE)
     }
 }
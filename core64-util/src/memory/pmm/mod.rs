@@ -2,13 +2,15 @@ use core::{
     error::Error,
     fmt::{Display, Formatter},
     ptr::slice_from_raw_parts_mut,
-    write,
+    slice, write,
 };
 
 use crate::memory::{
-    MemoryMap,
-    MemoryType,
-    PAGE_SIZE, paging::manager::PageFrameAllocator, PhysicalAddress, pmm::bit_map::BitMap,
+    address::{PhysAddr, VirtAddr},
+    page::{PhysFrame, PhysFrameRange},
+    paging::manager::PageFrameAllocator,
+    pmm::bit_map::BitMap,
+    MemoryMap, MemoryType, PAGE_SIZE,
 };
 
 pub mod bit_map;
@@ -18,7 +20,7 @@ pub struct BitMapAllocator<'a> {
     memory_map: MemoryMap,
     bit_map: BitMap<'a>,
     current_descriptor_index: usize,
-    current_address: PhysicalAddress,
+    current_address: PhysAddr,
     free_memory: u64,
     used_memory: u64,
     reserved_memory: u64,
@@ -36,7 +38,7 @@ impl BitMapAllocator<'_> {
             .filter(|area| area.r#type == MemoryType::Available)
             .max_by(|a, b| a.size().cmp(&b.size()))
             .ok_or(PageFrameAllocatorError::InvalidMemoryMap)?;
-        let largest_memory_area_ptr = largest_memory_area.phys_start as *mut u8;
+        let largest_memory_area_ptr = largest_memory_area.phys_start.as_u64() as *mut u8;
 
         // total memory size in bytes => / PAGE_SIZE is the amount of pages. In the bitmap each page is one bit => /8 gives out the amount of bits
         let total_pages = (memory_map.last_addr as usize + PAGE_SIZE - 1) / PAGE_SIZE;
@@ -60,30 +62,63 @@ impl BitMapAllocator<'_> {
             memory_map,
             bit_map,
             current_descriptor_index: 0,
-            current_address: 0,
+            current_address: PhysAddr::ZERO,
             free_memory,
             used_memory: 0,
             reserved_memory: 0,
         };
 
         // reserve frames for bitmap
-        instance.reserve_frames(
-            (&instance.bit_map) as *const BitMap as u64,
-            instance.bit_map.pages(),
-        )?;
+        let bit_map_start =
+            PhysFrame::containing_address(PhysAddr::new(instance.bit_map.buffer.as_ptr() as u64));
+        instance.reserve_frames(PhysFrame::range(
+            bit_map_start,
+            bit_map_start + instance.bit_map.pages() as u64,
+        ))?;
 
         // reserve reserved memory descriptors (including kernel code, data, stack)
         let mmap = instance.memory_map;
         mmap.descriptors()
             .iter()
             .filter(|desc| desc.r#type != MemoryType::Available)
-            .try_for_each(|desc| {
-                instance.reserve_frames(desc.phys_start, desc.num_pages as usize)
-            })?;
+            .try_for_each(|desc| instance.reserve_frames(desc.frames()))?;
 
         Ok(instance)
     }
 
+    /// Restores an allocator from the state of a previous allocator, e.g. the loader's allocator, without re-scanning the memory map.
+    ///
+    /// # Safety
+    /// The memory map descriptors and the bitmap referenced by `state` must be valid, mapped and not used by anything else.
+    pub unsafe fn from_state(memory_map: MemoryMap, state: BitMapAllocatorState) -> Self {
+        let buffer = unsafe {
+            slice::from_raw_parts_mut(state.bit_map.as_mut_ptr(), state.bit_map_size as usize)
+        };
+
+        Self {
+            memory_map,
+            bit_map: BitMap { buffer },
+            current_descriptor_index: state.current_descriptor_index as usize,
+            current_address: state.current_address,
+            free_memory: state.free_memory,
+            used_memory: state.used_memory,
+            reserved_memory: state.reserved_memory,
+        }
+    }
+
+    /// Returns the current state, so that another allocator can continue allocating from it
+    pub fn state(&self) -> BitMapAllocatorState {
+        BitMapAllocatorState {
+            bit_map: VirtAddr::from_ptr(self.bit_map.buffer.as_ptr()),
+            bit_map_size: self.bit_map.buffer.len() as u64,
+            current_descriptor_index: self.current_descriptor_index as u64,
+            current_address: self.current_address,
+            free_memory: self.free_memory,
+            used_memory: self.used_memory,
+            reserved_memory: self.reserved_memory,
+        }
+    }
+
     /// Returns the amount of free memory in bytes
     pub fn free_memory(&self) -> u64 {
         self.free_memory
@@ -97,23 +132,52 @@ impl BitMapAllocator<'_> {
     pub fn reserved_memory(&self) -> u64 {
         self.reserved_memory
     }
+
+    /// Returns whether `frame` is used or reserved
+    pub fn is_frame_used(&self, frame: PhysFrame) -> Result<bool, PageFrameAllocatorError> {
+        self.bit_map.get(frame_index(frame))
+    }
+
+    /// Releases all frames marked as [`MemoryType::LoaderReclaimable`] and makes them available for allocation. Returns the amount of reclaimed memory in bytes.
+    ///
+    /// Must only be called once the kernel no longer accesses any data provided by the loader outside of kernel data (e.g. loader log messages or the kernel file).
+    pub fn reclaim_loader_memory(&mut self) -> Result<u64, PageFrameAllocatorError> {
+        let reserved_memory = self.reserved_memory;
+
+        let mut mmap = self.memory_map;
+        for desc in mmap
+            .descriptors_mut()
+            .iter_mut()
+            .filter(|desc| desc.r#type == MemoryType::LoaderReclaimable)
+        {
+            self.free_reserved_frames(desc.frames())?;
+            desc.r#type = MemoryType::Available;
+        }
+
+        // descriptors might have been skipped as unavailable during previous requests
+        self.current_descriptor_index = 0;
+        self.current_address = PhysAddr::ZERO;
+
+        Ok(reserved_memory - self.reserved_memory)
+    }
 }
 
 impl<'a> PageFrameAllocator<'a, PageFrameAllocatorError> for BitMapAllocator<'a> {
     /// Returns any available free page
-    fn request_page(&mut self) -> Result<PhysicalAddress, PageFrameAllocatorError> {
+    fn request_page(&mut self) -> Result<PhysFrame, PageFrameAllocatorError> {
         for desc_index in self.current_descriptor_index..self.memory_map.descriptors().len() {
-            let desc = &self.memory_map.descriptors()[desc_index];
+            let desc = self.memory_map.descriptors()[desc_index];
             if desc.r#type == MemoryType::Available {
-                for addr in
-                    (self.current_address.max(desc.phys_start)..desc.phys_end).step_by(PAGE_SIZE)
-                {
-                    let index = addr / PAGE_SIZE as u64;
-                    if !self.bit_map.get(index)? {
-                        self.allocate_frame(addr)?;
+                let frames = PhysFrame::range(
+                    PhysFrame::containing_address(self.current_address.max(desc.phys_start)),
+                    desc.frames().end,
+                );
+                for frame in frames {
+                    if !self.bit_map.get(frame_index(frame))? {
+                        self.allocate_frame(frame)?;
                         self.current_descriptor_index = desc_index;
-                        self.current_address = addr + PAGE_SIZE as u64;
-                        return Ok(addr);
+                        self.current_address = (frame + 1).start_address();
+                        return Ok(frame);
                     }
                 }
             }
@@ -121,7 +185,7 @@ impl<'a> PageFrameAllocator<'a, PageFrameAllocatorError> for BitMapAllocator<'a>
         }
         // If no free page is found, start from the beginning next time
         self.current_descriptor_index = 0;
-        self.current_address = 0;
+        self.current_address = PhysAddr::ZERO;
         // todo: page frame swap
         Err(PageFrameAllocatorError::NoMoreFreePages)
     }
@@ -129,11 +193,8 @@ impl<'a> PageFrameAllocator<'a, PageFrameAllocatorError> for BitMapAllocator<'a>
 
 impl BitMapAllocator<'_> {
     // either allocates frame or does nothing if it is already free
-    pub fn allocate_frame(
-        &mut self,
-        address: PhysicalAddress,
-    ) -> Result<(), PageFrameAllocatorError> {
-        let index = address / PAGE_SIZE as u64;
+    pub fn allocate_frame(&mut self, frame: PhysFrame) -> Result<(), PageFrameAllocatorError> {
+        let index = frame_index(frame);
         if self.bit_map.get(index)? {
             return Ok(());
         }
@@ -147,22 +208,18 @@ impl BitMapAllocator<'_> {
 
     pub fn allocate_frames(
         &mut self,
-        start_address: PhysicalAddress,
-        page_count: usize,
+        frames: PhysFrameRange,
     ) -> Result<(), PageFrameAllocatorError> {
-        for i in 0..page_count {
-            self.allocate_frame(start_address + (i * PAGE_SIZE) as u64)?;
+        for frame in frames {
+            self.allocate_frame(frame)?;
         }
 
         Ok(())
     }
 
     // either frees frame or does nothing if it is already free
-    pub fn free_frame(
-        &mut self,
-        address: PhysicalAddress,
-    ) -> Result<(), PageFrameAllocatorError> {
-        let index = address / PAGE_SIZE as u64;
+    pub fn free_frame(&mut self, frame: PhysFrame) -> Result<(), PageFrameAllocatorError> {
+        let index = frame_index(frame);
         if !self.bit_map.get(index)? {
             return Ok(());
         }
@@ -174,24 +231,17 @@ impl BitMapAllocator<'_> {
         Ok(())
     }
 
-    pub fn free_frames(
-        &mut self,
-        start_address: PhysicalAddress,
-        page_count: usize,
-    ) -> Result<(), PageFrameAllocatorError> {
-        for i in 0..page_count {
-            self.free_frame(start_address + (i * PAGE_SIZE) as u64)?;
+    pub fn free_frames(&mut self, frames: PhysFrameRange) -> Result<(), PageFrameAllocatorError> {
+        for frame in frames {
+            self.free_frame(frame)?;
         }
 
         Ok(())
     }
 
     // either reserves frame or does nothing if it is already free
-    pub fn reserve_frame(
-        &mut self,
-        address: PhysicalAddress,
-    ) -> Result<(), PageFrameAllocatorError> {
-        let index = address / PAGE_SIZE as u64;
+    pub fn reserve_frame(&mut self, frame: PhysFrame) -> Result<(), PageFrameAllocatorError> {
+        let index = frame_index(frame);
         if self.bit_map.get(index)? {
             return Ok(());
         }
@@ -205,46 +255,66 @@ impl BitMapAllocator<'_> {
 
     pub fn reserve_frames(
         &mut self,
-        start_address: PhysicalAddress,
-        page_count: usize,
+        frames: PhysFrameRange,
     ) -> Result<(), PageFrameAllocatorError> {
-        for i in 0..page_count {
-            self.reserve_frame(start_address + (i * PAGE_SIZE) as u64)?;
+        for frame in frames {
+            self.reserve_frame(frame)?;
         }
 
         Ok(())
     }
 
     // either frees reserved frame or does nothing if it is already free
-    pub fn free_reserved_frame(
-        &mut self,
-        address: PhysicalAddress,
-    ) -> Result<(), PageFrameAllocatorError> {
-        let index = address / PAGE_SIZE as u64;
+    pub fn free_reserved_frame(&mut self, frame: PhysFrame) -> Result<(), PageFrameAllocatorError> {
+        let index = frame_index(frame);
         if !self.bit_map.get(index)? {
             return Ok(());
         }
 
+        // the bitmap does not tell reserved and allocated frames apart, so at least the counter must not underflow
+        let reserved_memory = self
+            .reserved_memory
+            .checked_sub(PAGE_SIZE as u64)
+            .ok_or(PageFrameAllocatorError::FrameNotReserved)?;
         self.bit_map.set(index, false)?;
         self.free_memory += PAGE_SIZE as u64;
-        self.reserved_memory -= PAGE_SIZE as u64;
+        self.reserved_memory = reserved_memory;
 
         Ok(())
     }
 
     pub fn free_reserved_frames(
         &mut self,
-        start_address: PhysicalAddress,
-        page_count: usize,
+        frames: PhysFrameRange,
     ) -> Result<(), PageFrameAllocatorError> {
-        for i in 0..page_count {
-            self.free_reserved_frame(start_address + (i * PAGE_SIZE) as u64)?;
+        for frame in frames {
+            self.free_reserved_frame(frame)?;
         }
 
         Ok(())
     }
 }
 
+/// State of a [`BitMapAllocator`] passed between loader and kernel
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct BitMapAllocatorState {
+    /// Address of the bitmap buffer
+    pub bit_map: VirtAddr,
+    /// Size of the bitmap buffer in bytes
+    pub bit_map_size: u64,
+    pub current_descriptor_index: u64,
+    pub current_address: PhysAddr,
+    pub free_memory: u64,
+    pub used_memory: u64,
+    pub reserved_memory: u64,
+}
+
+/// Index of `frame` in the bitmap
+fn frame_index(frame: PhysFrame) -> u64 {
+    frame.start_address().as_u64() / PAGE_SIZE as u64
+}
+
 /// Returns total amount of available memory in bytes based on memory map.
 pub fn total_available_memory(mmap: &MemoryMap) -> u64 {
     mmap.descriptors()
@@ -254,11 +324,13 @@ pub fn total_available_memory(mmap: &MemoryMap) -> u64 {
         .sum()
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum PageFrameAllocatorError {
     InvalidBitMapIndex,
     InvalidMemoryMap,
     NoMoreFreePages,
+    /// A frame is freed as reserved, although no memory is reserved
+    FrameNotReserved,
 }
 
 impl Display for PageFrameAllocatorError {
@@ -268,3 +340,105 @@ impl Display for PageFrameAllocatorError {
 }
 
 impl Error for PageFrameAllocatorError {}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use crate::memory::MemoryDescriptor;
+
+    use super::*;
+
+    const PAGE: u64 = PAGE_SIZE as u64;
+
+    fn descriptor(phys_start: u64, num_pages: u64, r#type: MemoryType) -> MemoryDescriptor {
+        MemoryDescriptor {
+            phys_start: PhysAddr::new(phys_start),
+            phys_end: PhysAddr::new(phys_start + num_pages * PAGE),
+            num_pages,
+            r#type,
+        }
+    }
+
+    #[test]
+    fn reclaims_loader_memory_only() {
+        let mut descriptors = [
+            descriptor(0x0, 4, MemoryType::Available),
+            descriptor(0x4000, 2, MemoryType::KernelData),
+            descriptor(0x6000, 3, MemoryType::LoaderReclaimable),
+            descriptor(0x9000, 1, MemoryType::KernelStack),
+        ];
+        let memory_map = MemoryMap {
+            descriptors: descriptors.as_mut_ptr(),
+            descriptors_len: descriptors.len() as u64,
+            first_addr: 0x0,
+            last_addr: 0xA000,
+            first_available_addr: 0x0,
+            last_available_addr: 0x4000,
+        };
+        let mut bit_map = vec![0u8; 2];
+        let mut allocator = unsafe {
+            BitMapAllocator::from_state(
+                memory_map,
+                BitMapAllocatorState {
+                    bit_map: VirtAddr::from_ptr(bit_map.as_mut_ptr()),
+                    bit_map_size: bit_map.len() as u64,
+                    // reserving frames is accounted as free memory becoming reserved
+                    free_memory: memory_map.last_addr,
+                    ..Default::default()
+                },
+            )
+        };
+        // as done by `try_new` for all descriptors, which are not available
+        for desc in &descriptors[1..] {
+            allocator.reserve_frames(desc.frames()).unwrap();
+        }
+        let frame = allocator.request_page().unwrap();
+        assert_eq!(allocator.reserved_memory(), 6 * PAGE);
+
+        assert_eq!(allocator.reclaim_loader_memory(), Ok(3 * PAGE));
+        assert_eq!(allocator.reserved_memory(), 3 * PAGE);
+        assert_eq!(allocator.free_memory(), 6 * PAGE);
+        assert_eq!(descriptors[2].r#type, MemoryType::Available);
+        for desc in &descriptors[1..] {
+            let used = desc.r#type != MemoryType::Available;
+            assert!(desc
+                .frames()
+                .all(|frame| allocator.is_frame_used(frame) == Ok(used)));
+        }
+        assert_eq!(allocator.is_frame_used(frame), Ok(true));
+    }
+
+    #[test]
+    fn does_not_free_allocated_frame_as_reserved() {
+        let mut descriptors = [descriptor(0x0, 8, MemoryType::Available)];
+        let memory_map = MemoryMap {
+            descriptors: descriptors.as_mut_ptr(),
+            descriptors_len: descriptors.len() as u64,
+            first_addr: 0x0,
+            last_addr: 0x8000,
+            first_available_addr: 0x0,
+            last_available_addr: 0x8000,
+        };
+        let mut bit_map = vec![0u8; 1];
+        let mut allocator = unsafe {
+            BitMapAllocator::from_state(
+                memory_map,
+                BitMapAllocatorState {
+                    bit_map: VirtAddr::from_ptr(bit_map.as_mut_ptr()),
+                    bit_map_size: bit_map.len() as u64,
+                    free_memory: 8 * PAGE,
+                    ..Default::default()
+                },
+            )
+        };
+
+        let frame = allocator.request_page().unwrap();
+        assert_eq!(
+            allocator.free_reserved_frame(frame),
+            Err(PageFrameAllocatorError::FrameNotReserved)
+        );
+        assert_eq!(allocator.is_frame_used(frame), Ok(true));
+        assert_eq!(allocator.reserved_memory(), 0);
+    }
+}
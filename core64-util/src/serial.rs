@@ -0,0 +1,88 @@
+use core::{arch::asm, fmt};
+
+/// I/O port base of the first serial port
+pub const COM1: u16 = 0x3F8;
+
+const DATA: u16 = 0;
+const INTERRUPT_ENABLE: u16 = 1;
+const FIFO_CONTROL: u16 = 2;
+const LINE_CONTROL: u16 = 3;
+const MODEM_CONTROL: u16 = 4;
+const LINE_STATUS: u16 = 5;
+
+/// Divisor latch access bit of the line control register
+const LINE_CONTROL_DLAB: u8 = 1 << 7;
+/// 8 data bits, no parity, one stop bit
+const LINE_CONTROL_8N1: u8 = 0b11;
+/// Transmitter holding register empty bit of the line status register
+const LINE_STATUS_THR_EMPTY: u8 = 1 << 5;
+/// Divisor of the 115200 Hz base clock, resulting in 115200 baud
+const BAUD_RATE_DIVISOR: u16 = 1;
+/// Polls before a byte is dropped, so that logging never hangs on a missing port
+const TRANSMIT_TIMEOUT: usize = 100_000;
+
+/// Polled 16550 UART. Does not use interrupts, so it can be used without any further set up (e.g. after boot services have been exited or in a panic handler).
+#[derive(Copy, Clone, Debug)]
+pub struct SerialPort {
+    base: u16,
+}
+
+impl SerialPort {
+    /// # Safety
+    /// `base` must be the I/O port base of a 16550 compatible UART, which is not concurrently used by anyone else.
+    pub const unsafe fn new(base: u16) -> Self {
+        Self { base }
+    }
+
+    /// Configures the port for 115200 baud 8N1 with enabled FIFOs and disabled interrupts
+    pub fn init(&mut self) {
+        unsafe {
+            self.write_register(INTERRUPT_ENABLE, 0x00);
+            self.write_register(LINE_CONTROL, LINE_CONTROL_DLAB);
+            self.write_register(DATA, BAUD_RATE_DIVISOR as u8);
+            self.write_register(INTERRUPT_ENABLE, (BAUD_RATE_DIVISOR >> 8) as u8);
+            self.write_register(LINE_CONTROL, LINE_CONTROL_8N1);
+            // enable and clear FIFOs, 14 byte threshold
+            self.write_register(FIFO_CONTROL, 0xC7);
+            // data terminal ready, request to send, out2
+            self.write_register(MODEM_CONTROL, 0x0B);
+        }
+    }
+
+    /// Blocks until the transmitter is ready and sends `byte`
+    pub fn write_byte(&mut self, byte: u8) {
+        for _ in 0..TRANSMIT_TIMEOUT {
+            if unsafe { self.read_register(LINE_STATUS) } & LINE_STATUS_THR_EMPTY != 0 {
+                unsafe { self.write_register(DATA, byte) };
+                return;
+            }
+            core::hint::spin_loop();
+        }
+    }
+
+    unsafe fn write_register(&self, register: u16, value: u8) {
+        unsafe {
+            asm!("out dx, al", in("dx") self.base + register, in("al") value, options(nomem, nostack, preserves_flags));
+        }
+    }
+
+    unsafe fn read_register(&self, register: u16) -> u8 {
+        let value: u8;
+        unsafe {
+            asm!("in al, dx", out("al") value, in("dx") self.base + register, options(nomem, nostack, preserves_flags));
+        }
+        value
+    }
+}
+
+impl fmt::Write for SerialPort {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for byte in s.bytes() {
+            if byte == b'\n' {
+                self.write_byte(b'\r');
+            }
+            self.write_byte(byte);
+        }
+        Ok(())
+    }
+}